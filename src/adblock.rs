@@ -0,0 +1,599 @@
+
+use headless_chrome as chrome;
+use chrome::browser::tab::RequestPausedDecision;
+use chrome::protocol::cdp::{Fetch, Network};
+use super::events::{EtymonEvent, BrowserEvent, EventSender};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Network filters follow the Adblock Plus/uBlock Origin syntax used by EasyList:
+//   ||ads.example.com^     block a domain and its subdomains
+//   |https://track.        block URLs starting with the text
+//   /banner/*.gif|         block URLs containing a pattern, ending the URL
+//   @@||example.com/ads^   exception that lets matching requests through
+//   ...$third-party,script,domain=a.com|~b.com   options
+// Element hiding (##), regex and scriptlet filters are not supported and are skipped.
+
+/// Blocking and exception filters parsed from one or more filter lists.
+#[derive(Default)]
+pub struct FilterList {
+    /// Filters anchored to a complete domain, indexed by that domain.
+    domain_filters: HashMap<String, Vec<NetworkFilter>>,
+    /// Filters that have to be checked against every request.
+    filters: Vec<NetworkFilter>,
+    /// Exception filters, which take priority over blocking filters.
+    exceptions: Vec<NetworkFilter>,
+} impl FilterList {
+
+    /// Reads and combines filter lists from files.
+    pub fn load(paths: &[std::path::PathBuf]) -> Result<FilterList, anyhow::Error> {
+        let mut list = FilterList::default();
+        for path in paths {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read filter list {}: {}", path.display(), e))?;
+            list.add_rules(&text);
+        }
+        Ok(list)
+    }
+
+    /// Parses filter list text, skipping comments and unsupported rules.
+    pub fn parse(text: &str) -> FilterList {
+        let mut list = FilterList::default();
+        list.add_rules(text);
+        list
+    }
+
+    fn add_rules(&mut self, text: &str) {
+        for line in text.lines().map(str::trim) {
+            let (line, exception) = match line.strip_prefix("@@") {
+                Some(rule) => (rule, true),
+                None => (line, false),
+            };
+            let Some(filter) = NetworkFilter::parse(line) else { continue };
+            match (exception, filter.domain_key()) {
+                (true, _) => self.exceptions.push(filter),
+                (false, Some(domain)) => self.domain_filters.entry(domain).or_default().push(filter),
+                (false, None) => self.filters.push(filter),
+            }
+        }
+    }
+
+    /// Number of filters in the list.
+    pub fn len(&self) -> usize {
+        self.domain_filters.values().map(Vec::len).sum::<usize>() + self.filters.len() + self.exceptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// True if a request for `url`, made by the page at `page_url`, should be blocked.
+    pub fn should_block(&self, url: &str, page_url: &str, kind: RequestKind) -> bool {
+        let Some(request) = Request::new(url, page_url, kind) else { return false };
+
+        let mut domain = request.host.as_str();
+        let mut blocked = false;
+        loop {
+            if let Some(filters) = self.domain_filters.get(domain) {
+                blocked = filters.iter().any(|f| f.matches(&request));
+            }
+            if blocked { break }
+            match domain.split_once('.') {
+                Some((_, parent)) if !parent.is_empty() => domain = parent,
+                _ => break,
+            }
+        }
+
+        blocked = blocked || self.filters.iter().any(|f| f.matches(&request));
+        blocked && !self.exceptions.iter().any(|f| f.matches(&request))
+    }
+
+    /// Decides whether chrome should send a paused request, failing it if the filters block it.
+    /// Documents loading in frames other than the main frame are subdocuments.
+    /// Requests from pages on allowlisted domains are always sent.
+    pub fn decide(&self, page_url: &str, main_frame: &str, event: &Fetch::events::RequestPausedEvent) -> RequestPausedDecision {
+        let params = &event.params;
+        let page_host = url::Url::parse(page_url).ok().and_then(|u| u.host_str().map(str::to_owned)).unwrap_or_default();
+        let allowlisted = crate::CONFIG.adblock_allowlist.iter().any(|d| crate::utils::host_matches(&page_host, d));
+
+        if !allowlisted && self.should_block(&params.request.url, page_url, RequestKind::of(&params.resource_Type, params.frame_id == main_frame)) {
+            RequestPausedDecision::Fail(Fetch::FailRequest {
+                request_id: params.request_id.clone(),
+                error_reason: Network::ErrorReason::BlockedByClient,
+            })
+        } else {
+            RequestPausedDecision::Continue(None)
+        }
+    }
+}
+
+/// Intercepts every request made by a chrome tab, failing those blocked by the filters
+/// and reporting each one to the main loop.
+pub fn block_requests(tab: &chrome::Tab, filters: Arc<FilterList>, events: EventSender) -> Result<(), anyhow::Error> {
+    let interceptor = Interceptor::new(tab.get_target_id(), filters, events);
+    tab.enable_request_interception(Arc::new(move |_transport, _session, event: Fetch::events::RequestPausedEvent| {
+        interceptor.intercept(&event)
+    }))?;
+
+    let every_request = Fetch::RequestPattern {
+        url_pattern: Some("*".to_owned()),
+        resource_Type: None,
+        request_stage: Some(Fetch::RequestStage::Request),
+    };
+    tab.enable_fetch(Some(&[every_request]), None)?;
+    Ok(())
+}
+
+
+/// Judges the requests made by a chrome tab, keeping track of the page in its main frame.
+struct Interceptor {
+    /// Target id of the tab, which is also the id of its main frame.
+    tab_id: String,
+    filters: Arc<FilterList>,
+    page_url: Mutex<String>,
+    events: EventSender,
+} impl Interceptor {
+
+    fn new(tab_id: &str, filters: Arc<FilterList>, events: EventSender) -> Interceptor {
+        Interceptor { tab_id: tab_id.to_owned(), filters, page_url: Mutex::new(String::new()), events }
+    }
+
+    /// Decides on a paused request, reporting it to the main loop if it is blocked.
+    fn intercept(&self, event: &Fetch::events::RequestPausedEvent) -> RequestPausedDecision {
+        let params = &event.params;
+        let Ok(mut page) = self.page_url.lock() else { return RequestPausedDecision::Continue(None) };
+        // Main frame documents are checked against the page they replace, and become the page for what follows.
+        // Frames loading within the page leave it as it is.
+        let current_page = page.clone();
+        if params.resource_Type == Network::ResourceType::Document && params.frame_id == self.tab_id {
+            *page = params.request.url.clone();
+        }
+
+        let decision = self.filters.decide(&current_page, &self.tab_id, event);
+        if let RequestPausedDecision::Fail(_) = decision {
+            let blocked = BrowserEvent::Blocked { tab_id: self.tab_id.clone(), url: params.request.url.clone() };
+            let _closed = self.events.send(EtymonEvent::Browser(blocked));
+        }
+        decision
+    }
+}
+
+
+/// Types of request that filter options can be restricted to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RequestKind {
+    Document, Subdocument, Script, Image, Stylesheet, Font, Media, XmlHttpRequest, WebSocket, Ping, Other
+} impl RequestKind {
+
+    /// Maps chrome's resource type to the filter option for it, telling frames from the main document.
+    pub fn of(resource: &Network::ResourceType, main_frame: bool) -> Self {
+        use Network::ResourceType::*;
+        match resource {
+            Document if main_frame => Self::Document,
+            Document => Self::Subdocument,
+            Script => Self::Script,
+            Image => Self::Image,
+            Stylesheet => Self::Stylesheet,
+            Font => Self::Font,
+            Media | TextTrack => Self::Media,
+            Xhr | Fetch | EventSource => Self::XmlHttpRequest,
+            WebSocket => Self::WebSocket,
+            Ping | CspViolationReport => Self::Ping,
+            _ => Self::Other,
+        }
+    }
+
+    /// Reads a filter option naming a request type.
+    fn from_option(option: &str) -> Option<Self> {
+        Some(match option {
+            "document" | "doc" => Self::Document,
+            "subdocument" | "frame" => Self::Subdocument,
+            "script" => Self::Script,
+            "image" => Self::Image,
+            "stylesheet" | "css" => Self::Stylesheet,
+            "font" => Self::Font,
+            "media" => Self::Media,
+            "xmlhttprequest" | "xhr" => Self::XmlHttpRequest,
+            "websocket" => Self::WebSocket,
+            "ping" | "beacon" => Self::Ping,
+            "other" => Self::Other,
+            _ => return None,
+        })
+    }
+}
+
+
+/// A request as seen by the filters.
+struct Request {
+    /// URL in lower case, as filters are case-insensitive by default.
+    url: String,
+    /// URL as it was given, for filters with the match-case option.
+    original: String,
+    /// Byte range of the host within the URL.
+    host_range: (usize, usize),
+    host: String,
+    page_host: String,
+    kind: RequestKind,
+} impl Request {
+
+    /// Reads a request from its URL as parsed, so the host's range is taken from the same text that is matched.
+    fn new(url: &str, page_url: &str, kind: RequestKind) -> Option<Request> {
+        use url::Position;
+        let parsed = url::Url::parse(url).ok()?;
+        let host = parsed.host_str()?.to_ascii_lowercase();
+        let page_host = url::Url::parse(page_url).ok().and_then(|u| u.host_str().map(str::to_ascii_lowercase)).unwrap_or_default();
+        let host_range = (parsed[..Position::BeforeHost].len(), parsed[..Position::AfterHost].len());
+        Some(Request {
+            url: parsed.as_str().to_ascii_lowercase(),
+            original: parsed.as_str().to_owned(),
+            host_range,
+            host, page_host, kind,
+        })
+    }
+
+    /// Requests to a different site than the page are third-party.
+    fn third_party(&self) -> bool {
+        self.page_host.is_empty() || site(&self.host) != site(&self.page_host)
+    }
+}
+
+/// Common public suffixes of more than one label, under which each domain is a separate site.
+/// This is only part of the public suffix list: a site under a suffix missing here, like "example.gov.au",
+/// is taken to be the suffix's, so requests between sites under it are seen as first-party.
+const PUBLIC_SUFFIXES: [&str; 24] = [
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "me.uk", "ltd.uk", "com.au", "net.au", "org.au", "co.nz",
+    "co.jp", "ne.jp", "or.jp", "com.br", "com.cn", "com.tw", "com.hk", "co.in", "co.kr", "co.za",
+    "com.mx", "com.tr", "github.io", "blogspot.com",
+];
+
+/// The site a host belongs to: its registrable domain, one label under its public suffix.
+fn site(host: &str) -> &str {
+    let labels = match PUBLIC_SUFFIXES.iter().any(|s| crate::utils::host_matches(host, s) && host.len() > s.len()) {
+        true => 3,
+        false => 2,
+    };
+    match host.rmatch_indices('.').nth(labels - 1) {
+        Some((dot, _)) => &host[dot + 1..],
+        None => host,
+    }
+}
+
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    /// `*` matches any run of characters.
+    Wildcard,
+    /// `^` matches a separator character or the end of the URL.
+    Separator,
+}
+
+/// A single blocking or exception rule.
+struct NetworkFilter {
+    tokens: Vec<Token>,
+    /// `||` anchors the pattern to the start of the host or one of its subdomains.
+    domain_anchor: bool,
+    /// A leading `|` anchors the pattern to the start of the URL.
+    start_anchor: bool,
+    /// A trailing `|` anchors the pattern to the end of the URL.
+    end_anchor: bool,
+    match_case: bool,
+    third_party: Option<bool>,
+    /// Page domains the filter is limited to, and those it excludes.
+    domains: Vec<String>,
+    excluded_domains: Vec<String>,
+    /// Request types the filter is limited to, and those it excludes.
+    kinds: Vec<RequestKind>,
+    excluded_kinds: Vec<RequestKind>,
+} impl NetworkFilter {
+
+    /// Parses a filter rule, without its exception prefix.
+    /// Returns None for comments, element hiding rules and anything unsupported.
+    fn parse(rule: &str) -> Option<NetworkFilter> {
+        if rule.is_empty() || rule.starts_with('!') || rule.starts_with('[') { return None }
+        if rule.contains("##") || rule.contains("#@#") || rule.contains("#?#") || rule.contains("#$#") { return None }
+
+        let (pattern, options) = match rule.rfind('$') {
+            Some(i) => (&rule[..i], Some(&rule[i + 1..])),
+            None => (rule, None),
+        };
+        // Regular expression filters are not supported.
+        if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') { return None }
+
+        let mut filter = NetworkFilter {
+            tokens: Vec::new(), domain_anchor: false, start_anchor: false, end_anchor: false, match_case: false,
+            third_party: None, domains: Vec::new(), excluded_domains: Vec::new(), kinds: Vec::new(), excluded_kinds: Vec::new(),
+        };
+        for option in options.unwrap_or_default().split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            let (negated, name) = match option.strip_prefix('~') { Some(o) => (true, o), None => (false, option) };
+            match name {
+                "third-party" | "3p" => filter.third_party = Some(!negated),
+                "first-party" | "1p" => filter.third_party = Some(negated),
+                "match-case" => filter.match_case = true,
+                "important" | "all" => (),
+                _ if name.starts_with("domain=") => for domain in name["domain=".len()..].split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(d) => filter.excluded_domains.push(d.to_ascii_lowercase()),
+                        None => filter.domains.push(domain.to_ascii_lowercase()),
+                    }
+                },
+                _ => match (RequestKind::from_option(name), negated) {
+                    (Some(kind), false) => filter.kinds.push(kind),
+                    (Some(kind), true) => filter.excluded_kinds.push(kind),
+                    // Options that change what happens to a request, like redirects, aren't supported.
+                    (None, _) => return None,
+                },
+            }
+        }
+
+        let mut pattern = pattern;
+        if let Some(p) = pattern.strip_prefix("||") { filter.domain_anchor = true; pattern = p }
+        else if let Some(p) = pattern.strip_prefix('|') { filter.start_anchor = true; pattern = p }
+        if let Some(p) = pattern.strip_suffix('|') { filter.end_anchor = true; pattern = p }
+
+        let mut literal = String::new();
+        for ch in pattern.chars() {
+            let token = match ch { '*' => Token::Wildcard, '^' => Token::Separator, _ => { literal.push(ch); continue } };
+            if !literal.is_empty() { filter.tokens.push(Token::Literal(std::mem::take(&mut literal))) }
+            if !(token == Token::Wildcard && filter.tokens.last() == Some(&Token::Wildcard)) { filter.tokens.push(token) }
+        }
+        if !literal.is_empty() { filter.tokens.push(Token::Literal(literal)) }
+        if !filter.match_case {
+            for token in filter.tokens.iter_mut() {
+                if let Token::Literal(l) = token { *l = l.to_ascii_lowercase() }
+            }
+        }
+        Some(filter)
+    }
+
+    /// The complete domain a `||domain^` filter is anchored to, for indexing.
+    fn domain_key(&self) -> Option<String> {
+        if !self.domain_anchor { return None }
+        match self.tokens.as_slice() {
+            [Token::Literal(domain)] if self.end_anchor => Some(domain.to_ascii_lowercase()),
+            [Token::Literal(domain), Token::Separator, ..] => Some(domain.to_ascii_lowercase()),
+            [Token::Literal(literal), ..] => literal.split_once('/')
+                .filter(|(domain, _)| !domain.is_empty())
+                .map(|(domain, _)| domain.to_ascii_lowercase()),
+            _ => None,
+        }
+        .filter(|domain| !domain.contains(':'))
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        if let Some(third_party) = self.third_party {
+            if request.third_party() != third_party { return false }
+        }
+        if !self.kinds.is_empty() && !self.kinds.contains(&request.kind) { return false }
+        // Top-level documents are only blocked by filters that ask for them.
+        if self.kinds.is_empty() && request.kind == RequestKind::Document { return false }
+        if self.excluded_kinds.contains(&request.kind) { return false }
+        let on_page = |d: &String| crate::utils::host_matches(&request.page_host, d);
+        if !self.domains.is_empty() && !self.domains.iter().any(on_page) { return false }
+        if self.excluded_domains.iter().any(on_page) { return false }
+
+        let url = match self.match_case { true => request.original.as_bytes(), false => request.url.as_bytes() };
+        if self.domain_anchor {
+            let (start, end) = request.host_range;
+            // The pattern can begin at the host or after any dot within it.
+            std::iter::once(start)
+                .chain((start..end).filter(|&i| url[i] == b'.').map(|i| i + 1))
+                .any(|at| self.matches_at(&self.tokens, url, at))
+        } else if self.start_anchor {
+            self.matches_at(&self.tokens, url, 0)
+        } else {
+            (0..=url.len()).any(|at| self.matches_at(&self.tokens, url, at))
+        }
+    }
+
+    fn matches_at(&self, tokens: &[Token], url: &[u8], at: usize) -> bool {
+        let Some((token, rest)) = tokens.split_first() else { return !self.end_anchor || at == url.len() };
+        match token {
+            Token::Literal(literal) => {
+                url[at..].starts_with(literal.as_bytes()) && self.matches_at(rest, url, at + literal.len())
+            },
+            Token::Separator => match url.get(at) {
+                None => self.matches_at(rest, url, at),
+                Some(&ch) => is_separator(ch) && self.matches_at(rest, url, at + 1),
+            },
+            Token::Wildcard => (at..=url.len()).any(|at| self.matches_at(rest, url, at)),
+        }
+    }
+}
+
+/// Characters that a `^` in a filter stands for.
+fn is_separator(ch: u8) -> bool {
+    !(ch.is_ascii_alphanumeric() || matches!(ch, b'_' | b'-' | b'.' | b'%'))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const FILTERS: &str = include_str!("../tests/fixtures/filters.txt");
+
+    #[test]
+    fn parses_supported_filters() {
+        let filters = FilterList::parse(FILTERS);
+        assert_eq!(filters.len(), 7);
+    }
+
+    #[test]
+    fn matches_filter_syntax() {
+        let filters = FilterList::parse(FILTERS);
+        let page = "https://news.example.com/story";
+        let blocked = |url: &str, kind| filters.should_block(url, page, kind);
+        use RequestKind::*;
+
+        assert!(blocked("https://ads.tracker.net/pixel.gif", Image));
+        assert!(blocked("https://cdn.ads.tracker.net/lib.js?id=1", Script));
+        assert!(!blocked("https://notads.tracker.net/lib.js", Script));
+        assert!(blocked("https://example.com/banner/top.gif", Image));
+        assert!(!blocked("https://example.com/banner/top.gif?size=2", Image));
+        assert!(blocked("https://stats.example.org/collect?v=1", XmlHttpRequest));
+        assert!(!blocked("https://stats.example.org/collect?v=1", Image));
+        assert!(blocked("https://widgets.social.io/share.js", Script));
+        assert!(!blocked("https://widgets.social.io/share.js", Image));
+        assert!(!filters.should_block("https://widgets.social.io/share.js", "https://www.social.io/", Script));
+        // Exception filter.
+        assert!(!blocked("https://ads.tracker.net/consent.js", Script));
+        // Top-level documents are left alone unless a filter asks for them.
+        assert!(!blocked("https://ads.tracker.net/", Document));
+        assert!(blocked("https://malware.test/", Document));
+        // domain= limits where the filter applies.
+        assert!(blocked("https://cdn.example.com/promo.js", Script));
+        assert!(!filters.should_block("https://cdn.example.com/promo.js", "https://shop.example.net/", Script));
+    }
+
+    /// Starts an HTTP server that answers every request with no content, and logs the path of each one.
+    fn stand_in_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        std::thread::spawn(move || for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() { continue }
+            // Read the rest of the headers, so the client has finished writing before the reply.
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok_and(|n| n > 2) { header.clear() }
+            let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_owned();
+            log.lock().unwrap().push(path);
+            let _ = (&stream).write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        });
+        (address, received)
+    }
+
+    /// Sends a GET request and waits for the response.
+    fn send_request(url: &str) {
+        let url = url::Url::parse(url).unwrap();
+        let mut stream = std::net::TcpStream::connect((url.host_str().unwrap(), url.port().unwrap())).unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", url.path(), url.host_str().unwrap());
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 204"));
+    }
+
+    /// Builds the event chrome sends when it pauses a request.
+    fn paused_request(id: &str, frame: &str, url: &str, resource_type: &str) -> Fetch::events::RequestPausedEvent {
+        serde_json::from_value(serde_json::json!({ "params": {
+            "requestId": id,
+            "frameId": frame,
+            "resourceType": resource_type,
+            "request": {
+                "url": url,
+                "method": "GET",
+                "headers": {},
+                "initialPriority": "Low",
+                "referrerPolicy": "strict-origin-when-cross-origin",
+            },
+        }})).unwrap()
+    }
+
+    #[test]
+    fn fails_blocked_requests() {
+        let filters = FilterList::parse("||cdn.example.com^$script\n/track/*\n@@/track/allowed\n");
+        let page = "https://example.com/index.html";
+        let requests = [
+            ("1", "https://cdn.example.com/app.js", "Script"),
+            ("2", "https://cdn.example.com/logo.png", "Image"),
+            ("3", "https://example.com/track/pixel.gif", "Image"),
+            ("4", "https://example.com/track/allowed.gif", "Image"),
+            ("5", "https://example.com/style.css", "Stylesheet"),
+        ];
+        let failed: Vec<String> = requests.iter().filter_map(|(id, url, kind)| match filters.decide(page, "main", &paused_request(id, "main", url, kind)) {
+            RequestPausedDecision::Fail(fail) => {
+                assert_eq!(fail.error_reason, Network::ErrorReason::BlockedByClient);
+                Some(fail.request_id)
+            },
+            RequestPausedDecision::Continue(None) => None,
+            _ => panic!("Request {} should be sent as it is or failed", id),
+        }).collect();
+        assert_eq!(failed, ["1", "3"]);
+    }
+
+    #[test]
+    fn blocks_frames_as_subdocuments() {
+        let filters = FilterList::parse("||doubleclick.net^\n||embeds.example^$subdocument\n");
+        let page = "https://example.com/";
+        let failed = |id, frame, url| matches!(filters.decide(page, "main", &paused_request(id, frame, url, "Document")), RequestPausedDecision::Fail(_));
+        assert!(failed("1", "ad-frame", "https://ad.doubleclick.net/frame.html"));
+        assert!(failed("2", "embed-frame", "https://embeds.example/player"));
+        // The page itself is left alone, unless a filter asks for documents.
+        assert!(!failed("3", "main", "https://ad.doubleclick.net/"));
+        assert!(!failed("4", "main", "https://embeds.example/player"));
+    }
+
+    #[test]
+    fn blocked_requests_never_reach_the_server() {
+        let (server, received) = stand_in_server();
+        let (events, _received_events) = tokio::sync::mpsc::unbounded_channel();
+        let filters = Arc::new(FilterList::parse("/app.js$script\n/track/*\n@@/track/allowed\n/ads/frame.html$subdocument\n"));
+        let interceptor = Interceptor::new("main", filters, events);
+
+        let requests = [
+            ("1", "main", "/index.html", "Document"),
+            ("2", "main", "/app.js", "Script"),
+            ("3", "main", "/logo.png", "Image"),
+            ("4", "main", "/track/pixel.gif", "Image"),
+            ("5", "main", "/track/allowed.gif", "Image"),
+            ("6", "ad", "/ads/frame.html", "Document"),
+            ("7", "main", "/style.css", "Stylesheet"),
+        ];
+        for (id, frame, path, kind) in requests {
+            let url = format!("{}{}", server, path);
+            match interceptor.intercept(&paused_request(id, frame, &url, kind)) {
+                // Continue as chrome would, by sending the request.
+                RequestPausedDecision::Continue(None) => send_request(&url),
+                RequestPausedDecision::Fail(_) => (),
+                _ => panic!("Request {} should be sent as it is or failed", id),
+            }
+        }
+        let received = received.lock().unwrap();
+        assert_eq!(*received, ["/index.html", "/logo.png", "/track/allowed.gif", "/style.css"]);
+    }
+
+    #[test]
+    fn judges_requests_from_the_main_frame_page() {
+        let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+        let filters = Arc::new(FilterList::parse("||tracker.example^$domain=news.example\n"));
+        let interceptor = Interceptor::new("main", filters, events);
+        let requests = [
+            ("1", "main", "https://news.example/", "Document"),
+            ("2", "embed", "https://video.example/player", "Document"),
+            ("3", "main", "https://tracker.example/t.js", "Script"),
+        ];
+        let failed: Vec<&str> = requests.iter()
+            .filter(|(id, frame, url, kind)| matches!(interceptor.intercept(&paused_request(id, frame, url, kind)), RequestPausedDecision::Fail(_)))
+            .map(|(id, ..)| *id)
+            .collect();
+        // The embedded player doesn't become the page the tracker is judged from.
+        assert_eq!(failed, ["3"]);
+        assert_eq!(*interceptor.page_url.lock().unwrap(), "https://news.example/");
+        match received.try_recv() {
+            Ok(EtymonEvent::Browser(BrowserEvent::Blocked { tab_id, url })) => assert_eq!((tab_id.as_str(), url.as_str()), ("main", "https://tracker.example/t.js")),
+            _ => panic!("The blocked request should be reported"),
+        }
+    }
+
+    #[test]
+    fn tells_third_party_requests_by_site() {
+        let filters = FilterList::parse("||tracker.co.uk^$third-party\n||stats.example^$third-party\n");
+        use RequestKind::Script;
+        assert!(!filters.should_block("https://cdn.tracker.co.uk/t.js", "https://www.tracker.co.uk/", Script));
+        assert!(filters.should_block("https://cdn.tracker.co.uk/t.js", "https://www.bbc.co.uk/", Script));
+        assert_eq!((site("news.bbc.co.uk"), site("co.uk"), site("a.b.example.com")), ("bbc.co.uk", "co.uk", "example.com"));
+        // Suffixes missing from the short list are taken as a site of their own.
+        assert_eq!(site("www.health.gov.au"), "gov.au");
+        assert!(!filters.should_block("https://stats.example/t.js", "https://stats.example/", Script));
+
+        // Hosts are found in the URL as parsed, however the URL was written.
+        let request = Request::new("https://user:p@ss@EXAMPLE.com:8080/a", "", Script).unwrap();
+        let (start, end) = request.host_range;
+        assert_eq!(&request.url[start..end], "example.com");
+    }
+}
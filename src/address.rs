@@ -0,0 +1,126 @@
+
+use super::history::{Bookmark, History};
+
+/// Most completions offered at once in the command line.
+pub const MAX_COMPLETIONS: usize = 5;
+/// Score a bookmark adds to a completion, about that of a page visited once in the last few days.
+const BOOKMARK_SCORE: f64 = 100.0;
+/// Score an open tab adds to a completion.
+const TAB_SCORE: f64 = 50.0;
+
+/// Turns text typed as an address into a URL, or None if it is a search.
+/// Bare hosts are opened with https, or http for the local machine; paths are opened as files,
+/// and addresses with a scheme, like file:// and about: pages, are kept as they are.
+pub fn normalize(input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.is_empty() { return None }
+
+    let local = match input {
+        _ if input.starts_with('/') => Some(std::path::PathBuf::from(input)),
+        _ if input == "~" || input.starts_with("~/") => Some(crate::utils::expand_home(input)),
+        _ if input.starts_with("./") || input.starts_with("../") => std::env::current_dir().ok().map(|dir| dir.join(input)),
+        _ => None,
+    };
+    if let Some(path) = local { return url::Url::from_file_path(path).ok().map(String::from) }
+
+    let schemed = ["about:", "file:", "data:"].iter().any(|s| input.get(..s.len()).is_some_and(|p| p.eq_ignore_ascii_case(s)));
+    if input.contains("://") || schemed { return url::Url::parse(input).ok().map(String::from) }
+    if crate::search::is_search(input) { return None }
+
+    let host = input.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(host, |(host, _)| host);
+    let scheme = match host.eq_ignore_ascii_case("localhost") || host.starts_with("127.") {
+        true => "http",
+        false => "https",
+    };
+    url::Url::parse(&format!("{}://{}", scheme, input)).ok().map(String::from)
+}
+
+/// Where a completion was found.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompletionSource { Tab, Bookmark, History }
+
+/// A page offered while typing an address.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Completion {
+    pub url: String,
+    pub title: String,
+    /// Where it was found, taking an open tab over a bookmark over history.
+    pub source: CompletionSource,
+    pub score: f64,
+}
+
+/// Finds pages whose URL or title has every word typed, among open tabs, bookmarks and history.
+/// Pages are ranked by the frecency of their visits, with more for bookmarks and open tabs.
+pub fn complete(input: &str, tabs: &[(String, String)], bookmarks: &[Bookmark], history: &History, now: u64) -> Vec<Completion> {
+    let words: Vec<String> = input.split_whitespace().map(str::to_lowercase).collect();
+    if words.is_empty() { return Vec::new() }
+    let matches = |url: &str, title: &str| {
+        let (url, title) = (url.to_lowercase(), title.to_lowercase());
+        words.iter().all(|w| url.contains(w.as_str()) || title.contains(w.as_str()))
+    };
+
+    let mut found: Vec<Completion> = Vec::new();
+    let mut add = |url: &str, title: &str, source: CompletionSource, score: f64| {
+        match found.iter_mut().find(|c| c.url == url) {
+            Some(completion) => {
+                completion.score += score;
+                if completion.title.is_empty() { completion.title = title.to_owned() }
+            },
+            None => found.push(Completion { url: url.to_owned(), title: title.to_owned(), source, score }),
+        }
+    };
+    for (url, title) in tabs.iter().filter(|(u, t)| matches(u, t)) { add(url, title, CompletionSource::Tab, TAB_SCORE) }
+    for b in bookmarks.iter().filter(|b| matches(&b.url, &b.title)) { add(&b.url, &b.title, CompletionSource::Bookmark, BOOKMARK_SCORE) }
+    for e in history.entries.values().filter(|e| matches(&e.url, &e.title)) { add(&e.url, &e.title, CompletionSource::History, e.frecency(now)) }
+
+    found.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.url.len().cmp(&b.url.len())));
+    found.truncate(MAX_COMPLETIONS);
+    found
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryEntry;
+
+    #[test]
+    fn normalizes_addresses() {
+        assert_eq!(normalize("example.com").as_deref(), Some("https://example.com/"));
+        assert_eq!(normalize(" docs.rs/tokio?x=1 ").as_deref(), Some("https://docs.rs/tokio?x=1"));
+        assert_eq!(normalize("localhost:8080/api").as_deref(), Some("http://localhost:8080/api"));
+        assert_eq!(normalize("HTTP://Example.com").as_deref(), Some("http://example.com/"));
+        assert_eq!(normalize("/tmp/a page.html").as_deref(), Some("file:///tmp/a%20page.html"));
+        assert_eq!(normalize("file:///tmp/a.html").as_deref(), Some("file:///tmp/a.html"));
+        assert_eq!(normalize("about:blank").as_deref(), Some("about:blank"));
+        assert_eq!(normalize("rust borrow checker"), None);
+        assert_eq!(normalize("rust"), None);
+    }
+
+    #[test]
+    fn ranks_completions_by_frecency() {
+        let day = 24 * 60 * 60;
+        let mut history = History::default();
+        for (url, title, visits, last_visit) in [
+            ("https://doc.rust-lang.org/book/", "The Rust Programming Language", 3, 99 * day),
+            ("https://www.rust-lang.org/", "Rust Programming Language", 9, 0),
+            ("https://docs.rs/", "Docs.rs", 20, 99 * day),
+        ] {
+            history.entries.insert(url.into(), HistoryEntry { url: url.into(), title: title.into(), visits, last_visit });
+        }
+        let bookmarks = [Bookmark { url: "https://crates.io/".into(), title: "crates.io: Rust Package Registry".into() }];
+        let tabs = [("https://www.rust-lang.org/".to_owned(), "Rust".to_owned())];
+
+        let found = complete("rust LANG", &tabs, &bookmarks, &history, 100 * day);
+        let urls: Vec<&str> = found.iter().map(|c| c.url.as_str()).collect();
+        // 3 recent visits outrank 9 old ones, until the page is open in a tab.
+        assert_eq!(urls, ["https://doc.rust-lang.org/book/", "https://www.rust-lang.org/"]);
+        assert_eq!((found[1].source, found[1].score), (CompletionSource::Tab, 140.0));
+
+        let found = complete("rust", &[], &bookmarks, &history, 100 * day);
+        assert_eq!(found.iter().map(|c| c.url.as_str()).collect::<Vec<_>>(),
+            ["https://doc.rust-lang.org/book/", "https://crates.io/", "https://www.rust-lang.org/"]);
+        assert!(complete(" ", &tabs, &bookmarks, &history, 0).is_empty());
+    }
+}
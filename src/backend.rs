@@ -0,0 +1,307 @@
+
+use headless_chrome as chrome;
+use super::browser::{filter_dom_html, ToTui};
+use super::config::SelectorRules;
+use super::tui::TuiNode;
+use super::tools::Session;
+use serde::{Serialize, Deserialize};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Ways a tab can load pages.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BackendKind {
+    /// Headless chrome, which runs scripts and renders pages like a desktop browser.
+    Chrome,
+    /// Plain HTTP requests with reqwest. Quick, but scripts don't run.
+    Reqwest,
+    /// Output of curl, shown as text like a pager.
+    Curl,
+} impl BackendKind {
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendKind::Chrome => "chrome",
+            BackendKind::Reqwest => "reqwest",
+            BackendKind::Curl => "curl",
+        }
+    }
+
+    /// Reads a backend from its name, as typed in a command.
+    pub fn from_name(name: &str) -> Option<BackendKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "chrome" => Some(BackendKind::Chrome),
+            "reqwest" | "r" => Some(BackendKind::Reqwest),
+            "curl" | "c" => Some(BackendKind::Curl),
+            _ => None,
+        }
+    }
+}
+
+/// Loads and reads the pages of one tab.
+/// Calls block until the backend answers, so they are made from background tasks.
+pub trait PageBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+    /// Id of the tab the backend serves, unique across backends.
+    fn id(&self) -> &str;
+    /// Loads a URL, reporting progress as a percentage, and returns once the page can be read.
+    fn navigate(&self, url: &str, progress: &dyn Fn(u8)) -> Result<(), anyhow::Error>;
+    /// Address of the page, after any redirects.
+    fn url(&self) -> String;
+    /// HTML of the page as it stands.
+    fn content(&self) -> Result<String, anyhow::Error>;
+    fn title(&self) -> Result<String, anyhow::Error>;
+    /// Runs Javascript on the page, returning its result.
+    fn eval(&self, script: &str) -> Result<serde_json::Value, anyhow::Error>;
+    /// Gets a resource of the page, like an image, as the page would.
+    fn resource(&self, url: &str) -> Result<Vec<u8>, anyhow::Error>;
+    fn close(&self) -> Result<(), anyhow::Error>;
+    /// The chrome tab behind the backend, for features only chrome has.
+    fn chrome_tab(&self) -> Option<&Arc<chrome::Tab>> { None }
+}
+
+/// Reads a backend's page as a TUI tree, along with its title.
+/// Selector rules added during the session are applied along with those in the configuration.
+pub fn snapshot(backend: &dyn PageBackend, session_rules: &HashMap<String, SelectorRules>) -> Result<(TuiNode, String), anyhow::Error> {
+    let html = backend.content()?;
+    let rules = crate::CONFIG.selector_rules_for(&backend.url(), session_rules);
+    Ok((filter_dom_html(&html, &rules).to_tui(), backend.title()?))
+}
+
+
+/// Pages loaded in a headless chrome tab.
+pub struct ChromeBackend {
+    pub tab: Arc<chrome::Tab>,
+} impl ChromeBackend {
+    pub fn new(tab: Arc<chrome::Tab>) -> ChromeBackend { ChromeBackend { tab } }
+}
+impl PageBackend for ChromeBackend {
+
+    fn kind(&self) -> BackendKind { BackendKind::Chrome }
+
+    fn id(&self) -> &str { self.tab.get_target_id() }
+
+    /// Waits for the page by the wait strategy for its URL once chrome has navigated.
+    fn navigate(&self, url: &str, progress: &dyn Fn(u8)) -> Result<(), anyhow::Error> {
+        self.tab.navigate_to(url)?;
+        progress(30);
+        self.tab.wait_until_navigated()?;
+        progress(60);
+        crate::browser::wait_for_page(&self.tab, crate::CONFIG.wait_strategy_for(url))?;
+        progress(80);
+        Ok(())
+    }
+
+    fn url(&self) -> String { self.tab.get_url() }
+
+    /// Records computed styles in the HTML if configured to, and has chrome report later changes
+    /// to the document if live updates are on.
+    fn content(&self) -> Result<String, anyhow::Error> {
+        let html = match crate::CONFIG.computed_styles {
+            true => crate::browser::styled_content(&self.tab)?,
+            false => self.tab.get_content()?,
+        };
+        if crate::CONFIG.live_updates { crate::browser::track_dom(&self.tab)? }
+        Ok(html)
+    }
+
+    fn title(&self) -> Result<String, anyhow::Error> { self.tab.get_title() }
+
+    fn eval(&self, script: &str) -> Result<serde_json::Value, anyhow::Error> {
+        Ok(self.tab.evaluate(script, true)?.value.unwrap_or_default())
+    }
+
+    fn resource(&self, url: &str) -> Result<Vec<u8>, anyhow::Error> { crate::browser::fetch_resource(&self.tab, url) }
+
+    fn close(&self) -> Result<(), anyhow::Error> {
+        self.tab.close(false)?;
+        Ok(())
+    }
+
+    fn chrome_tab(&self) -> Option<&Arc<chrome::Tab>> { Some(&self.tab) }
+}
+
+
+/// A page fetched with reqwest, kept as HTML to be read.
+struct FetchedPage { url: String, title: String, html: String }
+
+/// Pages fetched with plain HTTP requests.
+pub struct ReqwestBackend {
+    id: String,
+    page: Mutex<Option<FetchedPage>>,
+} impl ReqwestBackend {
+    pub fn new() -> ReqwestBackend {
+        static TABS: AtomicUsize = AtomicUsize::new(0);
+        let id = format!("reqwest-{}", TABS.fetch_add(1, Ordering::Relaxed));
+        ReqwestBackend { id, page: Mutex::new(None) }
+    }
+}
+impl std::default::Default for ReqwestBackend {
+    fn default() -> Self { ReqwestBackend::new() }
+}
+impl PageBackend for ReqwestBackend {
+
+    fn kind(&self) -> BackendKind { BackendKind::Reqwest }
+
+    fn id(&self) -> &str { &self.id }
+
+    fn navigate(&self, url: &str, progress: &dyn Fn(u8)) -> Result<(), anyhow::Error> {
+        let response = crate::fetch::fetch(url)?;
+        progress(60);
+        let (html, title) = crate::fetch::page_html(&response)?;
+        let (html, title) = match crate::CONFIG.native_search_results {
+            true => crate::search::native_page(&response.url, &html).unwrap_or((html, title)),
+            false => (html, title),
+        };
+        let page = FetchedPage { url: response.url, title, html };
+        *self.page.lock().map_err(|_| anyhow::anyhow!("Page lock was poisoned"))? = Some(page);
+        Ok(())
+    }
+
+    fn url(&self) -> String { page_of(&self.page, |p| p.url.clone()).unwrap_or_default() }
+
+    fn content(&self) -> Result<String, anyhow::Error> { page_of(&self.page, |p| p.html.clone()) }
+
+    fn title(&self) -> Result<String, anyhow::Error> { page_of(&self.page, |p| p.title.clone()) }
+
+    fn eval(&self, _script: &str) -> Result<serde_json::Value, anyhow::Error> {
+        Err(anyhow::anyhow!("Pages fetched with reqwest don't run scripts"))
+    }
+
+    fn resource(&self, url: &str) -> Result<Vec<u8>, anyhow::Error> { crate::fetch::fetch_bytes(url) }
+
+    fn close(&self) -> Result<(), anyhow::Error> {
+        if let Ok(mut page) = self.page.lock() { *page = None }
+        Ok(())
+    }
+}
+
+
+/// Pages fetched by running curl, shown as its output.
+pub struct CurlBackend {
+    id: String,
+    /// Flags passed to curl with every URL.
+    flags: Vec<String>,
+    session: Session,
+    page: Mutex<Option<FetchedPage>>,
+} impl CurlBackend {
+    pub fn new(flags: Vec<String>, session: Session) -> CurlBackend {
+        static TABS: AtomicUsize = AtomicUsize::new(0);
+        let id = format!("curl-{}", TABS.fetch_add(1, Ordering::Relaxed));
+        CurlBackend { id, flags, session, page: Mutex::new(None) }
+    }
+}
+impl PageBackend for CurlBackend {
+
+    fn kind(&self) -> BackendKind { BackendKind::Curl }
+
+    fn id(&self) -> &str { &self.id }
+
+    /// Runs curl on the URL, failing with what curl printed to stderr if it exits with an error.
+    fn navigate(&self, url: &str, progress: &dyn Fn(u8)) -> Result<(), anyhow::Error> {
+        let jar = crate::tools::CookieJar::write(&self.session, true)?;
+        let output = crate::tools::curl(url, &self.flags, &self.session, jar.as_ref()).output()?;
+        drop(jar);
+        progress(80);
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("curl {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim()))
+        }
+        let title = format!("curl {}", url);
+        let html = crate::fetch::preformatted(&String::from_utf8_lossy(&output.stdout), &title);
+        *self.page.lock().map_err(|_| anyhow::anyhow!("Page lock was poisoned"))? = Some(FetchedPage { url: url.to_owned(), title, html });
+        Ok(())
+    }
+
+    fn url(&self) -> String { page_of(&self.page, |p| p.url.clone()).unwrap_or_default() }
+
+    fn content(&self) -> Result<String, anyhow::Error> { page_of(&self.page, |p| p.html.clone()) }
+
+    fn title(&self) -> Result<String, anyhow::Error> { page_of(&self.page, |p| p.title.clone()) }
+
+    fn eval(&self, _script: &str) -> Result<serde_json::Value, anyhow::Error> {
+        Err(anyhow::anyhow!("curl output doesn't run scripts"))
+    }
+
+    fn resource(&self, url: &str) -> Result<Vec<u8>, anyhow::Error> { crate::fetch::fetch_bytes(url) }
+
+    fn close(&self) -> Result<(), anyhow::Error> {
+        if let Ok(mut page) = self.page.lock() { *page = None }
+        Ok(())
+    }
+}
+
+/// Reads the page a backend has fetched, failing if none has been.
+fn page_of<T>(page: &Mutex<Option<FetchedPage>>, read: impl FnOnce(&FetchedPage) -> T) -> Result<T, anyhow::Error> {
+    let page = page.lock().map_err(|_| anyhow::anyhow!("Page lock was poisoned"))?;
+    page.as_ref().map(read).ok_or_else(|| anyhow::anyhow!("No page has been loaded"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves fixed pages from memory.
+    struct FakeBackend {
+        pages: HashMap<&'static str, &'static str>,
+        current: Mutex<String>,
+    }
+    impl PageBackend for FakeBackend {
+        fn kind(&self) -> BackendKind { BackendKind::Reqwest }
+        fn id(&self) -> &str { "fake" }
+        fn navigate(&self, url: &str, progress: &dyn Fn(u8)) -> Result<(), anyhow::Error> {
+            if !self.pages.contains_key(url) { return Err(anyhow::anyhow!("Not found: {}", url)) }
+            progress(50);
+            *self.current.lock().unwrap() = url.to_owned();
+            Ok(())
+        }
+        fn url(&self) -> String { self.current.lock().unwrap().clone() }
+        fn content(&self) -> Result<String, anyhow::Error> { Ok(self.pages[self.url().as_str()].to_owned()) }
+        fn title(&self) -> Result<String, anyhow::Error> { Ok(format!("Fake {}", self.url())) }
+        fn eval(&self, _script: &str) -> Result<serde_json::Value, anyhow::Error> { Ok(serde_json::Value::Null) }
+        fn resource(&self, _url: &str) -> Result<Vec<u8>, anyhow::Error> { Ok(Vec::new()) }
+        fn close(&self) -> Result<(), anyhow::Error> { Ok(()) }
+    }
+
+    #[test]
+    fn reads_pages_through_any_backend() {
+        let fake = FakeBackend {
+            pages: HashMap::from([
+                ("https://www.example.com/", "<html><body><p>Hello</p><div class='ad'>Buy now</div></body></html>"),
+            ]),
+            current: Mutex::new(String::new()),
+        };
+        let backend: &dyn PageBackend = &fake;
+        let progress = std::cell::Cell::new(0);
+        assert!(backend.navigate("https://www.example.com/missing", &|p| progress.set(p)).is_err());
+        backend.navigate("https://www.example.com/", &|p| progress.set(p)).unwrap();
+        assert_eq!(progress.get(), 50);
+        assert!(backend.chrome_tab().is_none());
+
+        // Session rules for the page's domain apply to what is read.
+        let rules = HashMap::from([("example.com".to_owned(), SelectorRules { hide: vec![".ad".into()], keep: vec![] })]);
+        let (mut dom, title) = snapshot(backend, &rules).unwrap();
+        dom.collapse_phantoms();
+        assert_eq!(title, "Fake https://www.example.com/");
+        assert_eq!(dom.text_blocks().into_iter().map(|b| b.text).collect::<Vec<_>>(), ["Hello"]);
+    }
+
+    #[test]
+    fn reqwest_backend_has_no_page_until_loaded() {
+        let backend = ReqwestBackend::new();
+        assert_ne!(backend.id(), ReqwestBackend::new().id());
+        assert_eq!(backend.url(), "");
+        assert!(backend.content().is_err());
+        assert!(backend.eval("1 + 1").is_err());
+    }
+
+    #[test]
+    fn names_backends() {
+        assert_eq!(BackendKind::from_name("Chrome"), Some(BackendKind::Chrome));
+        assert_eq!(BackendKind::from_name("r"), Some(BackendKind::Reqwest));
+        assert_eq!(BackendKind::from_name(BackendKind::Reqwest.name()), Some(BackendKind::Reqwest));
+        assert_eq!(BackendKind::from_name("lynx"), None);
+    }
+}
@@ -1,16 +1,210 @@
 
-//use headless_chrome as chrome;
+use headless_chrome as chrome;
 use super::tui;
+use super::events::{EtymonEvent, BrowserEvent, EventSender};
+use super::config::{WaitStrategy, SelectorRules};
 
-use kuchikiki::{traits::*, NodeRef, NodeData};
+
+use kuchikiki::{traits::*, NodeRef, NodeData, Selectors};
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 
 
 /// Filters out HTML elements & attributes that aren't needed to generate TUI.
-pub fn filter_dom_html(html: &str) {
+/// Returns the pruned component tree, ready for conversion to TUI.
+pub fn filter_dom_html(html: &str, rules: &SelectorRules) -> NodeRef {
     let dom = parse_content(html);
-    only_useful_children(&dom);
-    // TBD Get CSS/style information & simplify to TuiStyle/TuiAttribute
+    remove_unrendered(&dom);
+    mark_selectors(&dom);
+    only_useful_children(&dom, rules);
+    dom
+}
+
+/// Sets the size of a tab's viewport in pixels, so pages lay out for that width.
+pub fn set_viewport(tab: &chrome::Tab, width: u32, height: u32) -> Result<(), anyhow::Error> {
+    use chrome::protocol::cdp::Emulation::SetDeviceMetricsOverride;
+    tab.call_method(SetDeviceMetricsOverride {
+        width,
+        height,
+        device_scale_factor: 1.0,
+        mobile: false,
+        scale: None,
+        screen_width: None,
+        screen_height: None,
+        position_x: None,
+        position_y: None,
+        dont_set_visible_size: None,
+        screen_orientation: None,
+        viewport: None,
+        display_feature: None,
+    })?;
+    Ok(())
+}
+
+/// Launches headless chrome with the configured options and a user data directory, sending its traffic
+/// through the configured proxies, or connects to a running chrome if a websocket URL is configured.
+pub fn launch(profile: Option<&std::path::Path>) -> Result<chrome::Browser, anyhow::Error> {
+    let options = &crate::CONFIG.chrome;
+    let idle_timeout = std::time::Duration::from_secs(options.idle_timeout);
+    if let Some(url) = &options.websocket_url {
+        return chrome::Browser::connect_with_timeout(url.clone(), idle_timeout);
+    }
+
+    let mut args: Vec<std::ffi::OsString> = crate::proxy::ProxyRules::from_config(&crate::CONFIG)?
+        .chrome_args().into_iter().map(Into::into).collect();
+    if let Some(agent) = &options.user_agent { args.push(format!("--user-agent={}", agent).into()) }
+    args.extend(options.args.iter().map(Into::into));
+    let path = match &options.path {
+        Some(path) => path.clone(),
+        None => chrome::browser::default_executable().map_err(anyhow::Error::msg)?,
+    };
+    let launch = chrome::LaunchOptions::default_builder()
+        .path(Some(path))
+        .headless(options.headless)
+        .sandbox(options.sandbox)
+        .window_size(options.window_size)
+        .ignore_certificate_errors(options.ignore_certificate_errors)
+        .idle_browser_timeout(idle_timeout)
+        .user_data_dir(profile.map(std::path::Path::to_path_buf))
+        .args(args.iter().map(|a| a.as_os_str()).collect())
+        .build()?;
+    chrome::Browser::new(launch)
+}
+
+/// Holds off reading a page until the wait strategy is satisfied or the configured timeout passes.
+/// Pages that never satisfy the strategy are read as they are.
+pub fn wait_for_page(tab: &chrome::Tab, strategy: &WaitStrategy) -> Result<(), anyhow::Error> {
+    use std::time::{Duration, Instant};
+    let timeout = Duration::from_millis(crate::CONFIG.wait_timeout);
+    let interval = Duration::from_millis(100);
+
+    match strategy {
+        WaitStrategy::Navigated => (),
+        WaitStrategy::Delay(ms) => std::thread::sleep(Duration::from_millis(*ms).min(timeout)),
+        WaitStrategy::Selector(selector) => {
+            // Times out with an error, which is not a reason to give up on the page.
+            let _timed_out = tab.wait_for_element_with_custom_timeout(selector, timeout);
+        },
+        WaitStrategy::NetworkIdle(idle) => {
+            // Resource timing entries are added as requests finish, so the count holding steady means the network is quiet.
+            let script = format!("new Promise(resolve => {{
+                const start = Date.now(); let count = -1; let quiet = 0;
+                const check = () => {{
+                    const n = performance.getEntriesByType('resource').length;
+                    if (n === count) {{ quiet += {step} }} else {{ count = n; quiet = 0 }}
+                    if (quiet >= {idle} || Date.now() - start >= {timeout}) {{ resolve(true) }}
+                    else {{ setTimeout(check, {step}) }}
+                }};
+                check();
+            }})", step = interval.as_millis(), idle = idle, timeout = timeout.as_millis());
+            tab.evaluate(&script, true)?;
+        },
+        WaitStrategy::Script(predicate) => {
+            // Any truthy result, or promise of one, ends the wait. Predicates that throw,
+            // like those reading elements that don't exist yet, aren't ready.
+            let script = format!("(async () => !!(await ({}\n)))().catch(() => false)", predicate);
+            let start = Instant::now();
+            while start.elapsed() < timeout {
+                let result = tab.evaluate(&script, true).ok().and_then(|r| r.value);
+                if result == Some(serde_json::Value::Bool(true)) { break }
+                std::thread::sleep(interval);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Attribute used to carry computed styles from chrome into the parsed HTML.
+pub const STYLE_ATTRIBUTE: &str = "data-etymon-style";
+
+/// CSS properties recorded for each element, in the order `TuiStyle::from_computed` reads them.
+pub const COMPUTED_STYLE_PROPERTIES: [&str; 8] = [
+    "display", "visibility", "fontWeight", "fontStyle", "color", "backgroundColor", "textDecorationLine", "whiteSpace"
+];
+
+/// Gets the page HTML with each element's computed style recorded in an attribute,
+/// along with the duration and chosen source of video and audio.
+/// Styles are written to a copy of the document, so the page itself isn't modified
+/// and no DOM mutation events are triggered.
+pub fn styled_content(tab: &chrome::Tab) -> Result<String, anyhow::Error> {
+    let properties = COMPUTED_STYLE_PROPERTIES.map(|p| format!("s.{}", p)).join(", ");
+    let script = format!("(() => {{
+        const root = document.documentElement;
+        const copy = root.cloneNode(true);
+        const originals = [root, ...root.querySelectorAll('*')];
+        const copies = [copy, ...copy.querySelectorAll('*')];
+        originals.forEach((element, i) => {{
+            const s = getComputedStyle(element);
+            copies[i].setAttribute('{}', [{}].join('|'));
+            if (element instanceof HTMLMediaElement) {{
+                if (isFinite(element.duration)) copies[i].setAttribute('{}', element.duration);
+                if (element.currentSrc) copies[i].setAttribute('{}', element.currentSrc);
+            }}
+        }});
+        return '<!DOCTYPE html>' + copy.outerHTML;
+    }})()", STYLE_ATTRIBUTE, properties, crate::media::DURATION_ATTRIBUTE, crate::media::CURRENT_SOURCE_ATTRIBUTE);
+
+    match tab.evaluate(&script, false)?.value {
+        Some(serde_json::Value::String(html)) => Ok(html),
+        _ => Err(anyhow::anyhow!("Chrome did not return the page content")),
+    }
+}
+
+/// Gets a resource, like an image, through the tab's browsing session so its cookies apply.
+/// Resources the page already loaded come from chrome's cache, and others are fetched by the page.
+pub fn fetch_resource(tab: &chrome::Tab, url: &str) -> Result<Vec<u8>, anyhow::Error> {
+    use base64::Engine;
+    let base64 = base64::engine::general_purpose::STANDARD;
+    let cached = tab.call_method(chrome::protocol::cdp::Page::GetResourceContent {
+        frame_id: tab.get_target_id().to_owned(),
+        url: url.to_owned(),
+    });
+    if let Ok(resource) = cached {
+        return match resource.base_64_encoded {
+            true => Ok(base64.decode(resource.content)?),
+            false => Ok(resource.content.into_bytes()),
+        }
+    }
+
+    let script = format!("fetch({}, {{ credentials: 'include' }})
+        .then(response => response.ok ? response.blob() : Promise.reject(response.statusText))
+        .then(blob => new Promise((resolve, reject) => {{
+            const reader = new FileReader();
+            reader.onload = () => resolve(reader.result.split(',')[1] || '');
+            reader.onerror = reject;
+            reader.readAsDataURL(blob);
+        }}))", serde_json::Value::String(url.to_owned()));
+    match tab.evaluate(&script, true)?.value {
+        Some(serde_json::Value::String(data)) => Ok(base64.decode(data)?),
+        _ => Err(anyhow::anyhow!("Could not fetch {}", url)),
+    }
+}
+
+/// How deep into the document chrome should report mutations.
+const TRACKED_DOM_DEPTH: u32 = 256;
+
+/// Requests the document tree from chrome, which makes it report mutations to the nodes it sent.
+pub fn track_dom(tab: &chrome::Tab) -> Result<(), anyhow::Error> {
+    tab.call_method(chrome::protocol::cdp::DOM::GetDocument { depth: Some(TRACKED_DOM_DEPTH), pierce: Some(false) })?;
+    Ok(())
+}
+
+/// Forwards DOM mutation events from a chrome tab to the main loop.
+pub fn watch_dom(tab: &chrome::Tab, events: EventSender) -> Result<(), anyhow::Error> {
+    use chrome::protocol::cdp::types::Event::*;
+    let tab_id = tab.get_target_id().to_owned();
+    tab.add_event_listener(std::sync::Arc::new(move |event: &chrome::protocol::cdp::types::Event| {
+        match event {
+            DOMDocumentUpdated(_) | DOMChildNodeInserted(_) | DOMChildNodeRemoved(_) |
+            DOMChildNodeCountUpdated(_) | DOMAttributeModified(_) | DOMAttributeRemoved(_) |
+            DOMCharacterDataModified(_) => {
+                let changed = BrowserEvent::DomChanged { tab_id: tab_id.clone() };
+                let _closed = events.send(EtymonEvent::Browser(changed));
+            },
+            _ => (),
+        }
+    }))?;
+    Ok(())
 }
 
 /// Parse HTML string into HTML component tree.
@@ -18,18 +212,96 @@ pub fn parse_content(html: &str) -> NodeRef {
     kuchikiki::parse_html().one(html)
 }
 
+/// Elements that never contribute readable text to the page.
+pub const UNRENDERED_ELEMENTS: [&str; 7] = ["head", "script", "style", "noscript", "svg", "iframe", "template"];
+
+/// Remove elements that are never rendered as text, or that the page hides from view.
+pub fn remove_unrendered(dom: &NodeRef) {
+    let unrendered: Vec<NodeRef> = dom.descendants()
+        .filter(|node| node.as_element().is_some_and(|e| {
+            UNRENDERED_ELEMENTS.contains(&&*e.name.local) || is_hidden(e)
+        }))
+        .collect();
+    for node in unrendered { node.detach() }
+}
+
+/// True for elements hidden by their attributes or inline style.
+pub fn is_hidden(element: &kuchikiki::ElementData) -> bool {
+    let attributes = element.attributes.borrow();
+    let style = attributes.get("style").unwrap_or_default().to_ascii_lowercase().replace(char::is_whitespace, "");
+    attributes.contains("hidden")
+        || attributes.get("aria-hidden") == Some("true")
+        || (&*element.name.local == "input" && attributes.get("type").is_some_and(|t| t.eq_ignore_ascii_case("hidden")))
+        || style.contains("display:none")
+        || style.contains("visibility:hidden")
+        || attributes.get(STYLE_ATTRIBUTE).is_some_and(|computed| tui::TuiStyle::from_computed(computed).hidden)
+}
+
+/// Attribute holding a selector that matches only its element, for rules that hide it.
+pub const SELECTOR_ATTRIBUTE: &str = "data-etymon-selector";
+
+/// Marks each element with a selector naming only it: its chain of tags and :nth-of-type positions
+/// from the nearest ancestor with an id no other element shares, or else from the root.
+/// Positions are counted before selector rules run, in the same DOM the rules are matched against.
+pub fn mark_selectors(dom: &NodeRef) {
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    for element in dom.descendants().elements() {
+        if let Some(id) = element.attributes.borrow().get("id") { *ids.entry(id.to_owned()).or_default() += 1 }
+    }
+    mark_children(dom, "", &ids);
+}
+
+fn mark_children(node: &NodeRef, parent: &str, ids: &HashMap<String, usize>) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for child in node.children() {
+        let Some(element) = child.as_element() else { continue };
+        let name = element.name.local.to_string();
+        let nth = counts.entry(name.clone()).or_default();
+        *nth += 1;
+        let id = element.attributes.borrow().get("id").map(str::to_owned)
+            .filter(|id| ids.get(id) == Some(&1) && crate::utils::is_css_ident(id));
+        let selector = match (id, parent) {
+            (Some(id), _) => format!("#{}", id),
+            (None, "") => format!("{}:nth-of-type({})", name, nth),
+            (None, parent) => format!("{} > {}:nth-of-type({})", parent, name, nth),
+        };
+        element.attributes.borrow_mut().insert(SELECTOR_ATTRIBUTE, selector.clone());
+        mark_children(&child, &selector, ids);
+    }
+}
+
+/// Remove HTML elements hidden by selector rules, and those with types that aren't marked for inclusion.
+pub fn only_useful_children(dom: &NodeRef, rules: &SelectorRules) {
+    hide_selected(dom, rules);
+    only_useful_kinds(dom);
+}
+
 /// Remove HTML elements with types that aren't marked for inclusion.
-pub fn only_useful_children(dom: &NodeRef) {
+fn only_useful_kinds(dom: &NodeRef) {
     let useful_kinds = &crate::CONFIG.useful_nodes;
     let mut useless: Vec<NodeRef> = Vec::new();
     for child in dom.children() {
-        only_useful_children(&child);
-        if !useful_kinds.contains(&NodeDataKind::type_of(&child.data())) && child.children().count() == 0 {
+        only_useful_kinds(&child);
+        if !useful_kinds.contains(&NodeDataKind::type_of(child.data())) && child.children().count() == 0 {
             useless.push(child)
     }   }
     for child in useless { child.detach() }
 }
 
+/// Remove elements matching a hide rule, unless they match a keep rule or contain an element that does.
+/// Selectors that fail to parse are ignored.
+pub fn hide_selected(dom: &NodeRef, rules: &SelectorRules) {
+    let keep: Vec<Selectors> = rules.keep.iter().filter_map(|k| Selectors::compile(k).ok()).collect();
+    let kept = |node: &NodeRef| node.inclusive_descendants().elements().any(|e| keep.iter().any(|k| k.matches(&e)));
+
+    let mut hidden: Vec<NodeRef> = Vec::new();
+    for rule in rules.hide.iter() {
+        let Ok(selected) = dom.select(rule) else { continue };
+        hidden.extend(selected.map(|e| e.as_node().clone()).filter(|node| !kept(node)));
+    }
+    for node in hidden { node.detach() }
+}
+
 
 /// Data-less representation of NodeData.
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
@@ -64,7 +336,9 @@ impl ToTui for kuchikiki::Node {
             data: self.data().to_tui(),
             children: if let Some(child) = self.first_child() {
                 child.inclusive_following_siblings().map(|c| c.to_tui()).collect()
-            } else { vec![] }
+            } else { vec![] },
+            style: self.as_element()
+                .and_then(|e| e.attributes.borrow().get(STYLE_ATTRIBUTE).map(tui::TuiStyle::from_computed)),
         }
     }
 }
@@ -88,7 +362,7 @@ impl ToTui for kuchikiki::ElementData {
     fn to_tui(&self) -> Self::TuiType {
         tui::TuiElement {
             qual_name: self.name.clone(),
-            attributes: self.attributes.borrow().map.iter().map(|(_, a)| a.to_tui()).collect(),
+            attributes: self.attributes.borrow().map.iter().map(|(name, a)| (name, a).to_tui()).collect(),
             // TBD
             //contents: {
             //    if let Some(node) = &self.template_contents {
@@ -99,10 +373,133 @@ impl ToTui for kuchikiki::ElementData {
     }
 }
 
-/// HTML attribute to TUI widget attribute.
-impl ToTui for kuchikiki::Attribute {
+/// HTML attribute and its name to TUI widget attribute.
+impl ToTui for (&kuchikiki::ExpandedName, &kuchikiki::Attribute) {
     type TuiType = tui::TuiAttribute;
     fn to_tui(&self) -> Self::TuiType {
-        tui::TuiAttribute(self.value.clone())
+        tui::TuiAttribute { name: self.0.local.to_string(), value: self.1.value.clone() }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNRENDERED: &str = include_str!("../tests/fixtures/unrendered.html");
+    const HIDDEN: &str = include_str!("../tests/fixtures/hidden.html");
+    const SELECTORS: &str = include_str!("../tests/fixtures/selectors.html");
+    const ARTICLE: &str = include_str!("../tests/fixtures/article.html");
+    const IMAGES: &str = include_str!("../tests/fixtures/images.html");
+
+    /// Text of each block that the page would render as.
+    fn rendered_text(dom: &NodeRef) -> Vec<String> {
+        let mut tui = dom.to_tui();
+        tui.collapse_phantoms();
+        tui.text_blocks().into_iter().map(|b| b.text).collect()
+    }
+
+    #[test]
+    fn removes_unrendered_elements() {
+        let dom = filter_dom_html(UNRENDERED, &SelectorRules::default());
+        for name in UNRENDERED_ELEMENTS {
+            assert_eq!(dom.select(name).unwrap().count(), 0, "<{}> should be removed", name);
+        }
+        let text = dom.text_contents();
+        for leftover in ["should not render", "script text", "enable Javascript", "svg text", "iframe fallback", "template text", "color: red"] {
+            assert!(!text.contains(leftover), "{:?} should not be rendered", leftover);
+        }
+    }
+
+    #[test]
+    fn keeps_content_in_order() {
+        let dom = filter_dom_html(UNRENDERED, &SelectorRules::default());
+        assert_eq!(rendered_text(&dom), vec![
+            "Article heading",
+            "First paragraph of the article.",
+            "Last paragraph of the article.",
+        ]);
+    }
+
+    #[test]
+    fn strips_hidden_elements() {
+        let dom = filter_dom_html(HIDDEN, &SelectorRules::default());
+        assert_eq!(rendered_text(&dom), vec![
+            "Visible paragraph.",
+            "Visible by display.",
+            "Another visible paragraph.",
+        ]);
+        assert_eq!(dom.select("input").unwrap().count(), 1);
+        assert!(!dom.to_string().contains("secret-token"));
+    }
+
+    #[test]
+    fn applies_selector_rules() {
+        let rules = SelectorRules {
+            hide: vec!["#cookie-banner".into(), ".ad".into(), "aside".into(), "not a [valid selector".into()],
+            keep: vec![".ad.house".into(), ".search".into()],
+        };
+        let dom = filter_dom_html(SELECTORS, &rules);
+        assert_eq!(rendered_text(&dom), vec![
+            "Main content.",
+            "House ad for our own newsletter.",
+            "Trending now.",
+            "Site search",
+        ]);
+    }
+
+    #[test]
+    fn hides_only_the_focused_element() {
+        let html = "<main><section><p>One</p><p>Two</p><p>Three</p></section><div id=\"notes\"><p>Four</p><p>Five</p></div></main>";
+        let blocks = |rules: &SelectorRules| {
+            let mut tui = filter_dom_html(html, rules).to_tui();
+            tui.collapse_phantoms();
+            tui.text_blocks()
+        };
+        let before = blocks(&SelectorRules::default());
+        assert_eq!(before[1].selectors[0], "html:nth-of-type(1) > body:nth-of-type(1) > main:nth-of-type(1) > section:nth-of-type(1) > p:nth-of-type(2)");
+        assert_eq!(before[4].selectors[0], "#notes > p:nth-of-type(2)");
+
+        let rules = SelectorRules { hide: vec![before[1].selectors[0].clone(), before[4].selectors[0].clone()], keep: vec![] };
+        let text: Vec<String> = blocks(&rules).into_iter().map(|b| b.text).collect();
+        assert_eq!(text, ["One", "Three", "Four"]);
+        // Positions are counted in the page as it is, so rules added one after another still name the element meant.
+        let rules = SelectorRules { hide: vec![before[0].selectors[0].clone(), before[1].selectors[0].clone()], keep: vec![] };
+        let after = blocks(&rules);
+        assert_eq!(after[0].text, "Three");
+        assert_eq!(after[0].selectors[0], before[2].selectors[0]);
+    }
+
+    #[test]
+    fn isolates_landmark_regions() {
+        let mut tui = filter_dom_html(ARTICLE, &SelectorRules::default()).to_tui();
+        tui.collapse_phantoms();
+        let panes: Vec<tui::TuiPane> = tui.isolate_nav_elements().iter().filter_map(tui::TuiPane::new).collect();
+        let names: Vec<&str> = panes.iter().map(|p| p.landmark.name()).collect();
+        assert_eq!(names, vec!["Header", "Sidebar", "Footer"]);
+        assert_eq!(panes[0].summary, "Home · World · Tech");
+
+        let text: Vec<String> = tui.text_blocks().into_iter().map(|b| b.text).collect();
+        assert_eq!(text.first().map(String::as_str), Some("Reading the web from a terminal"));
+        assert!(!text.iter().any(|t| t.contains("Most popular") || t.contains("Copyright")));
+    }
+
+    #[test]
+    fn shows_image_placeholders() {
+        let mut tui = filter_dom_html(IMAGES, &SelectorRules::default()).to_tui();
+        tui.collapse_phantoms();
+        let blocks = tui.text_blocks();
+        let text: Vec<&str> = blocks.iter().map(|b| b.text.as_str()).collect();
+        assert_eq!(text, vec![
+            "Before [image: A red square] after.",
+            "Decorative images are skipped.",
+            "[image: harbour.jpg]Lazy images fall back to their file name.",
+        ]);
+
+        let image = &blocks[0].images[0];
+        assert_eq!(image.src, "/static/red.png");
+        assert_eq!(blocks[0].text.chars().skip(image.start).take(image.end - image.start).collect::<String>(), "[image: A red square]");
+        assert!(blocks[1].images.is_empty());
+        assert_eq!(blocks[2].images[0].src, "https://example.com/photos/harbour.jpg?w=640");
     }
 }
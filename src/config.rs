@@ -1,12 +1,11 @@
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use serde::{Serialize, Deserialize};
 use super::browser::NodeDataKind::{self, *};
 
 /*
     TODO
     - Setup VPN connection through browser?
-    - Backend selector Chrome/TOR
 */
 
 lazy_static::lazy_static!{
@@ -14,13 +13,21 @@ lazy_static::lazy_static!{
     pub static ref CONFIG: ConfigOptions = ConfigOptions::read_config();
 }
 
+/// Settings missing from the config file keep their defaults.
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConfigOptions {
-    /// Webpage url to open on startup. Needs to begin with https://...
+    /// Webpage to open on startup and in new tabs. Bare hosts like example.com are opened with https.
     pub homepage: String,
+    /// How new tabs load pages: Chrome, or Reqwest for quick requests that don't run scripts.
+    pub backend: crate::backend::BackendKind,
     /// If true, links to an existing user Chrome/Chromium profile. Default is false.
     pub sync_profile: bool,
-    /// If true, Etymon does not save session data or logs. Default is true.
+    /// Chrome/Chromium user data directory to use with sync_profile, like ~/.config/chromium.
+    /// If unset, the usual location of Chrome's, and then Chromium's, is used.
+    pub profile_path: Option<std::path::PathBuf>,
+    /// If true, Etymon does not save session data or logs, and chrome starts from an empty profile
+    /// that is deleted on exit. Default is true.
     pub amnesia: bool,
     /// Useful document structures to render to TUI.
     pub useful_nodes: HashSet<NodeDataKind>,
@@ -38,19 +45,92 @@ pub struct ConfigOptions {
     pub frame_rate: f32,
     /// Starts Edymon with mouse events enabled, if terminal supports them.
     pub mouse_capture: bool,
+    /// If true, pages are refreshed when chrome reports changes to their DOM.
+    pub live_updates: bool,
+    /// Milliseconds to wait for DOM changes to settle before refreshing the page.
+    pub update_debounce: u64,
+    /// If set, pages are also re-read from chrome at this interval in milliseconds.
+    pub update_poll: Option<u64>,
+    /// How to tell that a page has finished rendering before it is read from chrome.
+    pub wait_strategy: WaitStrategy,
+    /// Wait strategies for specific domains, which also apply to their subdomains.
+    pub domain_wait_strategies: HashMap<String, WaitStrategy>,
+    /// Longest time in milliseconds to wait on a strategy before reading the page anyway.
+    pub wait_timeout: u64,
+    /// CSS selector rules for elements to hide or keep on every page.
+    pub selector_rules: SelectorRules,
+    /// Selector rules for specific domains, added to the global rules for the domain and its subdomains.
+    pub domain_selector_rules: HashMap<String, SelectorRules>,
+    /// If true, styles computed by chrome are used to hide, break up and colour page text.
+    pub computed_styles: bool,
+    /// Colours the terminal can show, for approximating page colours.
+    pub color_mode: ColorMode,
+    /// If true, the chrome viewport is resized to match the terminal so responsive sites fit the page view.
+    pub match_viewport: bool,
+    /// Approximate size of a terminal cell in pixels (width, height), used to size the chrome viewport.
+    pub cell_size: (u16, u16),
+    /// Paths of EasyList/uBlock style filter lists used to block ads and trackers.
+    pub filter_lists: Vec<std::path::PathBuf>,
+    /// Domains whose pages are never filtered, including their subdomains.
+    pub adblock_allowlist: Vec<String>,
+    /// If true, navigation, headers, sidebars and footers are moved out of the page into collapsible panes.
+    pub isolate_regions: bool,
+    /// How images are drawn when expanded, or Placeholder to only show their alt text.
+    pub image_mode: ImageMode,
+    /// If true, images are fetched and expanded as pages load, rather than when asked for.
+    pub inline_images: bool,
+    /// Most terminal rows an expanded image takes up.
+    pub image_rows: u16,
+    /// Commands that play video and audio, by MIME type, which may be a wildcard like video/* or */*.
+    /// The media URL replaces {url} in the command, or is added to the end of it.
+    pub media_players: HashMap<String, String>,
+    /// Directory chrome saves downloads to.
+    pub download_dir: std::path::PathBuf,
+    /// Commands that open downloaded files, by MIME type, matched like media_players.
+    pub download_handlers: HashMap<String, String>,
+    /// Proxy for all traffic, as a URL: http://, https://, socks5:// (names resolved locally)
+    /// or socks5h:// (names resolved by the proxy). Chrome always has SOCKS5 proxies resolve names.
+    pub proxy: Option<String>,
+    /// Proxies for specific domains and their subdomains, or "direct" to bypass the proxy.
+    pub domain_proxies: HashMap<String, String>,
+    /// If true, all traffic goes through Tor at tor_proxy. .onion addresses always do.
+    pub tor: bool,
+    /// SOCKS address of the local Tor client.
+    pub tor_proxy: String,
+    /// How chrome is launched, or connected to.
+    pub chrome: ChromeOptions,
+    /// Search engines for :s and for text typed in the command line that isn't an address.
+    pub search_engines: Vec<crate::search::SearchEngine>,
+    /// Name of the engine to search with when no keyword is given.
+    pub search_engine: String,
+    /// If true, DuckDuckGo searches are fetched without chrome and shown as a compact list of results.
+    pub native_search_results: bool,
     // TBD Font size
 } impl ConfigOptions {
 
-    // TBD Read & parse config.toml
+    /// Reads config.toml from the config directory, or the defaults if there is none.
+    /// Panics on a config file that can't be read, so CONFIG should be initialized before the terminal is taken over.
     fn read_config() -> ConfigOptions {
-        Self::default_options()
+        let path = crate::utils::config_dir().join("config.toml");
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).unwrap_or_else(|e| panic!("Error in {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default_options(),
+            Err(e) => panic!("Could not read {}: {}", path.display(), e),
+        }
+    }
+
+    /// Reads settings from TOML, keeping the defaults for any that are left out.
+    pub fn parse(toml: &str) -> Result<ConfigOptions, anyhow::Error> {
+        Ok(toml::from_str(toml)?)
     }
 
     /// Private, minimal, and performant configuration with no bindings.
     fn default_options() -> ConfigOptions {
         ConfigOptions {
             homepage: "https://www.duckduckgo.com".to_owned(),
+            backend: crate::backend::BackendKind::Chrome,
             sync_profile: false,
+            profile_path: None,
             amnesia: true,
             useful_nodes: [Element, Text].into_iter().collect(),
             cursor_controls: CursorControls::WASD,
@@ -61,13 +141,160 @@ pub struct ConfigOptions {
             tick_rate: 4.0,
             frame_rate: 60.0,
             mouse_capture: false,
+            live_updates: true,
+            update_debounce: 300,
+            update_poll: None,
+            wait_strategy: WaitStrategy::NetworkIdle(500),
+            domain_wait_strategies: HashMap::new(),
+            wait_timeout: 10_000,
+            selector_rules: SelectorRules::default(),
+            domain_selector_rules: HashMap::new(),
+            computed_styles: true,
+            color_mode: ColorMode::Auto,
+            match_viewport: true,
+            cell_size: (8, 16),
+            filter_lists: Vec::new(),
+            adblock_allowlist: Vec::new(),
+            isolate_regions: true,
+            image_mode: ImageMode::Auto,
+            inline_images: false,
+            image_rows: 12,
+            media_players: HashMap::from([
+                ("video/*".to_owned(), "mpv".to_owned()),
+                ("audio/*".to_owned(), "mpv --no-video".to_owned()),
+                ("application/vnd.apple.mpegurl".to_owned(), "mpv".to_owned()),
+                ("application/dash+xml".to_owned(), "mpv".to_owned()),
+            ]),
+            download_dir: crate::utils::home_dir().join("Downloads"),
+            download_handlers: HashMap::from([("*/*".to_owned(), "xdg-open".to_owned())]),
+            proxy: None,
+            domain_proxies: HashMap::new(),
+            tor: false,
+            tor_proxy: "socks5h://127.0.0.1:9050".to_owned(),
+            chrome: ChromeOptions::default(),
+            search_engines: vec![
+                crate::search::SearchEngine::new("DuckDuckGo", "https://html.duckduckgo.com/html/?q={}", Some("ddg")),
+                crate::search::SearchEngine::new("Wikipedia", "https://en.wikipedia.org/w/index.php?search={}", Some("w")),
+                crate::search::SearchEngine::new("GitHub", "https://github.com/search?q={}", Some("gh")),
+            ],
+            search_engine: "DuckDuckGo".to_owned(),
+            native_search_results: true,
+        }
+    }
+
+    /// Finds the setting for the most specific domain that a URL belongs to.
+    pub fn for_domain<'a, T>(settings: &'a HashMap<String, T>, url: &str) -> Option<&'a T> {
+        let host = url::Url::parse(url).ok()?.host_str()?.to_owned();
+        settings.iter()
+            .filter(|(domain, _)| crate::utils::host_matches(&host, domain))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, setting)| setting)
+    }
+
+    /// Combines the global selector rules with those of every domain a URL belongs to,
+    /// from both the configuration and the extra rules given.
+    pub fn selector_rules_for(&self, url: &str, extra: &HashMap<String, SelectorRules>) -> SelectorRules {
+        let mut rules = self.selector_rules.clone();
+        let Some(host) = url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_owned)) else { return rules };
+        for (domain, domain_rules) in self.domain_selector_rules.iter().chain(extra.iter()) {
+            if crate::utils::host_matches(&host, domain) { rules.extend(domain_rules) }
+        }
+        rules
+    }
+
+    /// Gets the wait strategy to use when loading a URL.
+    pub fn wait_strategy_for(&self, url: &str) -> &WaitStrategy {
+        Self::for_domain(&self.domain_wait_strategies, url).unwrap_or(&self.wait_strategy)
+    }
+}
+
+impl std::default::Default for ConfigOptions {
+    fn default() -> Self { Self::default_options() }
+}
+
+/// Options for launching chrome, set in the [chrome] section of the config file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChromeOptions {
+    /// Chrome or Chromium binary to launch. If unset, one is looked for in the usual places.
+    pub path: Option<std::path::PathBuf>,
+    /// DevTools websocket URL of a chrome that is already running, like ws://127.0.0.1:9222/devtools/browser/<id>.
+    /// If set, Etymon connects to it instead of launching chrome, and only idle_timeout applies.
+    pub websocket_url: Option<String>,
+    /// If false, chrome shows its own window as well.
+    pub headless: bool,
+    /// If false, chrome runs without its sandbox, which some containers need.
+    pub sandbox: bool,
+    /// Window size in pixels (width, height). match_viewport resizes the page view within it.
+    pub window_size: Option<(u32, u32)>,
+    /// User agent chrome sends instead of its own.
+    pub user_agent: Option<String>,
+    /// If true, pages with invalid TLS certificates load without complaint.
+    pub ignore_certificate_errors: bool,
+    /// Extra command line switches, like "--lang=fr".
+    pub args: Vec<String>,
+    /// Seconds chrome may go without sending anything before the connection to it is dropped.
+    pub idle_timeout: u64,
+}
+impl std::default::Default for ChromeOptions {
+    fn default() -> Self {
+        ChromeOptions {
+            path: None,
+            websocket_url: None,
+            headless: true,
+            sandbox: true,
+            window_size: None,
+            user_agent: None,
+            ignore_certificate_errors: false,
+            args: Vec::new(),
+            // An idle terminal can leave chrome quiet for a long time.
+            idle_timeout: 24 * 60 * 60,
         }
     }
 }
 
+/// CSS selectors for elements to remove from pages, such as cookie banners and ad containers.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SelectorRules {
+    /// Elements matching these selectors are hidden.
+    pub hide: Vec<String>,
+    /// Elements matching these selectors are never hidden by a rule, nor are their ancestors.
+    pub keep: Vec<String>,
+} impl SelectorRules {
+
+    /// Adds the rules from another set to this one.
+    pub fn extend(&mut self, other: &SelectorRules) {
+        self.hide.extend(other.hide.iter().cloned());
+        self.keep.extend(other.keep.iter().cloned());
+    }
+}
+
+/// Ways to decide that a page is ready to be read, for pages that keep rendering after they load.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum WaitStrategy {
+    /// Read the page as soon as navigation completes.
+    Navigated,
+    /// Wait until the page has gone this many milliseconds without finishing a network request.
+    NetworkIdle(u64),
+    /// Wait for an element matching the CSS selector to appear.
+    Selector(String),
+    /// Wait for a fixed number of milliseconds.
+    Delay(u64),
+    /// Wait until the Javascript expression evaluates to a truthy value, or a promise of one.
+    Script(String),
+}
+
+/// Ways of drawing images. Auto picks kitty graphics, sixels or half blocks from what the terminal reports about itself.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum ImageMode { Auto, Kitty, Sixel, HalfBlock, Placeholder }
+
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum CursorControls { WASD, HJKL }
 
+/// Colour support of the terminal. Auto checks COLORTERM for truecolour, and otherwise uses 256 colours.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum ColorMode { Auto, TrueColor, Indexed, Monochrome }
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct CharKeymap {
     pub binds: std::collections::HashMap<char, String>
@@ -78,6 +305,7 @@ pub struct CharKeymap {
 pub struct FnKeymap {
     pub scripts: std::collections::HashMap<usize, FnScript>
 } impl FnKeymap {
+    #[allow(unused_variables)]
     pub fn serve_fn(&self, id: usize) -> Result<(), anyhow::Error> {
         todo!{"Match id to fn key"}
     }
@@ -92,6 +320,7 @@ pub enum FnScript {
     Lua(String),  // TBD Filepath type?
 } impl FnScript {
 
+    #[allow(clippy::unit_arg)]
     pub fn execute(&self) {
         match self {
             FnScript::None        => (),
@@ -101,20 +330,24 @@ pub enum FnScript {
         }
     }
 
+    #[allow(unused_variables)]
     pub fn execute_rust(rust: ()) {
         todo!{"Execute rust functions from library on page, text, and browser"}
     }
 
+    #[allow(unused_variables)]
     pub fn execute_perl(script_path: &str) {
         todo!{"Execute perl scripts on page, text, and browser"}
     }
 
+    #[allow(unused_variables)]
     pub fn execute_lua(script_path: &str) {
         todo!{"Execute lua scripts on page, text, and browser"}
     }
 }
 
 
+#[allow(clippy::derivable_impls)]
 impl std::default::Default for FnScript {
     fn default() -> Self {
         FnScript::None
@@ -122,3 +355,32 @@ impl std::default::Default for FnScript {
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG_FILE: &str = include_str!("../tests/fixtures/config.toml");
+
+    #[test]
+    fn reads_config_files() {
+        let config = ConfigOptions::parse(CONFIG_FILE).unwrap();
+        assert_eq!(config.homepage, "https://lite.duckduckgo.com/lite");
+        assert_eq!(config.backend, crate::backend::BackendKind::Reqwest);
+        assert!(config.tor);
+        assert!(matches!(config.wait_strategy, WaitStrategy::NetworkIdle(800)));
+        assert_eq!(config.domain_proxies["intranet.example.com"], "direct");
+
+        assert_eq!(config.chrome.path, Some(std::path::PathBuf::from("/usr/bin/chromium")));
+        assert!(!config.chrome.sandbox);
+        assert_eq!(config.chrome.window_size, Some((1280, 800)));
+        assert_eq!(config.chrome.args, ["--lang=fr"]);
+
+        // Everything left out keeps its default.
+        assert!(config.chrome.headless);
+        assert!(!config.chrome.ignore_certificate_errors);
+        assert_eq!(config.tor_proxy, "socks5h://127.0.0.1:9050");
+        assert!(config.amnesia);
+
+        assert!(ConfigOptions::parse("tick_rate = \"fast\"").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+
+use headless_chrome as chrome;
+use chrome::protocol::cdp::Network;
+use ratatui::{prelude::*, widgets::*};
+
+/// First line of a Netscape cookies.txt file, which curl and wget look for.
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+/// Prefix curl gives the domains of cookies that scripts can't read.
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// A cookie a site has set in chrome.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Host the cookie belongs to. A leading dot means it is also sent to subdomains.
+    pub domain: String,
+    pub path: String,
+    /// Seconds since the epoch that the cookie expires at, or None for a cookie that lasts the session.
+    pub expires: Option<f64>,
+    pub http_only: bool,
+    pub secure: bool,
+} impl Cookie {
+
+    fn from_chrome(cookie: Network::Cookie) -> Cookie {
+        let expires = (!cookie.session && cookie.expires > 0.0).then_some(cookie.expires);
+        Cookie {
+            name: cookie.name, value: cookie.value, domain: cookie.domain, path: cookie.path,
+            expires, http_only: cookie.http_only, secure: cookie.secure,
+        }
+    }
+
+    fn to_chrome(&self) -> Network::CookieParam {
+        Network::CookieParam {
+            name: self.name.clone(),
+            value: self.value.clone(),
+            url: None,
+            domain: Some(self.domain.clone()),
+            path: Some(self.path.clone()),
+            secure: Some(self.secure),
+            http_only: Some(self.http_only),
+            same_site: None,
+            expires: self.expires,
+            priority: None,
+            same_party: None,
+            source_scheme: None,
+            source_port: None,
+            partition_key: None,
+        }
+    }
+}
+
+/// Cookies of the page a cookie list was opened on, with the one selected.
+pub struct Cookies {
+    pub url: String,
+    pub list: Vec<Cookie>,
+    pub selected: usize,
+} impl Cookies {
+
+    pub fn new(url: &str, list: Vec<Cookie>) -> Cookies { Cookies { url: url.to_owned(), list, selected: 0 } }
+
+    pub fn selected(&self) -> Option<&Cookie> { self.list.get(self.selected) }
+
+    /// Moves the selection up or down the list.
+    pub fn select(&mut self, step: isize) {
+        self.selected = (self.selected as isize + step).clamp(0, self.list.len().saturating_sub(1) as isize) as usize;
+    }
+
+    /// Lists cookies with their domain, path and flags.
+    pub fn widget(&self) -> Table<'_> {
+        let rows: Vec<Row> = self.list.iter().map(|c| {
+            let mut flags = Vec::new();
+            if c.secure { flags.push("secure") }
+            if c.http_only { flags.push("http-only") }
+            if c.expires.is_none() { flags.push("session") }
+            Row::new(vec![c.domain.clone(), c.path.clone(), c.name.clone(), flags.join(" "), c.value.clone()])
+        }).collect();
+        let title = match rows.is_empty() {
+            true => format!("Cookies for {} (none)", self.url),
+            false => format!("Cookies for {}: d deletes, x deletes the domain's, :cookies export/import <file>", self.url),
+        };
+        let widths = [
+            Constraint::Percentage(20), Constraint::Length(12), Constraint::Percentage(20),
+            Constraint::Length(22), Constraint::Percentage(40),
+        ];
+        Table::new(rows, widths)
+            .header(Row::new(vec!["Domain", "Path", "Name", "Flags", "Value"]).bold())
+            .highlight_style(Style::default().reversed())
+            .block(Block::default().title(title))
+    }
+}
+
+/// Reads the cookies chrome would send to a URL.
+pub fn read(tab: &chrome::Tab, url: &str) -> Result<Vec<Cookie>, anyhow::Error> {
+    let cookies = tab.call_method(Network::GetCookies { urls: Some(vec![url.to_owned()]) })?.cookies;
+    Ok(cookies.into_iter().map(Cookie::from_chrome).collect())
+}
+
+/// Deletes cookies from chrome.
+pub fn delete(tab: &chrome::Tab, cookies: &[Cookie]) -> Result<(), anyhow::Error> {
+    for cookie in cookies {
+        tab.call_method(Network::DeleteCookies {
+            name: cookie.name.clone(),
+            url: None,
+            domain: Some(cookie.domain.clone()),
+            path: Some(cookie.path.clone()),
+        })?;
+    }
+    Ok(())
+}
+
+/// Adds cookies to chrome, replacing any with the same name, domain and path.
+pub fn set(tab: &chrome::Tab, cookies: &[Cookie]) -> Result<(), anyhow::Error> {
+    tab.call_method(Network::SetCookies { cookies: cookies.iter().map(Cookie::to_chrome).collect() })?;
+    Ok(())
+}
+
+/// Clears the localStorage and sessionStorage of the tab's page.
+pub fn clear_storage(tab: &chrome::Tab) -> Result<(), anyhow::Error> {
+    tab.evaluate("localStorage.clear(); sessionStorage.clear();", false)?;
+    Ok(())
+}
+
+/// Writes cookies in the Netscape cookies.txt format that curl and wget read.
+pub fn to_netscape(cookies: &[Cookie]) -> String {
+    let flag = |on: bool| match on { true => "TRUE", false => "FALSE" };
+    let mut text = format!("{}\n", NETSCAPE_HEADER);
+    for c in cookies {
+        let prefix = match c.http_only { true => HTTP_ONLY_PREFIX, false => "" };
+        let expires = c.expires.map_or(0, |e| e as u64);
+        text += &format!("{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            prefix, c.domain, flag(c.domain.starts_with('.')), c.path, flag(c.secure), expires, c.name, c.value);
+    }
+    text
+}
+
+/// Reads cookies from a Netscape cookies.txt file, skipping comments and malformed lines.
+pub fn from_netscape(text: &str) -> Vec<Cookie> {
+    text.lines().filter_map(|line| {
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        if line.starts_with('#') || line.trim().is_empty() { return None }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [domain, subdomains, path, secure, expires, name, value] = fields[..] else { return None };
+        // Cookies for subdomains are marked by a leading dot in chrome.
+        let domain = match (subdomains == "TRUE", domain.starts_with('.')) {
+            (true, false) => format!(".{}", domain),
+            _ => domain.to_owned(),
+        };
+        let expires = expires.parse::<u64>().ok().filter(|e| *e > 0).map(|e| e as f64);
+        Some(Cookie {
+            name: name.to_owned(), value: value.to_owned(), domain, path: path.to_owned(),
+            expires, http_only, secure: secure == "TRUE",
+        })
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOKIES: &str = include_str!("../tests/fixtures/cookies.txt");
+
+    #[test]
+    fn reads_and_writes_cookies_txt() {
+        let cookies = from_netscape(COOKIES);
+        assert_eq!(cookies, vec![
+            Cookie {
+                name: "session".into(), value: "abc123".into(), domain: ".example.com".into(), path: "/".into(),
+                expires: Some(1893456000.0), http_only: true, secure: true,
+            },
+            Cookie {
+                name: "theme".into(), value: "dark".into(), domain: "www.example.com".into(), path: "/app".into(),
+                expires: None, http_only: false, secure: false,
+            },
+            Cookie {
+                name: "lang".into(), value: "en".into(), domain: ".example.org".into(), path: "/".into(),
+                expires: None, http_only: false, secure: false,
+            },
+        ]);
+        // Domains are written with their leading dot, as curl does.
+        assert_eq!(from_netscape(&to_netscape(&cookies)), cookies);
+        assert!(to_netscape(&cookies).starts_with("# Netscape HTTP Cookie File\n#HttpOnly_.example.com\tTRUE\t/\tTRUE\t1893456000\tsession\tabc123\n"));
+    }
+}
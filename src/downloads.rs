@@ -0,0 +1,291 @@
+
+use headless_chrome as chrome;
+use super::events::{EtymonEvent, BrowserEvent, EventSender};
+use ratatui::{prelude::*, widgets::*};
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File that completed downloads are recorded in, within the data directory.
+const HISTORY_FILE: &str = "downloads.tsv";
+
+/// Progress of a download through chrome.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DownloadState { InProgress, Completed, Canceled, Failed(String) }
+
+/// A file chrome or wget is saving, or has saved, to the download directory.
+pub struct Download {
+    /// Chrome's id for the download, or one made up for wget, which is also the name the file is saved under until it completes.
+    pub guid: String,
+    pub tab_id: String,
+    pub url: String,
+    /// Name the server suggested for the file.
+    pub filename: String,
+    /// Where the file is kept once complete.
+    pub path: PathBuf,
+    pub received: u64,
+    pub total: Option<u64>,
+    pub state: DownloadState,
+} impl Download {
+
+    /// Moves a completed download from its temporary name to its own in the directory, and records it
+    /// in the history file if there is one. A download that can't be moved fails, and isn't recorded.
+    fn finish(&mut self, dir: &Path, history: Option<&Path>) {
+        let path = unique_path(dir, &self.filename);
+        match std::fs::rename(&self.path, &path) {
+            Ok(()) => {
+                self.path = path;
+                if let Some(history) = history { let _unrecorded = record(history, self); }
+            },
+            Err(error) => self.state = DownloadState::Failed(error.to_string()),
+        }
+    }
+
+    /// MIME type of the file, guessed from its name or else its URL.
+    pub fn mime(&self) -> Option<&'static str> {
+        crate::utils::guess_mime(&self.filename).or_else(|| crate::utils::guess_mime(&self.url))
+    }
+
+    /// Describes how far along the download is.
+    pub fn progress(&self) -> String {
+        match (&self.state, self.total) {
+            (DownloadState::InProgress, Some(total)) if total > 0 => format!("{}% of {}",
+                self.received * 100 / total, format_bytes(total)),
+            (DownloadState::InProgress, _) => format!("{} so far", format_bytes(self.received)),
+            (DownloadState::Completed, _) => format!("Done, {}", format_bytes(self.received)),
+            (DownloadState::Canceled, _) => "Canceled".to_owned(),
+            (DownloadState::Failed(error), _) => format!("Failed: {}", error),
+        }
+    }
+}
+
+/// Every download of the session, newest last, with the one selected in the download list.
+#[derive(Default)]
+pub struct Downloads {
+    pub list: Vec<Download>,
+    pub selected: usize,
+} impl Downloads {
+
+    /// Adds a download chrome has started, selecting it.
+    pub fn start(&mut self, tab_id: &str, guid: &str, url: &str, suggested: &str) -> &Download {
+        self.list.push(Download {
+            guid: guid.to_owned(),
+            tab_id: tab_id.to_owned(),
+            url: url.to_owned(),
+            filename: file_name(suggested, url),
+            path: crate::CONFIG.download_dir.join(guid),
+            received: 0,
+            total: None,
+            state: DownloadState::InProgress,
+        });
+        self.selected = self.list.len() - 1;
+        &self.list[self.selected]
+    }
+
+    /// Updates a download's progress. When it completes, the file is moved from chrome's
+    /// temporary name to its own, and recorded unless amnesia is on.
+    /// Returns the download if it has just finished, one way or another.
+    pub fn update(&mut self, guid: &str, received: u64, total: Option<u64>, state: DownloadState) -> Option<&Download> {
+        let download = self.list.iter_mut().find(|d| d.guid == guid)?;
+        if download.state != DownloadState::InProgress { return None }
+        download.received = received;
+        download.total = total.or(download.total);
+        download.state = state;
+        if download.state == DownloadState::Completed {
+            let history = (!crate::CONFIG.amnesia).then(|| crate::utils::data_dir().join(HISTORY_FILE));
+            download.finish(&crate::CONFIG.download_dir, history.as_deref());
+        }
+        (download.state != DownloadState::InProgress).then_some(&*download)
+    }
+
+    pub fn selected(&self) -> Option<&Download> { self.list.get(self.selected) }
+
+    /// Moves the selection up or down the list.
+    pub fn select(&mut self, step: isize) {
+        self.selected = (self.selected as isize + step).clamp(0, self.list.len().saturating_sub(1) as isize) as usize;
+    }
+
+    /// Lists downloads with their progress.
+    pub fn widget(&self) -> Table<'_> {
+        let rows: Vec<Row> = self.list.iter().map(|d| {
+            let style = match d.state {
+                DownloadState::InProgress => Style::default(),
+                DownloadState::Completed => Style::default().green(),
+                DownloadState::Canceled | DownloadState::Failed(_) => Style::default().dim(),
+            };
+            Row::new(vec![d.filename.clone(), d.progress(), d.url.clone()]).style(style)
+        }).collect();
+        let empty = rows.is_empty();
+        let widths = [Constraint::Percentage(30), Constraint::Length(20), Constraint::Percentage(50)];
+        Table::new(rows, widths)
+            .header(Row::new(vec!["File", "Progress", "From"]).bold())
+            .highlight_style(Style::default().reversed())
+            .block(Block::default().title(match empty {
+                true => "Downloads (none yet)",
+                false => "Downloads: Enter/o opens, c cancels, :open-with <command> picks a program",
+            }))
+    }
+}
+
+/// Has chrome save downloads from a tab to the download directory, and report on them.
+pub fn enable_downloads(tab: &chrome::Tab, events: EventSender) -> Result<(), anyhow::Error> {
+    use chrome::protocol::cdp::{Browser, types::Event::*};
+    std::fs::create_dir_all(&crate::CONFIG.download_dir)?;
+    tab.call_method(Browser::SetDownloadBehavior {
+        // Files are saved under their guid, and renamed by Downloads once complete.
+        behavior: Browser::SetDownloadBehaviorBehaviorOption::AllowAndName,
+        browser_context_id: None,
+        download_path: Some(crate::CONFIG.download_dir.to_string_lossy().into_owned()),
+        events_enabled: Some(true),
+    })?;
+
+    let tab_id = tab.get_target_id().to_owned();
+    tab.add_event_listener(std::sync::Arc::new(move |event: &chrome::protocol::cdp::types::Event| {
+        let event = match event {
+            BrowserDownloadWillBegin(begin) => BrowserEvent::DownloadStarted {
+                tab_id: tab_id.clone(),
+                guid: begin.params.guid.clone(),
+                url: begin.params.url.clone(),
+                filename: begin.params.suggested_filename.clone(),
+            },
+            BrowserDownloadProgress(progress) => BrowserEvent::DownloadProgress {
+                tab_id: tab_id.clone(),
+                guid: progress.params.guid.clone(),
+                received: progress.params.received_bytes as u64,
+                total: (progress.params.total_bytes > 0.0).then_some(progress.params.total_bytes as u64),
+                state: match progress.params.state {
+                    Browser::DownloadProgressEventStateOption::InProgress => DownloadState::InProgress,
+                    Browser::DownloadProgressEventStateOption::Completed => DownloadState::Completed,
+                    Browser::DownloadProgressEventStateOption::Canceled => DownloadState::Canceled,
+                },
+            },
+            _ => return,
+        };
+        let _closed = events.send(EtymonEvent::Browser(event));
+    }))?;
+    Ok(())
+}
+
+/// Asks chrome to stop a download.
+pub fn cancel(tab: &chrome::Tab, guid: &str) -> Result<(), anyhow::Error> {
+    tab.call_method(chrome::protocol::cdp::Browser::CancelDownload { guid: guid.to_owned(), browser_context_id: None })?;
+    Ok(())
+}
+
+/// A safe name to save a download under, from the name the server suggested or else the URL.
+pub fn file_name(suggested: &str, url: &str) -> String {
+    let from_url = url.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+    let name = [suggested, from_url].into_iter()
+        .map(|n| n.replace(['/', '\\', '\0'], "_").trim().trim_start_matches('.').to_owned())
+        .find(|n| !n.is_empty());
+    name.unwrap_or_else(|| "download".to_owned())
+}
+
+/// A path in the directory for the file name that isn't taken, numbering it like "name (1).ext" if need be.
+pub fn unique_path(dir: &Path, filename: &str) -> PathBuf {
+    let (stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (filename, String::new()),
+    };
+    let mut path = dir.join(filename);
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({}){}", stem, n, extension));
+        n += 1;
+    }
+    path
+}
+
+/// Appends a completed download to the history file, as a line of tab separated time, URL and path.
+pub fn record(history: &Path, download: &Download) -> Result<(), anyhow::Error> {
+    if let Some(dir) = history.parent() { std::fs::create_dir_all(dir)? }
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(history)?;
+    writeln!(file, "{}\t{}\t{}", time, download.url.replace(['\t', '\n'], " "), download.path.display())?;
+    Ok(())
+}
+
+/// Formats a byte count with a binary unit, e.g. "1.5 MiB".
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_downloads_safely() {
+        assert_eq!(file_name("report.pdf", "https://example.com/get?id=1"), "report.pdf");
+        assert_eq!(file_name("", "https://example.com/files/archive.zip?token=x"), "archive.zip");
+        assert_eq!(file_name("../../.bashrc", "https://example.com/"), "_.._.bashrc");
+        assert_eq!(file_name("", "https://example.com/"), "download");
+    }
+
+    #[test]
+    fn numbers_taken_paths() {
+        let dir = std::env::temp_dir().join(format!("etymon-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(unique_path(&dir, "report.pdf"), dir.join("report.pdf"));
+        std::fs::write(dir.join("report.pdf"), "").unwrap();
+        std::fs::write(dir.join("report (1).pdf"), "").unwrap();
+        assert_eq!(unique_path(&dir, "report.pdf"), dir.join("report (2).pdf"));
+        std::fs::write(dir.join("README"), "").unwrap();
+        assert_eq!(unique_path(&dir, "README"), dir.join("README (1)"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn records_only_moved_downloads() {
+        let dir = std::env::temp_dir().join(format!("etymon-finish-{}", std::process::id()));
+        let history = dir.join(HISTORY_FILE);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut download = Download {
+            guid: "guid".into(), tab_id: "tab".into(), url: "https://example.com/a.zip".into(),
+            filename: "a.zip".into(), path: dir.join("guid"), received: 3, total: Some(3),
+            state: DownloadState::Completed,
+        };
+
+        // Nothing was saved under the guid, so there is nothing to move.
+        download.finish(&dir, Some(&history));
+        assert!(matches!(download.state, DownloadState::Failed(_)));
+        assert!(!history.exists());
+
+        download.state = DownloadState::Completed;
+        std::fs::write(dir.join("guid"), "zip").unwrap();
+        download.finish(&dir, Some(&history));
+        assert_eq!((&download.state, &download.path), (&DownloadState::Completed, &dir.join("a.zip")));
+        let recorded = std::fs::read_to_string(&history).unwrap();
+        assert!(recorded.ends_with(&format!("\thttps://example.com/a.zip\t{}\n", dir.join("a.zip").display())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn formats_progress() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+
+        let mut download = Download {
+            guid: "guid".into(), tab_id: "tab".into(), url: "https://example.com/a.zip".into(),
+            filename: "a.zip".into(), path: PathBuf::from("guid"), received: 1024, total: Some(4096),
+            state: DownloadState::InProgress,
+        };
+        assert_eq!(download.progress(), "25% of 4.0 KiB");
+        assert_eq!(download.mime(), Some("application/zip"));
+        download.total = None;
+        assert_eq!(download.progress(), "1.0 KiB so far");
+    }
+}
@@ -1,29 +1,68 @@
 
 use headless_chrome as chrome;
 use super::tui;
+use super::events::{EtymonEvent, BrowserEvent, EventSender};
+use super::config::SelectorRules;
+use super::adblock::FilterList;
+use super::downloads::DownloadState;
+use super::profile::Profile;
+use super::cookies::Cookies;
+use super::tui::TuiOverlay;
+use super::backend::{BackendKind, PageBackend, ChromeBackend, ReqwestBackend, CurlBackend};
+use super::tools::{Session, Tool};
+use super::history::{History, Bookmark};
 
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use crossterm::{execute, terminal::{EnterAlternateScreen, LeaveAlternateScreen}};
 
 pub use crate::ui;
 
-/// Handles the communication between headless Chrome browser and terminal UI.
+/// Most images to expand when a page loads with inline_images on.
+const MAX_INLINE_IMAGES: usize = 32;
+
+/// Handles the communication between page backends and terminal UI.
 pub struct Etymon {
-    /// Chrome backend to handle web requests.
-    pub browser: chrome::Browser,
+    /// Headless chrome, launched when the first chrome tab opens.
+    pub browser: Option<chrome::Browser>,
+    /// User data directory chrome was launched with. Dropped after chrome, as it may be deleted.
+    pub profile: Option<Profile>,
+    /// Page backend of each tab, by tab id.
+    pub backends: HashMap<String, Arc<dyn PageBackend>>,
     /// TUI frontend handle to tui webpage HTML.
     pub tui: tui::Tui,
     /// The app will gracefully quit when this evaluates to true.
     pub should_quit: bool,
     /// Holds the current UI mode: View or Write.
     pub mode: ui::UIMode,
+    /// Channel to the main loop, cloned into background tasks.
+    pub events: EventSender,
+    /// Selector rules added during this session, by domain.
+    pub session_rules: HashMap<String, SelectorRules>,
+    /// Network filters shared by every tab, if any filter lists are configured.
+    pub filters: Option<Arc<FilterList>>,
+    /// Process ids of wget downloads in progress, by download guid.
+    pub processes: HashMap<String, u32>,
+    /// Pages visited, for completing addresses.
+    pub history: History,
+    pub bookmarks: Vec<Bookmark>,
 } impl Etymon {
 
     /// Create a new instance of Etymon from configuration parameters.
-    pub fn init() -> Result<Etymon, anyhow::Error> {
-        let browser = chrome::Browser::default()?;
+    pub fn init(events: EventSender) -> Result<Etymon, anyhow::Error> {
         let tui = tui::Tui::init();
-        let mut etymon = Self { browser, tui, should_quit: false, mode: ui::UIMode::View };
-        etymon.new_tab(None)?;
+        let filters = match crate::CONFIG.filter_lists.is_empty() {
+            true => None,
+            false => Some(Arc::new(FilterList::load(&crate::CONFIG.filter_lists)?)),
+        };
+        let mut etymon = Self {
+            browser: None, profile: None, backends: HashMap::new(), tui, should_quit: false, mode: ui::UIMode::View,
+            events, session_rules: HashMap::new(), filters, processes: HashMap::new(),
+            history: History::open(crate::CONFIG.amnesia)?,
+            bookmarks: crate::history::read_bookmarks(&crate::history::bookmarks_path()),
+        };
+        etymon.new_tab(None, crate::CONFIG.backend)?;
         Ok(etymon)
     }
 
@@ -31,48 +70,704 @@ pub struct Etymon {
     pub fn run() -> Result<(), anyhow::Error> {
         execute!(std::io::stdout(), EnterAlternateScreen)?;
 
-        let mut etymon = Etymon::init()?;
+        let runtime = tokio::runtime::Runtime::new()?;
+        let result = runtime.block_on(Etymon::event_loop());
+        // Don't wait on chrome calls that are still in flight.
+        runtime.shutdown_background();
+
+        if crossterm::terminal::is_raw_mode_enabled()? { crossterm::terminal::disable_raw_mode()? }
+        execute!(std::io::stdout(), LeaveAlternateScreen)?;
+        result
+    }
+
+    /// Draws frames at the configured frame rate while handling events as they arrive.
+    async fn event_loop() -> Result<(), anyhow::Error> {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        Etymon::listen_for_input(sender.clone());
+        let mut etymon = Etymon::init(sender)?;
+
+        let mut frames = tokio::time::interval(Duration::from_secs_f32(1.0 / crate::CONFIG.frame_rate));
+        frames.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         loop {
-            etymon.tui.draw()?;
-            etymon.handle_ui()?;
+            tokio::select! {
+                _ = frames.tick() => etymon.tui.draw()?,
+                Some(event) = receiver.recv() => etymon.handle_event(event)?,
+            }
             if etymon.should_quit { break }
         }
+        Ok(())
+    }
 
-        if crossterm::terminal::is_raw_mode_enabled()? { crossterm::terminal::disable_raw_mode()? }
-        execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    /// Polls terminal input on its own thread, waiting at most one tick for each event.
+    fn listen_for_input(events: EventSender) {
+        let tick = Duration::from_secs_f32(1.0 / crate::CONFIG.tick_rate);
+        std::thread::spawn(move || -> Result<(), anyhow::Error> {
+            loop {
+                let event = match crossterm::event::poll(tick)? {
+                    true => EtymonEvent::Input(crossterm::event::read()?),
+                    false => EtymonEvent::Tick,
+                };
+                // Main loop has closed, so stop listening.
+                if events.send(event).is_err() { return Ok(()) }
+            }
+        });
+    }
+
+    /// Dispatches an event received by the main loop.
+    pub fn handle_event(&mut self, event: EtymonEvent) -> Result<(), anyhow::Error> {
+        match event {
+            EtymonEvent::Input(event) => self.handle_ui(event),
+            EtymonEvent::Tick => {
+                self.tui.display.navbar.status.expire_message();
+                self.refresh_changed_tabs();
+                Ok(())
+            },
+            EtymonEvent::Browser(event) => self.handle_browser_event(event),
+        }
+    }
+
+    /// Applies the result of a browser task to the TUI.
+    pub fn handle_browser_event(&mut self, event: BrowserEvent) -> Result<(), anyhow::Error> {
+        // Downloads outlive the tab they started from, so their events are handled even once it is closed.
+        let event = match event {
+            BrowserEvent::DownloadStarted { tab_id, guid, url, filename } => {
+                self.tui.display.start_download(&tab_id, &guid, &url, &filename);
+                return Ok(())
+            },
+            BrowserEvent::DownloadProgress { guid, received, total, state, .. } => {
+                if self.tui.display.update_download(&guid, received, total, state) { self.processes.remove(&guid); }
+                return Ok(())
+            },
+            event => event,
+        };
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.iter_mut().find(|t| t.id == event.tab_id()) else { return Ok(()) };
+        let mut fetch_images = Vec::new();
+        let mut aborted = None;
+        let mut refresh = None;
+        let mut tool = None;
+        match event {
+            BrowserEvent::Loading { url, .. } => {
+                tab.url = url;
+                tab.load = tui::LoadState::Loading(0);
+                tab.blocked = 0;
+                tab.images.clear();
+                tab.expanded_images.clear();
+            },
+            BrowserEvent::Progress { percent, .. } => tab.load = tui::LoadState::Loading(percent),
+            BrowserEvent::Loaded { url, title, dom, .. } => {
+                if let Err(error) = self.history.record(&url, &title) {
+                    display.navbar.status.post(&format!("Could not save history: {}", error));
+                }
+                tab.url = url;
+                tab.update(dom, &title)?;
+                tab.load = tui::LoadState::Loaded;
+                tab.dom_changed = None;
+                tab.last_read = Instant::now();
+                if crate::CONFIG.inline_images && crate::graphics::current_protocol().is_some() {
+                    let sources = tab.image_sources().into_iter().take(MAX_INLINE_IMAGES);
+                    tab.expanded_images.extend(sources.clone());
+                    fetch_images.extend(sources.map(|src| (tab.id.clone(), src)));
+                }
+            },
+            BrowserEvent::Refreshed { title, dom, .. } => {
+                tab.patch(dom, &title);
+                tab.last_read = Instant::now();
+            },
+            BrowserEvent::DomChanged { .. } => {
+                let now = Instant::now();
+                let first = tab.dom_changed.map_or(now, |(first, _)| first);
+                tab.dom_changed = Some((first, now));
+            },
+            BrowserEvent::Blocked { .. } => tab.blocked += 1,
+            BrowserEvent::Image { src, image, .. } => {
+                let state = match image {
+                    Ok(image) => tui::ImageState::Ready(image),
+                    Err(error) => {
+                        display.navbar.status.post(&format!("Image {} failed: {}", src, error));
+                        tui::ImageState::Failed
+                    },
+                };
+                tab.images.insert(src, state);
+                tab.refresh_images();
+            },
+            // Handled above.
+            BrowserEvent::DownloadStarted { .. } | BrowserEvent::DownloadProgress { .. } => (),
+            // Navigating to a file that chrome downloads aborts the navigation, leaving the page as it was.
+            BrowserEvent::Failed { error, .. } if error.contains("net::ERR_ABORTED") => {
+                tab.load = tui::LoadState::Loaded;
+                aborted = Some(tab.id.clone());
+            },
+            BrowserEvent::Failed { error, .. } => {
+                display.navbar.status.post(&format!("Error: {}", error));
+                tab.load = tui::LoadState::Failed(error);
+            },
+            BrowserEvent::Status { message, .. } => display.navbar.status.post(&message),
+            BrowserEvent::CookiesRead { url, list, deleted: None, .. } => {
+                display.overlay = Some(TuiOverlay::Cookies(Cookies::new(&url, list)));
+            },
+            // The list may have been closed, or another opened, while the cookies were deleted.
+            BrowserEvent::CookiesRead { url, list, deleted: Some(count), .. } => {
+                if let Some(TuiOverlay::Cookies(cookies)) = &mut display.overlay {
+                    if cookies.url == url {
+                        cookies.list = list;
+                        cookies.select(0);
+                    }
+                }
+                display.navbar.status.post(&format!("Deleted {} cookies", count));
+            },
+            BrowserEvent::SessionRead { tab_id, url, session, tool: run } => tool = Some((tab_id, url, session, run)),
+            BrowserEvent::ViewportFitted { error: Some(error), .. } => {
+                display.navbar.status.post(&format!("Could not resize the page: {}", error));
+            },
+            // Pages still loading are read at the new size anyway.
+            BrowserEvent::ViewportFitted { tab_id, error: None } => {
+                let in_view = display.tabs.get(display.current_tab).is_some_and(|t| t.id == tab_id);
+                if in_view && display.tabs[display.current_tab].load == tui::LoadState::Loaded { refresh = Some(tab_id) }
+            },
+        }
+        for (id, src) in fetch_images { self.fetch_image(&id, &src) }
+        if let Some(id) = refresh { self.refresh_tab(&id) }
+        if let Some((tab_id, url, session, tool)) = tool {
+            if let Err(error) = self.run_tool(&tab_id, &url, session, tool) {
+                self.tui.display.navbar.status.post(&format!("Error: {}", error));
+            }
+        }
+        if let Some(url) = aborted.as_deref().and_then(|id| self.backends.get(id)).map(|b| b.url()) {
+            if let Some(tab) = self.tui.display.tab_mut(aborted.as_deref().unwrap_or_default()) { tab.url = url }
+        }
         Ok(())
     }
 
-    /// Get access to the Chrome browser backend.
-    pub fn chrome(&self) -> &chrome::Browser { &self.browser }
+    /// Switches input mode, keeping the status bar in step.
+    pub fn set_mode(&mut self, mode: ui::UIMode) {
+        self.mode = mode;
+        self.tui.display.navbar.status.mode = mode;
+        self.tui.display.navbar.completions.clear();
+        self.tui.display.navbar.completion = None;
+    }
+
+    /// Gets headless chrome, launching it if no chrome tab has needed it yet.
+    pub fn chrome(&mut self) -> Result<&chrome::Browser, anyhow::Error> {
+        if self.browser.is_none() {
+            let config = &crate::CONFIG;
+            // A chrome that is already running brings its own profile.
+            let profile = match config.chrome.websocket_url {
+                Some(_) => None,
+                None => Some(Profile::open(config.sync_profile, config.profile_path.as_deref(), config.amnesia)?),
+            };
+            self.browser = Some(crate::browser::launch(profile.as_ref().map(|p| p.path.as_path()))?);
+            self.profile = profile;
+        }
+        Ok(self.browser.as_ref().expect("Chrome is launched"))
+    }
     /// Get mutable access to the TUI tui handle.
     pub fn tui(&mut self) -> &mut tui::Tui { &mut self.tui }
 
-    /// Creates a new tab in the browser backend.
-    pub fn new_tab(&mut self, url: Option<&str>) -> Result<(), anyhow::Error> {
-        let tab = self.chrome().new_tab()?;
+    /// Creates a new tab with a page backend, and loads a URL or the homepage in it.
+    pub fn new_tab(&mut self, url: Option<&str>, kind: BackendKind) -> Result<(), anyhow::Error> {
+        let page = url.unwrap_or(&crate::CONFIG.homepage);
+        let page = crate::address::normalize(page).unwrap_or_else(|| page.to_owned());
+
+        let backend: Arc<dyn PageBackend> = match kind {
+            BackendKind::Chrome => {
+                let tab = self.chrome()?.new_tab()?;
+                if crate::CONFIG.live_updates { crate::browser::watch_dom(&tab, self.events.clone())? }
+                if let Some(filters) = &self.filters { crate::adblock::block_requests(&tab, filters.clone(), self.events.clone())? }
+                crate::downloads::enable_downloads(&tab, self.events.clone())?;
+                Arc::new(ChromeBackend::new(tab))
+            },
+            BackendKind::Reqwest => Arc::new(ReqwestBackend::new()),
+            BackendKind::Curl => Arc::new(CurlBackend::new(Vec::new(), Session::default())),
+        };
+        let id = backend.id().to_owned();
+        self.open_tab(backend, &page)?;
+        if kind == BackendKind::Chrome { self.fit_viewport(&id)? }
+        Ok(())
+    }
+
+    /// Adds a tab for a page backend and loads a URL in it.
+    pub fn open_tab(&mut self, backend: Arc<dyn PageBackend>, url: &str) -> Result<(), anyhow::Error> {
+        let id = backend.id().to_owned();
+        self.tui().display.new_tab(&id, url, backend.kind())?; // TODO Truncate title to first word
+        self.backends.insert(id.clone(), backend);
+        self.load_page(&id, url);
+        Ok(())
+    }
+
+    /// Closes the tab in view and its backend, quitting once no tabs are left.
+    pub fn close_tab(&mut self) {
+        let display = &mut self.tui.display;
+        let Some(id) = display.tabs.get(display.current_tab).map(|t| t.id.clone()) else { return };
+        display.close_tab(&id);
+        if let Some(backend) = self.backends.remove(&id) {
+            tokio::task::spawn_blocking(move || backend.close());
+        }
+        if self.tui.display.tabs.is_empty() { self.quit() }
+    }
+
+    /// Runs a blocking operation on a tab's page backend as a task, sending its result back to the main loop.
+    /// Errors are reported as a failure of the tab.
+    pub fn spawn_backend_task<F>(&self, id: &str, task: F)
+    where F: FnOnce(&dyn PageBackend, &EventSender) -> Result<BrowserEvent, anyhow::Error> + Send + 'static {
+        let Some(backend) = self.backends.get(id).cloned() else { return };
+        let events = self.events.clone();
+        tokio::task::spawn_blocking(move || {
+            let tab_id = backend.id().to_owned();
+            let event = task(backend.as_ref(), &events)
+                .unwrap_or_else(|error| BrowserEvent::Failed { tab_id, error: error.to_string() });
+            let _closed = events.send(EtymonEvent::Browser(event));
+        });
+    }
+
+    /// Loads URL to a tab in the background with the tab's backend. The TUI updates once the page arrives.
+    pub fn load_page(&mut self, id: &str, url: &str) {
+        let url = url.to_owned();
+        let rules = self.session_rules.clone();
+        self.spawn_backend_task(id, move |backend, events| {
+            let tab_id = backend.id().to_owned();
+            let progress = |percent| { let _closed = events.send(EtymonEvent::Browser(BrowserEvent::Progress { tab_id: tab_id.clone(), percent })); };
+            let _closed = events.send(EtymonEvent::Browser(BrowserEvent::Loading { tab_id: tab_id.clone(), url: url.clone() }));
+
+            backend.navigate(&url, &progress)?;
+            let (dom, title) = crate::backend::snapshot(backend, &rules)?;
+            Ok(BrowserEvent::Loaded { tab_id, url: backend.url(), title, dom })
+        });
+    }
+
+    /// Fetches and decodes an image on a tab's page in the background, unless it is already fetched or fetching.
+    pub fn fetch_image(&mut self, id: &str, src: &str) {
+        let Some(tab) = self.tui.display.tab_mut(id) else { return };
+        if tab.images.contains_key(src) { return }
+        let Ok(url) = url::Url::parse(&tab.url).and_then(|base| base.join(src)) else { return };
+        tab.images.insert(src.to_owned(), tui::ImageState::Loading);
+
+        let src = src.to_owned();
+        self.spawn_backend_task(id, move |backend, _| {
+            let image = backend.resource(url.as_str())
+                .and_then(|bytes| crate::graphics::decode(&bytes))
+                .map(Arc::new)
+                .map_err(|e| e.to_string());
+            Ok(BrowserEvent::Image { tab_id: backend.id().to_owned(), src, image })
+        });
+    }
+
+    /// Expands the image under the cursor in place, fetching it if need be, or collapses it back to a placeholder.
+    pub fn toggle_image(&mut self) {
+        let (x, y) = self.tui.cursor_cache;
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get_mut(display.current_tab) else { return };
+        let Some(src) = tab.image_at(x, y).map(|i| i.src.clone()) else {
+            return display.navbar.status.post("No image under the cursor");
+        };
+        if crate::graphics::current_protocol().is_none() {
+            return display.navbar.status.post("Images are shown as placeholders (image_mode = \"placeholder\")");
+        }
+
+        if tab.expanded_images.remove(&src) { return tab.refresh_images() }
+        tab.expanded_images.insert(src.clone());
+        match tab.images.get(&src) {
+            Some(tui::ImageState::Ready(_)) => tab.refresh_images(),
+            Some(tui::ImageState::Loading) => (),
+            // Failed images are fetched again.
+            _ => {
+                tab.images.remove(&src);
+                let id = tab.id.clone();
+                self.fetch_image(&id, &src);
+            },
+        }
+    }
+
+    /// Opens the video or audio under the cursor in the player configured for its type.
+    pub fn play_media(&mut self) {
+        let (x, y) = self.tui.cursor_cache;
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get(display.current_tab) else { return };
+        let status = &mut display.navbar.status;
+        let Some(media) = tab.media_at(x, y) else { return status.post("No video or audio under the cursor") };
+        if media.sources.is_empty() { return status.post("This media has no source to play") }
+        let Some((source, player)) = media.playable(&crate::CONFIG.media_players) else {
+            let mime = media.mime_of(&media.sources[0]);
+            return status.post(&format!("No media player is configured for {}", mime));
+        };
+        let url = match url::Url::parse(&tab.url).and_then(|base| base.join(&source.src)) {
+            Ok(url) => url,
+            Err(error) => return status.post(&format!("Bad media URL {}: {}", source.src, error)),
+        };
+        match crate::utils::spawn_handler(player, url.as_str()) {
+            Ok(()) => status.post(&format!("Playing {} with {}", media.label(), player)),
+            Err(error) => status.post(&format!("Could not start {}: {}", player, error)),
+        }
+    }
+
+    /// Cancels the download selected in the download list.
+    pub fn cancel_download(&mut self) {
+        let downloads = &self.tui.display.downloads;
+        let Some(download) = downloads.selected().filter(|d| d.state == DownloadState::InProgress) else {
+            return self.tui.display.navbar.status.post("No download in progress is selected");
+        };
+        if let Some(pid) = self.processes.get(&download.guid) {
+            let killed = std::process::Command::new("kill").arg(pid.to_string()).status();
+            if !killed.is_ok_and(|s| s.success()) { self.tui.display.navbar.status.post("Could not stop wget") }
+            return
+        }
+        // The download's own tab may have been closed, and any tab can cancel it.
+        let tab = self.chrome_tab(&download.tab_id).or_else(|| self.current_chrome_tab());
+        let result = tab.map(|tab| crate::downloads::cancel(&tab, &download.guid));
+        if let Some(Err(error)) = result { self.tui.display.navbar.status.post(&format!("Could not cancel download: {}", error)) }
+    }
+
+    /// Opens the download selected in the download list with a command,
+    /// or with the handler configured for its type.
+    pub fn open_download(&mut self, command: Option<&str>) {
+        let display = &mut self.tui.display;
+        let status = &mut display.navbar.status;
+        let Some(download) = display.downloads.selected() else { return status.post("No download is selected") };
+        if download.state != DownloadState::Completed { return status.post(&format!("{} has not finished downloading", download.filename)) }
+
+        let mime = download.mime().unwrap_or("application/octet-stream");
+        let Some(handler) = command.or_else(|| crate::utils::handler_for(&crate::CONFIG.download_handlers, mime)) else {
+            return status.post(&format!("No handler is configured for {}; use :open-with <command>", mime));
+        };
+        let path = download.path.to_string_lossy();
+        match crate::utils::spawn_handler(handler, &path) {
+            Ok(()) => status.post(&format!("Opened {} with {}", download.filename, handler)),
+            Err(error) => status.post(&format!("Could not start {}: {}", handler, error)),
+        }
+    }
 
-        let page: String;
-        if let Some(url) = url { page = url.to_owned() }
-        else { let url = crate::CONFIG.homepage.clone(); page = url }
+    /// Runs :cookies, which lists the cookies of the page in view, or with a subcommand
+    /// exports them to or imports them from a cookies.txt file, or clears the page's storage.
+    /// Chrome is asked in the background, and the list or a message comes back as an event.
+    pub fn cookies_command(&mut self, args: &str) {
+        let (subcommand, path) = args.split_once(' ').map_or((args, ""), |(s, p)| (s, p.trim()));
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get(display.current_tab) else { return };
+        let status = &mut display.navbar.status;
+        if tab.backend != BackendKind::Chrome { return status.post("Only chrome tabs keep cookies") }
+        match subcommand {
+            "" | "clear-storage" => (),
+            "export" | "import" if path.is_empty() => return status.post(&format!("Usage: :cookies {} <file>", subcommand)),
+            "export" | "import" => (),
+            _ => return status.post("Usage: :cookies [export <file> | import <file> | clear-storage]"),
+        }
+
+        let (id, url) = (tab.id.clone(), tab.url.clone());
+        let (subcommand, path) = (subcommand.to_owned(), path.to_owned());
+        self.spawn_backend_task(&id, move |backend, _| {
+            let tab_id = backend.id().to_owned();
+            let status = |message: String| BrowserEvent::Status { tab_id: tab_id.clone(), message };
+            let Some(tab) = backend.chrome_tab() else { return Ok(status("Only chrome tabs keep cookies".to_owned())) };
+            let result = match subcommand.as_str() {
+                "" => crate::cookies::read(tab, &url).map(|list| BrowserEvent::CookiesRead { tab_id: tab_id.clone(), url: url.clone(), list, deleted: None }),
+                "export" => crate::cookies::read(tab, &url).and_then(|list| {
+                    std::fs::write(crate::utils::expand_home(&path), crate::cookies::to_netscape(&list))?;
+                    Ok(status(format!("Saved {} cookies to {}", list.len(), path)))
+                }),
+                "import" => std::fs::read_to_string(crate::utils::expand_home(&path)).map_err(anyhow::Error::from).and_then(|text| {
+                    let list = crate::cookies::from_netscape(&text);
+                    crate::cookies::set(tab, &list)?;
+                    Ok(status(format!("Loaded {} cookies from {}", list.len(), path)))
+                }),
+                _ => crate::cookies::clear_storage(tab).map(|()| status("Cleared local and session storage".to_owned())),
+            };
+            Ok(result.unwrap_or_else(|error| status(format!("Cookies: {}", error))))
+        });
+    }
+
+    /// Deletes the cookie selected in the cookie list, or every cookie listed for its domain,
+    /// in the background. The list is read again once they are gone.
+    pub fn delete_cookies(&mut self, whole_domain: bool) {
+        let display = &self.tui.display;
+        let Some(TuiOverlay::Cookies(cookies)) = &display.overlay else { return };
+        let Some(selected) = cookies.selected() else { return };
+        let Some(id) = display.tabs.get(display.current_tab).map(|t| t.id.clone()) else { return };
+        let doomed: Vec<_> = match whole_domain {
+            true => cookies.list.iter().filter(|c| c.domain == selected.domain).cloned().collect(),
+            false => vec![selected.clone()],
+        };
+        let url = cookies.url.clone();
+        self.spawn_backend_task(&id, move |backend, _| {
+            let tab_id = backend.id().to_owned();
+            let result = backend.chrome_tab().ok_or_else(|| anyhow::anyhow!("only chrome tabs keep cookies"))
+                .and_then(|tab| crate::cookies::delete(tab, &doomed).and_then(|()| crate::cookies::read(tab, &url)));
+            Ok(match result {
+                Ok(list) => BrowserEvent::CookiesRead { tab_id, url, list, deleted: Some(doomed.len()) },
+                Err(error) => BrowserEvent::Status { tab_id, message: format!("Could not delete cookies: {}", error) },
+            })
+        });
+    }
+
+    /// Gathers what the tab in view would send with a request to a URL in the background: its user agent,
+    /// its address as the referer, and the cookies chrome has for the URL. The tool runs once they arrive.
+    pub fn request_session(&mut self, url: String, tool: Tool) {
+        let display = &self.tui.display;
+        let Some(tab) = display.tabs.get(display.current_tab) else { return };
+        let referer = Some(tab.url.clone()).filter(|u| u.starts_with("http"));
+        self.spawn_backend_task(&tab.id, move |backend, _| {
+            let user_agent = match backend.kind() {
+                BackendKind::Chrome => backend.eval("navigator.userAgent").ok().and_then(|a| a.as_str().map(str::to_owned)),
+                _ => Some(crate::fetch::USER_AGENT.to_owned()),
+            };
+            let cookies = backend.chrome_tab().and_then(|t| crate::cookies::read(t, &url).ok()).unwrap_or_default();
+            let session = Session { user_agent, referer, cookies };
+            Ok(BrowserEvent::SessionRead { tab_id: backend.id().to_owned(), url, session, tool })
+        });
+    }
+
+    /// Runs a tool with the session gathered from a tab.
+    pub fn run_tool(&mut self, tab_id: &str, url: &str, session: Session, tool: Tool) -> Result<(), anyhow::Error> {
+        match tool {
+            Tool::Curl(flags) => self.open_tab(Arc::new(CurlBackend::new(flags, session)), url),
+            Tool::Wget(flags) => self.start_wget(tab_id, url, &flags, session),
+        }
+    }
+
+    /// Runs :c, which shows curl's output for a URL, or the page in view, in a new tab.
+    /// Flags before the URL are passed on to curl.
+    pub fn curl_page(&mut self, args: &str) -> Result<(), anyhow::Error> {
+        let (flags, url) = crate::tools::parse_args(args);
+        let display = &self.tui.display;
+        let Some(url) = url.or_else(|| display.tabs.get(display.current_tab).map(|t| t.url.clone())) else {
+            self.tui.display.navbar.status.post("Usage: :c [flags] [--] <url>");
+            return Ok(())
+        };
+        self.request_session(url, Tool::Curl(flags));
+        Ok(())
+    }
+
+    /// Runs :w, which downloads a URL, or the page in view, with wget into the download directory.
+    /// Flags before the URL are passed on to wget.
+    pub fn wget_download(&mut self, args: &str) -> Result<(), anyhow::Error> {
+        let (flags, url) = crate::tools::parse_args(args);
+        let display = &self.tui.display;
+        let Some(url) = url.or_else(|| display.tabs.get(display.current_tab).map(|t| t.url.clone())) else {
+            self.tui.display.navbar.status.post("Usage: :w [flags] [--] <url>");
+            return Ok(())
+        };
+        self.request_session(url, Tool::Wget(flags));
+        Ok(())
+    }
+
+    /// Starts wget downloading a URL for a tab, tracking it in the download list.
+    fn start_wget(&mut self, tab_id: &str, url: &str, flags: &[String], session: Session) -> Result<(), anyhow::Error> {
+        std::fs::create_dir_all(&crate::CONFIG.download_dir)?;
+
+        // Saved under its id until complete, like chrome's downloads, then renamed by Downloads.
+        let guid = crate::tools::download_id();
+        let download = self.tui.display.downloads.start(tab_id, &guid, url, "");
+        let path = download.path.clone();
+        self.tui.display.navbar.status.post(&format!("Downloading {} with wget (D lists downloads)", download.filename));
+
+        use std::process::Stdio;
+        let child = crate::tools::CookieJar::write(&session, false).and_then(|jar| {
+            let child = crate::tools::wget(url, flags, &session, jar.as_ref(), &path)
+                .stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::piped())
+                .spawn()?;
+            Ok((child, jar))
+        });
+        let (child, jar) = match child {
+            Ok(child) => child,
+            Err(error) => {
+                let state = DownloadState::Failed(format!("Could not start wget: {}", error));
+                self.tui.display.downloads.update(&guid, 0, None, state);
+                return Ok(())
+            },
+        };
+        self.processes.insert(guid.clone(), child.id());
+        let events = self.events.clone();
+        let tab_id = tab_id.to_owned();
+        tokio::task::spawn_blocking(move || {
+            use std::os::unix::process::ExitStatusExt;
+            let state = match child.wait_with_output() {
+                Ok(output) if output.status.success() => DownloadState::Completed,
+                Ok(output) if output.status.signal().is_some() => DownloadState::Canceled,
+                Ok(output) => DownloadState::Failed(match String::from_utf8_lossy(&output.stderr).trim() {
+                    "" => output.status.to_string(),
+                    error => error.to_owned(),
+                }),
+                Err(error) => DownloadState::Failed(error.to_string()),
+            };
+            // wget has read its cookies by now.
+            drop(jar);
+            let received = std::fs::metadata(&path).map_or(0, |m| m.len());
+            let event = BrowserEvent::DownloadProgress { tab_id, guid, received, total: None, state };
+            let _closed = events.send(EtymonEvent::Browser(event));
+        });
+        Ok(())
+    }
+
+    /// Fetches a URL with reqwest instead of chrome, in the tab in view if it uses reqwest or else in a new tab.
+    /// Nothing on the page runs, so this is quicker for static pages and suits API requests.
+    pub fn request_page(&mut self, url: &str) -> Result<(), anyhow::Error> {
+        let display = &mut self.tui.display;
+        if url.is_empty() { display.navbar.status.post("Usage: :r <url>"); return Ok(()) }
+        // Anything that isn't an address is still tried as a host.
+        let url = crate::address::normalize(url).unwrap_or_else(|| format!("https://{}", url));
+        match display.tabs.get(display.current_tab).filter(|t| t.backend == BackendKind::Reqwest).map(|t| t.id.clone()) {
+            Some(id) => self.load_page(&id, &url),
+            None => self.new_tab(Some(&url), BackendKind::Reqwest)?,
+        }
+        Ok(())
+    }
+
+    /// Searches the web, with the engine whose keyword the search starts with or else the default engine.
+    /// DuckDuckGo results are fetched with reqwest and listed natively if configured.
+    pub fn search(&mut self, input: &str) -> Result<(), anyhow::Error> {
+        let config = &crate::CONFIG;
+        if input.trim().is_empty() { self.tui.display.navbar.status.post("Usage: :s [keyword] <search>"); return Ok(()) }
+        let Some((engine, query)) = crate::search::resolve(&config.search_engines, &config.search_engine, input) else {
+            self.tui.display.navbar.status.post("No search engines are configured");
+            return Ok(())
+        };
+        let url = engine.url_for(query);
+        match config.native_search_results && crate::search::has_native_results(&url) {
+            true => self.request_page(&url),
+            false => self.open_url(&url),
+        }
+    }
+
+    /// Opens text typed in the command line, as an address once normalized or else as a search.
+    pub fn go(&mut self, input: &str) -> Result<(), anyhow::Error> {
+        match crate::address::normalize(input) {
+            Some(url) => self.open_url(&url),
+            None => self.search(input),
+        }
+    }
+
+    /// Bookmarks the page in view, saving it to the bookmarks file.
+    pub fn bookmark(&mut self) {
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get(display.current_tab) else { return };
+        if self.bookmarks.iter().any(|b| b.url == tab.url) {
+            display.navbar.status.post(&format!("{} is already bookmarked", tab.url));
+            return
+        }
+        let bookmark = Bookmark { url: tab.url.clone(), title: tab.title.clone() };
+        match crate::history::add_bookmark(&crate::history::bookmarks_path(), &bookmark) {
+            Ok(()) => {
+                display.navbar.status.post(&format!("Bookmarked {}", bookmark.url));
+                self.bookmarks.push(bookmark);
+            },
+            Err(error) => display.navbar.status.post(&format!("Could not save bookmark: {}", error)),
+        }
+    }
+
+    /// Offers pages from open tabs, bookmarks and history for the address being typed in the command line.
+    pub fn update_completions(&mut self) {
+        let display = &mut self.tui.display;
+        let navbar = &mut display.navbar;
+        navbar.completion = None;
+        let Some(start) = ui::address_start(&navbar.command) else { navbar.completions.clear(); return };
+        let tabs: Vec<(String, String)> = display.tabs.iter().map(|t| (t.url.clone(), t.title.clone())).collect();
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        navbar.completions = crate::address::complete(&navbar.command[start..], &tabs, &self.bookmarks, &self.history, now);
+    }
+
+    /// Loads a URL in the tab in view with its own backend, or in a new tab if none are open.
+    pub fn open_url(&mut self, url: &str) -> Result<(), anyhow::Error> {
+        match self.tui.display.tabs.get(self.tui.display.current_tab).map(|t| t.id.clone()) {
+            Some(id) => self.load_page(&id, url),
+            None => self.new_tab(Some(url), crate::CONFIG.backend)?,
+        }
+        Ok(())
+    }
+
+    /// Takes a new snapshot of a tab's page without navigating, patching it into the display.
+    pub fn refresh_tab(&mut self, id: &str) {
+        let rules = self.session_rules.clone();
+        self.spawn_backend_task(id, move |backend, _| {
+            let (dom, title) = crate::backend::snapshot(backend, &rules)?;
+            Ok(BrowserEvent::Refreshed { tab_id: backend.id().to_owned(), title, dom })
+        });
+    }
+
+    /// Re-reads pages whose DOM has settled after changing, or whose poll interval has passed.
+    /// Changes that never settle still refresh the page after several debounce periods.
+    pub fn refresh_changed_tabs(&mut self) {
+        let debounce = Duration::from_millis(crate::CONFIG.update_debounce);
+        let poll = crate::CONFIG.update_poll.map(Duration::from_millis);
+
+        let mut stale = Vec::new();
+        for tab in self.tui.display.tabs.iter_mut().filter(|t| t.load == tui::LoadState::Loaded) {
+            let settled = tab.dom_changed.is_some_and(|(first, last)| {
+                last.elapsed() >= debounce || first.elapsed() >= debounce * 5
+            });
+            let polled = poll.is_some_and(|poll| tab.last_read.elapsed() >= poll);
+            if settled || polled {
+                tab.dom_changed = None;
+                // Avoids queueing more reads while this one is in flight.
+                tab.last_read = Instant::now();
+                stale.push(tab.id.clone());
+            }
+        }
+        for id in stale { self.refresh_tab(&id) }
+    }
+
+    /// Adds a session rule hiding the block under the cursor, or one of its ancestors,
+    /// on every page of the current domain.
+    pub fn hide_focused(&mut self, levels_up: usize) {
+        let (x, y) = self.tui.cursor_cache;
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get(display.current_tab) else { return };
+        let selector = tab.block_at(x, y).and_then(|b| b.selectors.get(levels_up).cloned());
+        let host = url::Url::parse(&tab.url).ok().and_then(|u| u.host_str().map(str::to_owned));
+
+        match (selector, host) {
+            (Some(selector), Some(host)) => {
+                display.navbar.status.post(&format!("Hiding {} on {}", selector, host));
+                self.session_rules.entry(host).or_default().hide.push(selector);
+                let id = display.tabs.get(display.current_tab).map(|t| t.id.clone());
+                if let Some(id) = id { self.refresh_tab(&id) }
+            },
+            _ => display.navbar.status.post("No element to hide under the cursor"),
+        }
+    }
+
+    /// Finds the chrome tab backing the TuiTab in view.
+    pub fn current_chrome_tab(&self) -> Option<Arc<chrome::Tab>> {
+        let tab = self.tui.display.tabs.get(self.tui.display.current_tab)?;
+        self.chrome_tab(&tab.id)
+    }
+
+    /// Finds the chrome tab backing a TuiTab, if it uses chrome.
+    pub fn chrome_tab(&self, id: &str) -> Option<Arc<chrome::Tab>> {
+        self.backends.get(id)?.chrome_tab().cloned()
+    }
 
-        self.tui().display.new_tab(tab.get_target_id(), &tab.get_title()?)?; // TODO Truncate title to first word
-        self.load_page(&tab, &page)?;
+    /// Responds to the terminal changing size by rewrapping pages
+    /// and, if configured, resizing the chrome viewports to match.
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<(), anyhow::Error> {
+        self.tui().resize(width, height)?;
+        if !crate::CONFIG.match_viewport { return Ok(()) }
+        let ids: Vec<String> = self.tui.display.tabs.iter().filter(|t| t.backend == BackendKind::Chrome).map(|t| t.id.clone()).collect();
+        for id in ids { self.fit_viewport(&id)? }
         Ok(())
     }
 
-    /// Loads URL to browser tab and updates representation in TUI.
-    pub fn load_page(&mut self, tab: &chrome::Tab, url: &str) -> Result<(), anyhow::Error> {
-        tab.navigate_to(url)?
-            .wait_until_navigated()?;
-        self.tui().display.update_tab(&tab)?; // TODO Test
+    /// Sets a chrome tab's viewport to the pixel size of the terminal page view in the background.
+    /// Responsive layouts may change the page content, so the tab is refreshed once it is set if it is in view.
+    pub fn fit_viewport(&mut self, id: &str) -> Result<(), anyhow::Error> {
+        if !crate::CONFIG.match_viewport { return Ok(()) }
+        let (cell_w, cell_h) = crate::CONFIG.cell_size;
+        let width = self.tui().page_columns()? as u32 * cell_w as u32;
+        let height = self.tui().terminal.size()?.height as u32 * cell_h as u32;
+        self.spawn_backend_task(id, move |backend, _| {
+            let tab_id = backend.id().to_owned();
+            let error = match backend.chrome_tab() {
+                Some(tab) => crate::browser::set_viewport(tab, width, height).err().map(|e| e.to_string()),
+                None => None,
+            };
+            Ok(BrowserEvent::ViewportFitted { tab_id, error })
+        });
         Ok(())
     }
 
     /// Gracefully exit to shell.
     pub fn quit(&mut self) {
         self.should_quit = true;
-        if let Err(_) = self.tui().quit() {
+        if self.tui().quit().is_err() {
             panic!("Error: This panic was used to exit the terminal. Toodaloo!")
         }
     }
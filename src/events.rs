@@ -0,0 +1,71 @@
+
+use crate::tui::TuiNode;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Handle used by input and browser tasks to report back to the main loop.
+pub type EventSender = UnboundedSender<EtymonEvent>;
+
+/// Everything the main loop reacts to, besides drawing frames.
+pub enum EtymonEvent {
+    /// Terminal input read by the input thread.
+    Input(crossterm::event::Event),
+    /// Sent when no input arrives within a tick, so timed state can be updated.
+    Tick,
+    /// Result or progress of a browser operation running in the background.
+    Browser(BrowserEvent),
+}
+
+/// Reports from chrome operations that run outside of the main loop.
+pub enum BrowserEvent {
+    /// A tab has started navigating to a URL.
+    Loading { tab_id: String, url: String },
+    /// Load progress of a tab, as a percentage.
+    Progress { tab_id: String, percent: u8 },
+    /// A snapshot of the tab's page, ready to be displayed.
+    Loaded { tab_id: String, url: String, title: String, dom: TuiNode },
+    /// A fresh snapshot of a page that is already displayed, to be patched in.
+    Refreshed { tab_id: String, title: String, dom: TuiNode },
+    /// Chrome reported a change to the tab's DOM.
+    DomChanged { tab_id: String },
+    /// A request made by the tab's page was blocked by the filter lists.
+    Blocked { tab_id: String, url: String },
+    /// An image on the tab's page was fetched and decoded, or failed to be.
+    Image { tab_id: String, src: String, image: Result<std::sync::Arc<image::DynamicImage>, String> },
+    /// Chrome started downloading a file from the tab.
+    DownloadStarted { tab_id: String, guid: String, url: String, filename: String },
+    /// Progress of a download from the tab.
+    DownloadProgress { tab_id: String, guid: String, received: u64, total: Option<u64>, state: crate::downloads::DownloadState },
+    /// Cookies chrome has for a URL, read from the tab, after deleting some of them if Some.
+    CookiesRead { tab_id: String, url: String, list: Vec<crate::cookies::Cookie>, deleted: Option<usize> },
+    /// What the tab would send with a request to a URL, gathered to run a tool with.
+    SessionRead { tab_id: String, url: String, session: crate::tools::Session, tool: crate::tools::Tool },
+    /// A message about an operation on the tab, for the status bar.
+    Status { tab_id: String, message: String },
+    /// The tab's viewport was resized to fit the terminal, or failed to be.
+    ViewportFitted { tab_id: String, error: Option<String> },
+    /// A browser operation on the tab failed.
+    Failed { tab_id: String, error: String },
+}
+
+impl BrowserEvent {
+    /// Identifies the tab the event belongs to.
+    pub fn tab_id(&self) -> &str {
+        match self {
+            BrowserEvent::Loading { tab_id, .. }  |
+            BrowserEvent::Progress { tab_id, .. } |
+            BrowserEvent::Loaded { tab_id, .. }   |
+            BrowserEvent::Refreshed { tab_id, .. } |
+            BrowserEvent::DomChanged { tab_id }   |
+            BrowserEvent::Blocked { tab_id, .. }  |
+            BrowserEvent::Image { tab_id, .. }    |
+            BrowserEvent::DownloadStarted { tab_id, .. }  |
+            BrowserEvent::DownloadProgress { tab_id, .. } |
+            BrowserEvent::CookiesRead { tab_id, .. } |
+            BrowserEvent::SessionRead { tab_id, .. } |
+            BrowserEvent::Status { tab_id, .. } |
+            BrowserEvent::ViewportFitted { tab_id, .. } |
+            BrowserEvent::Failed { tab_id, .. }   => tab_id,
+        }
+    }
+}
@@ -0,0 +1,285 @@
+
+use super::browser::parse_content;
+use super::proxy::ProxyRules;
+
+use std::time::Duration;
+
+/*
+ Lightweight page loading without chrome, for the reqwest backend.
+    Pages are fetched with a blocking reqwest client, so fetches run as blocking tasks.
+    Scripts don't run, which suits static pages and APIs.
+    HTML is kept as it is for the same filtering as chrome pages; JSON and text are wrapped up as preformatted HTML.
+    file: addresses are read from disk and about: pages are blank, as neither goes over the network.
+*/
+
+/// Longest time to wait on a server before giving up.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// User agent pages are fetched with.
+pub const USER_AGENT: &str = concat!("Etymon/", env!("CARGO_PKG_VERSION"));
+/// Responses larger than this are not rendered.
+const MAX_BODY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// A response fetched without chrome.
+pub struct Response {
+    /// URL the response came from, after redirects.
+    pub url: String,
+    pub content_type: Option<String>,
+    pub body: String,
+}
+
+/// Ways of rendering a response.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContentKind { Html, Json, Text }
+impl ContentKind {
+
+    /// Picks how to render a response by its content type, or by its body if there is no type.
+    /// Returns None for content that isn't text, like images or archives.
+    pub fn of(content_type: Option<&str>, body: &str) -> Option<ContentKind> {
+        let mime = content_type.unwrap_or_default().split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => Some(ContentKind::Html),
+            "application/json" | "text/json" => Some(ContentKind::Json),
+            _ if mime.ends_with("+json") => Some(ContentKind::Json),
+            "application/xml" | "application/javascript" | "application/x-sh" => Some(ContentKind::Text),
+            _ if mime.starts_with("text/") || mime.ends_with("+xml") => Some(ContentKind::Text),
+            "" | "application/octet-stream" => {
+                let start = body.trim_start();
+                if serde_json::from_str::<serde_json::Value>(body).is_ok() { Some(ContentKind::Json) }
+                else if start.starts_with('<') { Some(ContentKind::Html) }
+                else if !body.contains('\0') { Some(ContentKind::Text) }
+                else { None }
+            },
+            _ => None,
+        }
+    }
+}
+
+fn client() -> Result<reqwest::blocking::Client, anyhow::Error> {
+    client_for(&ProxyRules::from_config(&crate::CONFIG)?)
+}
+
+/// A client that sends requests through proxies by the rules.
+pub fn client_for(proxies: &ProxyRules) -> Result<reqwest::blocking::Client, anyhow::Error> {
+    Ok(reqwest::blocking::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(TIMEOUT)
+        .proxy(proxies.reqwest_proxy())
+        .build()?)
+}
+
+/// Gets a URL with reqwest. Blocks, so it should be called from a blocking task.
+pub fn fetch(url: &str) -> Result<Response, anyhow::Error> {
+    if let Some(response) = local_response(url) { return response }
+    let response = client()?.get(url).send()?.error_for_status()?;
+    let url = response.url().to_string();
+    let content_type = response.headers().get(reqwest::header::CONTENT_TYPE)
+        .and_then(|t| t.to_str().ok())
+        .map(str::to_owned);
+    let bytes = read_limited(response, MAX_BODY_BYTES)?;
+    Ok(Response { body: decode(&bytes, content_type.as_deref()), url, content_type })
+}
+
+/// Reads a response body, failing once it passes the limit, whether or not the server gave its length.
+fn read_limited(response: reqwest::blocking::Response, limit: u64) -> Result<Vec<u8>, anyhow::Error> {
+    use std::io::Read;
+    let too_large = |url: &reqwest::Url| anyhow::anyhow!("{} is over {}, too large to read", url, crate::downloads::format_bytes(limit));
+    if response.content_length().is_some_and(|len| len > limit) { return Err(too_large(response.url())) }
+    let url = response.url().clone();
+    let mut body = Vec::new();
+    response.take(limit + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > limit { return Err(too_large(&url)) }
+    Ok(body)
+}
+
+/// Decodes a body by the charset in its content type, or as UTF-8, as reqwest would.
+fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let charset = content_type.unwrap_or_default().split(';')
+        .filter_map(|p| p.trim().split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+        .map(|(_, v)| v.trim().trim_matches('"'));
+    let encoding = charset.and_then(|c| encoding_rs::Encoding::for_label(c.as_bytes())).unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+/// Reads a file: address from disk, or an about: page as a blank page, and None for any other address.
+fn local_response(url: &str) -> Option<Result<Response, anyhow::Error>> {
+    let parsed = url::Url::parse(url).ok()?;
+    match parsed.scheme() {
+        "about" => Some(Ok(Response { url: url.to_owned(), content_type: Some("text/html".to_owned()), body: String::new() })),
+        "file" => Some(read_file(&parsed)),
+        _ => None,
+    }
+}
+
+fn read_file(url: &url::Url) -> Result<Response, anyhow::Error> {
+    let path = url.to_file_path().map_err(|_| anyhow::anyhow!("{} is not a local file", url))?;
+    if std::fs::metadata(&path)?.len() > MAX_BODY_BYTES { return Err(anyhow::anyhow!("{} is too large to show", url)) }
+    // Files have no content type, so only their extension tells HTML from text that starts with a tag.
+    let content_type = match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("html" | "htm" | "xhtml") => Some("text/html".to_owned()),
+        Some("json") => Some("application/json".to_owned()),
+        Some("txt" | "md" | "xml" | "csv") => Some("text/plain".to_owned()),
+        _ => None,
+    };
+    let body = String::from_utf8(std::fs::read(&path)?).map_err(|_| anyhow::anyhow!("{} can't be shown as text", url))?;
+    Ok(Response { url: url.to_string(), content_type, body })
+}
+
+/// Gets a resource, like an image, as raw bytes.
+pub fn fetch_bytes(url: &str) -> Result<Vec<u8>, anyhow::Error> {
+    if let Some(file) = url::Url::parse(url).ok().filter(|u| u.scheme() == "file") {
+        let path = file.to_file_path().map_err(|_| anyhow::anyhow!("{} is not a local file", url))?;
+        return Ok(std::fs::read(path)?)
+    }
+    let response = client()?.get(url).send()?.error_for_status()?;
+    read_limited(response, MAX_BODY_BYTES)
+}
+
+/// Turns a response into HTML to be read like any other page, along with a title for its tab.
+/// HTML is left as it is; JSON is pretty-printed, and text is shown as it is.
+pub fn page_html(response: &Response) -> Result<(String, String), anyhow::Error> {
+    let kind = ContentKind::of(response.content_type.as_deref(), &response.body).ok_or_else(|| anyhow::anyhow!(
+        "{} is {}, which can't be shown as text",
+        response.url, response.content_type.as_deref().unwrap_or("binary data"),
+    ))?;
+    let file = response.url.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+    let untitled = match file.is_empty() { true => response.url.clone(), false => file.to_owned() };
+
+    match kind {
+        ContentKind::Html => {
+            let title = parse_content(&response.body).select_first("title").ok()
+                .map(|t| t.text_contents().trim().to_owned())
+                .filter(|t| !t.is_empty());
+            Ok((response.body.clone(), title.unwrap_or(untitled)))
+        },
+        ContentKind::Json => {
+            // Bodies served as JSON that don't parse are shown as they are.
+            let text = serde_json::from_str::<serde_json::Value>(&response.body)
+                .and_then(|json| serde_json::to_string_pretty(&json))
+                .unwrap_or_else(|_| response.body.clone());
+            Ok((preformatted(&text, &untitled), untitled))
+        },
+        ContentKind::Text => Ok((preformatted(&response.body, &untitled), untitled)),
+    }
+}
+
+/// Wraps text up as a page of a single preformatted block, keeping its line breaks and spacing.
+pub fn preformatted(text: &str, title: &str) -> String {
+    format!("<!DOCTYPE html><html><head><title>{}</title></head><body><pre>{}</pre></body></html>", escape_html(title), escape_html(text))
+}
+
+/// Escapes text to be placed in HTML, within elements or quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const ARTICLE: &str = include_str!("../tests/fixtures/article.html");
+
+    /// Serves each path with a fixed content type and body on a local port.
+    fn stand_in_server(routes: Vec<(&'static str, &'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() { continue }
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok_and(|n| n > 2) { header.clear() }
+            let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+            let reply = match routes.iter().find(|(p, _, _)| *p == path) {
+                Some((_, content_type, body)) => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type, body.len(), body),
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+            };
+            let _ = (&stream).write_all(reply.as_bytes());
+        });
+        address
+    }
+
+    /// Fetches a page and reads the text of its blocks, along with its title.
+    fn read(url: &str) -> Result<(Vec<String>, String), anyhow::Error> {
+        let (html, title) = page_html(&fetch(url)?)?;
+        let mut dom = filter_dom_html(&html, &SelectorRules::default()).to_tui();
+        dom.collapse_phantoms();
+        Ok((dom.text_blocks().into_iter().map(|b| b.text).collect(), title))
+    }
+
+    #[test]
+    fn renders_fetched_content_by_type() {
+        let server = stand_in_server(vec![
+            ("/article", "text/html; charset=utf-8", ARTICLE),
+            ("/api/items", "application/json", r#"{"items":[1,2],"next":null}"#),
+            ("/notes.txt", "text/plain", "first line\n  indented <tag> & more"),
+            ("/logo.png", "image/png", "\u{89}PNG"),
+        ]);
+
+        let (text, title) = read(&format!("{}/article", server)).unwrap();
+        assert_eq!(title, "Page title | Example News");
+        assert!(text.iter().any(|t| t.starts_with("Terminal browsers")));
+
+        let (text, title) = read(&format!("{}/api/items", server)).unwrap();
+        assert_eq!(title, "items");
+        assert_eq!(text, ["{\n  \"items\": [\n    1,\n    2\n  ],\n  \"next\": null\n}"]);
+
+        let (text, _) = read(&format!("{}/notes.txt", server)).unwrap();
+        assert_eq!(text, ["first line\n  indented <tag> & more"]);
+
+        assert!(read(&format!("{}/logo.png", server)).is_err());
+        assert_eq!(fetch_bytes(&format!("{}/logo.png", server)).unwrap(), "\u{89}PNG".as_bytes());
+        assert!(fetch(&format!("{}/missing", server)).is_err());
+    }
+
+    #[test]
+    fn reads_local_pages() {
+        let path = std::env::temp_dir().join(format!("etymon-fetch-{}.html", std::process::id()));
+        std::fs::write(&path, ARTICLE).unwrap();
+        let url = url::Url::from_file_path(&path).unwrap().to_string();
+        let (text, title) = read(&url).unwrap();
+        assert_eq!(title, "Page title | Example News");
+        assert!(text.iter().any(|t| t.starts_with("Terminal browsers")));
+        assert_eq!(fetch_bytes(&url).unwrap(), ARTICLE.as_bytes());
+        std::fs::remove_file(&path).unwrap();
+        assert!(fetch(&url).is_err());
+
+        let (text, title) = read("about:blank").unwrap();
+        assert!(text.is_empty());
+        assert_eq!(title, "about:blank");
+    }
+
+    #[test]
+    fn limits_bodies_without_a_length() {
+        // Replies with a body of unknown length, ended by closing the connection.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/stream", listener.local_addr().unwrap());
+        std::thread::spawn(move || for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok_and(|n| n > 2) { header.clear() }
+            let _ = (&stream).write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=iso-8859-1\r\nConnection: close\r\n\r\ncaf\xe9 ");
+            let _ = (&stream).write_all(&[b'x'; 4096]);
+        });
+        let get = || client().unwrap().get(&url).send().unwrap();
+        assert_eq!(read_limited(get(), 5000).unwrap().len(), 4101);
+        assert!(read_limited(get(), 4100).unwrap_err().to_string().contains("too large"));
+        assert!(decode(&read_limited(get(), 5000).unwrap(), Some("text/plain; charset=iso-8859-1")).starts_with("café x"));
+    }
+
+    #[test]
+    fn sniffs_untyped_content() {
+        assert_eq!(ContentKind::of(None, " [1, 2]"), Some(ContentKind::Json));
+        assert_eq!(ContentKind::of(Some(""), "<p>Hi</p>"), Some(ContentKind::Html));
+        assert_eq!(ContentKind::of(None, "plain"), Some(ContentKind::Text));
+        assert_eq!(ContentKind::of(Some("application/problem+json"), "{}"), Some(ContentKind::Json));
+        assert_eq!(ContentKind::of(Some("application/pdf"), "%PDF"), None);
+    }
+}
@@ -0,0 +1,178 @@
+
+use super::tui::TuiBlock;
+use ratatui::prelude::*;
+
+/// How text is matched when finding it on a page.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    /// Treat the search as a regular expression rather than plain text.
+    pub regex: bool,
+}
+
+/// A match of the search, as a range of chars in one of the blocks of the page or of a landmark pane.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FindMatch {
+    /// Landmark pane the match is in, or None for the page itself.
+    pub pane: Option<usize>,
+    pub block: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A search for text on the page, with every match and the one in focus.
+pub struct Find {
+    pub query: String,
+    pub options: FindOptions,
+    pattern: regex::Regex,
+    pub matches: Vec<FindMatch>,
+    pub current: usize,
+} impl Find {
+
+    /// Style of every match on the page.
+    pub const MATCH_STYLE: Style = Style::new().fg(Color::Black).bg(Color::Yellow);
+    /// Style of the match in focus.
+    pub const CURRENT_STYLE: Style = Style::new().fg(Color::Black).bg(Color::LightRed);
+
+    /// Reads a search typed after :f, where leading -c makes it case sensitive and -r a regular expression.
+    pub fn parse(args: &str) -> Result<Find, anyhow::Error> {
+        let mut options = FindOptions::default();
+        let mut query = args.trim_start();
+        loop {
+            match query.split_once(' ').unwrap_or((query, "")) {
+                ("-c", _) => options.case_sensitive = true,
+                ("-r", _) => options.regex = true,
+                ("-cr" | "-rc", _) => { options.case_sensitive = true; options.regex = true },
+                _ => break,
+            }
+            query = query.split_once(' ').map_or("", |(_, rest)| rest);
+        }
+        Find::new(query, options)
+    }
+
+    pub fn new(query: &str, options: FindOptions) -> Result<Find, anyhow::Error> {
+        let source = match options.regex { true => query.to_owned(), false => regex::escape(query) };
+        let pattern = regex::RegexBuilder::new(&source).case_insensitive(!options.case_sensitive).build()?;
+        Ok(Find { query: query.to_owned(), options, pattern, matches: Vec::new(), current: 0 })
+    }
+
+    /// Finds every match in the page's blocks and then in each pane's, keeping focus on the same match where it is still found.
+    pub fn search(&mut self, page: &[TuiBlock], panes: &[&[TuiBlock]]) {
+        let focused = self.matches.get(self.current).copied();
+        self.matches.clear();
+        if self.query.is_empty() { return }
+        let sections = std::iter::once((None, page)).chain(panes.iter().enumerate().map(|(p, blocks)| (Some(p), *blocks)));
+        for (pane, blocks) in sections {
+            for (b, block) in blocks.iter().enumerate() {
+                // Matches are found by byte, but blocks are laid out by char.
+                let chars: Vec<usize> = block.text.char_indices().map(|(i, _)| i).collect();
+                let char_at = |byte: usize| chars.partition_point(|&i| i < byte);
+                for found in self.pattern.find_iter(&block.text).filter(|m| !m.is_empty()) {
+                    self.matches.push(FindMatch { pane, block: b, start: char_at(found.start()), end: char_at(found.end()) });
+                }
+            }
+        }
+        self.current = focused
+            .and_then(|f| self.matches.iter().position(|m| (m.pane, m.block, m.start) >= (f.pane, f.block, f.start)))
+            .unwrap_or(0);
+    }
+
+    pub fn focused(&self) -> Option<&FindMatch> { self.matches.get(self.current) }
+
+    /// Moves focus forward or back through the matches, wrapping around the page.
+    pub fn step(&mut self, step: isize) {
+        if self.matches.is_empty() { return }
+        self.current = (self.current as isize + step).rem_euclid(self.matches.len() as isize) as usize;
+    }
+
+    /// Describes the focused match and the number found, for the status bar.
+    pub fn status(&self) -> String {
+        match self.matches.len() {
+            0 => format!("no matches for \"{}\"", self.query),
+            count => format!("{}/{} \"{}\"", self.current + 1, count, self.query),
+        }
+    }
+
+    /// Marks the matches in each block of the page and its panes to be drawn highlighted.
+    pub fn highlight(&self, page: &mut [TuiBlock], panes: &mut [&mut [TuiBlock]]) {
+        page.iter_mut().chain(panes.iter_mut().flat_map(|blocks| blocks.iter_mut())).for_each(|b| b.highlights.clear());
+        for (m, found) in self.matches.iter().enumerate() {
+            let style = match m == self.current { true => Self::CURRENT_STYLE, false => Self::MATCH_STYLE };
+            let blocks = match found.pane {
+                None => Some(&mut *page),
+                Some(p) => panes.get_mut(p).map(|blocks| &mut **blocks),
+            };
+            if let Some(block) = blocks.and_then(|b| b.get_mut(found.block)) { block.highlights.push((found.start, found.end, style)) }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks() -> Vec<TuiBlock> {
+        ["Café au lait, café noir.", "No coffee here", "Le CAFÉ est fermé"].into_iter()
+            .map(|t| TuiBlock::new(t.to_owned()))
+            .collect()
+    }
+
+    fn found(find: &Find) -> Vec<(usize, usize, usize)> {
+        find.matches.iter().map(|m| (m.block, m.start, m.end)).collect()
+    }
+
+    #[test]
+    fn finds_text_by_char() {
+        let blocks = blocks();
+        let mut find = Find::parse("café").unwrap();
+        find.search(&blocks, &[]);
+        assert_eq!(found(&find), [(0, 0, 4), (0, 14, 18), (2, 3, 7)]);
+        assert_eq!(find.status(), "1/3 \"café\"");
+        find.step(-1);
+        assert_eq!(find.focused(), Some(&FindMatch { pane: None, block: 2, start: 3, end: 7 }));
+
+        let mut find = Find::parse("-c café").unwrap();
+        find.search(&blocks, &[]);
+        assert_eq!(found(&find), [(0, 14, 18)]);
+
+        let mut find = Find::parse("-r f+(ee|é)").unwrap();
+        find.search(&blocks, &[]);
+        assert_eq!(found(&find), [(0, 2, 4), (0, 16, 18), (1, 5, 9), (2, 5, 7)]);
+        // Plain searches don't treat their text as a pattern.
+        assert!(Find::parse("-r (").is_err());
+        let mut find = Find::parse("f+(").unwrap();
+        find.search(&blocks, &[]);
+        assert_eq!(find.status(), "no matches for \"f+(\"");
+    }
+
+    #[test]
+    fn keeps_focus_across_searches() {
+        let mut blocks = blocks();
+        let mut find = Find::parse("café").unwrap();
+        find.search(&blocks, &[]);
+        find.step(1);
+        blocks.push(TuiBlock::new("A new café opened".to_owned()));
+        find.search(&blocks, &[]);
+        assert_eq!(find.focused(), Some(&FindMatch { pane: None, block: 0, start: 14, end: 18 }));
+        assert_eq!(find.matches.len(), 4);
+
+        find.highlight(&mut blocks, &mut []);
+        assert_eq!(blocks[0].highlights, [(0, 4, Find::MATCH_STYLE), (14, 18, Find::CURRENT_STYLE)]);
+        assert_eq!(blocks[3].highlights, [(6, 10, Find::MATCH_STYLE)]);
+    }
+    #[test]
+    fn finds_text_in_panes_after_the_page() {
+        let mut page = blocks();
+        let mut nav = vec![TuiBlock::new("Home".to_owned()), TuiBlock::new("Café menu".to_owned())];
+        let mut find = Find::parse("café").unwrap();
+        find.search(&page, &[&[], &nav]);
+        assert_eq!(find.matches.len(), 4);
+        find.step(-1);
+        assert_eq!(find.focused(), Some(&FindMatch { pane: Some(1), block: 1, start: 0, end: 4 }));
+
+        find.highlight(&mut page, &mut [&mut [], &mut nav]);
+        assert_eq!(nav[1].highlights, [(0, 4, Find::CURRENT_STYLE)]);
+        assert_eq!(page[2].highlights, [(3, 7, Find::MATCH_STYLE)]);
+    }
+}
@@ -0,0 +1,267 @@
+
+use crate::config::ImageMode;
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops::FilterType};
+use ratatui::{prelude::*, layout::Rect};
+
+use std::io::Write;
+use std::sync::{Arc, OnceLock};
+
+/// Kitty graphics payloads are sent in chunks of at most this many bytes.
+const KITTY_CHUNK: usize = 4096;
+
+/// Ways of drawing pictures in the terminal.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GraphicsProtocol {
+    /// Kitty graphics protocol, also spoken by WezTerm and Ghostty.
+    Kitty,
+    /// DEC sixel graphics.
+    Sixel,
+    /// Unicode upper half blocks, with a pixel in the foreground and one in the background of each cell.
+    HalfBlock,
+}
+
+/// Picks how to draw pictures, or None to only show placeholders.
+/// Terminals aren't queried for their capabilities, since replies would contend with the input thread,
+/// so Auto goes by the environment variables terminals set.
+pub fn protocol(mode: ImageMode) -> Option<GraphicsProtocol> {
+    match mode {
+        ImageMode::Placeholder => None,
+        ImageMode::Kitty => Some(GraphicsProtocol::Kitty),
+        ImageMode::Sixel => Some(GraphicsProtocol::Sixel),
+        ImageMode::HalfBlock => Some(GraphicsProtocol::HalfBlock),
+        ImageMode::Auto => {
+            let var = |name| std::env::var(name).unwrap_or_default().to_lowercase();
+            let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+            if std::env::var("KITTY_WINDOW_ID").is_ok() || term.contains("kitty") || term.contains("ghostty")
+                || matches!(program.as_str(), "wezterm" | "ghostty") {
+                Some(GraphicsProtocol::Kitty)
+            } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm")
+                || matches!(program.as_str(), "mlterm" | "foot" | "contour") {
+                Some(GraphicsProtocol::Sixel)
+            } else if matches!(crate::CONFIG.color_mode, crate::config::ColorMode::Monochrome) {
+                None
+            } else {
+                Some(GraphicsProtocol::HalfBlock)
+            }
+        },
+    }
+}
+
+/// Graphics protocol chosen for this terminal.
+pub fn current_protocol() -> Option<GraphicsProtocol> {
+    static PROTOCOL: OnceLock<Option<GraphicsProtocol>> = OnceLock::new();
+    *PROTOCOL.get_or_init(|| protocol(crate::CONFIG.image_mode))
+}
+
+/// Reads an image fetched from a page.
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage, anyhow::Error> {
+    Ok(image::load_from_memory(bytes)?)
+}
+
+/// An image sized to a number of terminal cells, ready to draw.
+pub struct TuiPicture {
+    pub image: Arc<DynamicImage>,
+    /// Columns the picture was fitted within.
+    pub max_cols: u16,
+    pub cols: u16,
+    pub rows: u16,
+    /// Half-block rendering, used directly or where the picture is only partly on screen.
+    pub lines: Vec<Line<'static>>,
+    /// Escape sequence for the picture, encoded the first time it is placed.
+    encoded: OnceLock<String>,
+} impl TuiPicture {
+
+    /// Scales an image down to fit within the given cells, keeping its aspect ratio.
+    pub fn new(image: Arc<DynamicImage>, max_cols: u16, max_rows: u16) -> TuiPicture {
+        let (cols, rows) = fit_cells(image.dimensions(), max_cols, max_rows, crate::CONFIG.cell_size);
+        let lines = half_block_lines(&image, cols, rows);
+        TuiPicture { image, max_cols, cols, rows, lines, encoded: OnceLock::new() }
+    }
+
+    /// Escape sequence that draws the picture at the cursor.
+    pub fn encoded(&self, protocol: GraphicsProtocol) -> &str {
+        self.encoded.get_or_init(|| match protocol {
+            GraphicsProtocol::Kitty => kitty_sequence(&self.image, self.cols, self.rows),
+            GraphicsProtocol::Sixel => sixel_sequence(&self.image, self.cols, self.rows, crate::CONFIG.cell_size),
+            GraphicsProtocol::HalfBlock => String::new(),
+        })
+    }
+}
+
+/// Number of cells (columns, rows) that an image of some pixel size takes up,
+/// scaled down to fit and never scaled up.
+pub fn fit_cells((width, height): (u32, u32), max_cols: u16, max_rows: u16, (cell_w, cell_h): (u16, u16)) -> (u16, u16) {
+    let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+    let (cell_w, cell_h) = (cell_w.max(1) as f32, cell_h.max(1) as f32);
+    let scale = (max_cols as f32 * cell_w / width).min(max_rows as f32 * cell_h / height).min(1.0);
+    let cols = (width * scale / cell_w).ceil().clamp(1.0, max_cols.max(1) as f32);
+    let rows = (height * scale / cell_h).ceil().clamp(1.0, max_rows.max(1) as f32);
+    (cols as u16, rows as u16)
+}
+
+/// Draws an image with one half-block character per pair of pixels stacked in a cell.
+/// Transparent pixels are left as the terminal background.
+pub fn half_block_lines(image: &DynamicImage, cols: u16, rows: u16) -> Vec<Line<'static>> {
+    let pixels = image.resize_exact(cols as u32, rows as u32 * 2, FilterType::Triangle).to_rgba8();
+    let color = |x, y| {
+        let p: &image::Rgba<u8> = pixels.get_pixel(x, y);
+        match p[3] < 128 {
+            true => Color::Reset,
+            false => crate::tui::terminal_color((p[0], p[1], p[2])).unwrap_or(Color::Reset),
+        }
+    };
+    (0..rows as u32).map(|row| {
+        let spans: Vec<Span> = (0..cols as u32)
+            .map(|x| Span::styled("▀", Style::default().fg(color(x, row * 2)).bg(color(x, row * 2 + 1))))
+            .collect();
+        Line::from(spans)
+    }).collect()
+}
+
+/// Kitty graphics escape sequence transmitting the image as PNG and displaying it over the given cells.
+/// The cursor is left where it was.
+pub fn kitty_sequence(image: &DynamicImage, cols: u16, rows: u16) -> String {
+    let mut png = Vec::new();
+    if image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).is_err() { return String::new() }
+    let payload = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, png);
+
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut sequence = String::new();
+    for (c, chunk) in chunks.iter().enumerate() {
+        let more = (c + 1 < chunks.len()) as u8;
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        match c {
+            0 => sequence.push_str(&format!("\x1b_Gf=100,a=T,t=d,c={},r={},C=1,q=2,m={};{}\x1b\\", cols, rows, more, chunk)),
+            _ => sequence.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk)),
+        }
+    }
+    sequence
+}
+
+/// Sixel escape sequence for the image scaled to fill the given cells,
+/// with colours reduced to a 6x6x6 cube.
+pub fn sixel_sequence(image: &DynamicImage, cols: u16, rows: u16, (cell_w, cell_h): (u16, u16)) -> String {
+    let pixels: RgbaImage = image.resize(cols as u32 * cell_w as u32, rows as u32 * cell_h as u32, FilterType::Triangle).to_rgba8();
+    let (width, height) = pixels.dimensions();
+    let level = |v: u8| (v as u32 * 5 + 127) / 255;
+    // Palette index of each pixel, or None where it is transparent.
+    let index = |x, y| {
+        let p: &image::Rgba<u8> = pixels.get_pixel(x, y);
+        (p[3] >= 128).then(|| level(p[0]) * 36 + level(p[1]) * 6 + level(p[2]))
+    };
+
+    let mut sequence = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for i in 0..216u32 {
+        let percent = |l: u32| l * 100 / 5;
+        sequence.push_str(&format!("#{};2;{};{};{}", i, percent(i / 36), percent(i / 6 % 6), percent(i % 6)));
+    }
+    for band in (0..height).step_by(6) {
+        let band_rows = (band..(band + 6).min(height)).collect::<Vec<_>>();
+        let mut colors: Vec<u32> = (0..width).flat_map(|x| band_rows.iter().filter_map(move |&y| index(x, y))).collect();
+        colors.sort_unstable();
+        colors.dedup();
+        for color in colors {
+            sequence.push_str(&format!("#{}", color));
+            let sixels = (0..width).map(|x| {
+                let bits = band_rows.iter().enumerate()
+                    .filter(|(_, &y)| index(x, y) == Some(color))
+                    .fold(0u8, |bits, (r, _)| bits | 1 << r);
+                (63 + bits) as char
+            });
+            push_run_length(&mut sequence, sixels);
+            sequence.push('$');
+        }
+        sequence.push('-');
+    }
+    sequence.push_str("\x1b\\");
+    sequence
+}
+
+/// Appends sixel characters, compressing repeats.
+fn push_run_length(sequence: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let flush = |sequence: &mut String, (ch, count): (char, usize)| match count {
+        1..=3 => (0..count).for_each(|_| sequence.push(ch)),
+        _ => sequence.push_str(&format!("!{}{}", count, ch)),
+    };
+    for ch in sixels {
+        run = match run {
+            Some((last, count)) if last == ch => Some((last, count + 1)),
+            Some(last) => { flush(sequence, last); Some((ch, 1)) },
+            None => Some((ch, 1)),
+        };
+    }
+    if let Some(last) = run { flush(sequence, last) }
+}
+
+/// Where a picture sits on screen.
+#[derive(Clone)]
+pub struct Placement {
+    pub area: Rect,
+    pub picture: Arc<TuiPicture>,
+} impl PartialEq for Placement {
+    fn eq(&self, other: &Placement) -> bool { self.area == other.area && Arc::ptr_eq(&self.picture, &other.picture) }
+}
+
+/// Draws pictures over the cells ratatui left blank for them, removing those placed before.
+pub fn place(out: &mut impl Write, protocol: GraphicsProtocol, placements: &[Placement]) -> Result<(), anyhow::Error> {
+    if protocol == GraphicsProtocol::Kitty { write!(out, "\x1b_Ga=d,d=a,q=2\x1b\\")? }
+    for placement in placements {
+        crossterm::queue!(out, crossterm::cursor::SavePosition, crossterm::cursor::MoveTo(placement.area.x, placement.area.y))?;
+        write!(out, "{}", placement.picture.encoded(protocol))?;
+        crossterm::queue!(out, crossterm::cursor::RestorePosition)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x4 pixels, red over blue.
+    const RED_OVER_BLUE: &[u8] = include_bytes!("../tests/fixtures/red_over_blue.png");
+
+    #[test]
+    fn fits_images_to_cells() {
+        assert_eq!(fit_cells((4, 4), 80, 12, (8, 16)), (1, 1));
+        assert_eq!(fit_cells((800, 400), 80, 12, (8, 16)), (48, 12));
+        assert_eq!(fit_cells((1600, 160), 80, 12, (8, 16)), (80, 4));
+        assert_eq!(fit_cells((160, 1600), 80, 12, (8, 16)), (3, 12));
+    }
+
+    #[test]
+    fn draws_half_blocks() {
+        let image = decode(RED_OVER_BLUE).unwrap();
+        assert_eq!(image.dimensions(), (4, 4));
+        let lines = half_block_lines(&image, 4, 2);
+        assert_eq!(lines.len(), 2);
+        let colors = |line: &Line| line.spans.iter().map(|s| (s.content.to_string(), s.style.fg, s.style.bg)).collect::<Vec<_>>();
+        let (red, blue) = (crate::tui::terminal_color((255, 0, 0)), crate::tui::terminal_color((0, 0, 255)));
+        assert_eq!(colors(&lines[0]), vec![("▀".to_owned(), red, red); 4]);
+        assert_eq!(colors(&lines[1]), vec![("▀".to_owned(), blue, blue); 4]);
+    }
+
+    #[test]
+    fn encodes_kitty_graphics() {
+        let image = decode(RED_OVER_BLUE).unwrap();
+        let sequence = kitty_sequence(&image, 1, 1);
+        assert!(sequence.starts_with("\x1b_Gf=100,a=T,t=d,c=1,r=1,C=1,q=2,m=0;"));
+        assert!(sequence.ends_with("\x1b\\"));
+        let payload = &sequence[sequence.find(';').unwrap() + 1..sequence.len() - 2];
+        let png = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, payload).unwrap();
+        assert_eq!(decode(&png).unwrap().dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn encodes_sixels() {
+        let image = decode(RED_OVER_BLUE).unwrap();
+        let sequence = sixel_sequence(&image, 1, 1, (4, 4));
+        assert!(sequence.starts_with("\x1bP0;1;0q\"1;1;4;4"));
+        assert!(sequence.ends_with("-\x1b\\"));
+        // Red fills the top two rows of the band and blue the bottom two, four pixels across.
+        assert!(sequence.contains("#180!4B$"), "{:?}", sequence);
+        assert!(sequence.contains("#5!4K$"), "{:?}", sequence);
+    }
+}
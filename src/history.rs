@@ -1,3 +1,152 @@
+// TBD Search history tree, command history for completions
 
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// TBD Search history tree, command history for completions
+/// File that visits are recorded in, within the data directory.
+const HISTORY_FILE: &str = "history.tsv";
+/// File that bookmarks are kept in, within the data directory.
+const BOOKMARKS_FILE: &str = "bookmarks.tsv";
+/// Seconds in a day, for aging visits.
+const DAY: u64 = 24 * 60 * 60;
+
+/// A page that has been visited, with how often and how lately.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    pub visits: u32,
+    /// Seconds since the epoch of the latest visit.
+    pub last_visit: u64,
+} impl HistoryEntry {
+
+    /// Scores the entry for ranking completions, by its visits weighted by how recent the latest one is.
+    pub fn frecency(&self, now: u64) -> f64 {
+        let weight = match now.saturating_sub(self.last_visit) / DAY {
+            0..=3 => 100.0,
+            4..=13 => 70.0,
+            14..=30 => 50.0,
+            31..=89 => 30.0,
+            _ => 10.0,
+        };
+        self.visits as f64 * weight
+    }
+}
+
+/// Pages visited, by URL. Visits are appended to the history file as tab separated time, URL and title,
+/// and added up when the file is read. With amnesia on, history is kept for the session only.
+#[derive(Default)]
+pub struct History {
+    /// File visits are recorded in, or None to keep them in memory.
+    path: Option<PathBuf>,
+    pub entries: HashMap<String, HistoryEntry>,
+} impl History {
+
+    /// Opens the history in the data directory, or an empty one kept in memory with amnesia on.
+    pub fn open(amnesia: bool) -> Result<History, anyhow::Error> {
+        match amnesia {
+            true => Ok(History::default()),
+            false => History::load(crate::utils::data_dir().join(HISTORY_FILE)),
+        }
+    }
+
+    /// Reads a history file, skipping malformed lines. A missing file is an empty history.
+    pub fn load(path: PathBuf) -> Result<History, anyhow::Error> {
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut history = History { path: Some(path), entries: HashMap::new() };
+        for line in text.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(time), Some(url)) = (fields.next().and_then(|t| t.parse().ok()), fields.next()) else { continue };
+            history.add(url, fields.next().unwrap_or_default(), time);
+        }
+        Ok(history)
+    }
+
+    /// Records a visit to a page, saving it unless history is kept in memory.
+    pub fn record(&mut self, url: &str, title: &str) -> Result<(), anyhow::Error> {
+        // Blank pages aren't worth completing to.
+        if url.is_empty() || url.starts_with("about:") { return Ok(()) }
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.add(url, title, time);
+        let Some(path) = &self.path else { return Ok(()) };
+        append_line(path, &format!("{}\t{}\t{}", time, clean(url), clean(title)))
+    }
+
+    fn add(&mut self, url: &str, title: &str, time: u64) {
+        let entry = self.entries.entry(url.to_owned()).or_insert_with(|| HistoryEntry {
+            url: url.to_owned(), title: String::new(), visits: 0, last_visit: 0,
+        });
+        entry.visits += 1;
+        entry.last_visit = entry.last_visit.max(time);
+        if !title.is_empty() { entry.title = title.to_owned() }
+    }
+}
+
+/// A page saved to come back to.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: String,
+}
+
+/// Path of the bookmarks file. Bookmarks are saved on purpose, so they are kept even with amnesia on.
+pub fn bookmarks_path() -> PathBuf { crate::utils::data_dir().join(BOOKMARKS_FILE) }
+
+/// Reads bookmarks saved as lines of tab separated URL and title. A missing file has no bookmarks.
+pub fn read_bookmarks(path: &Path) -> Vec<Bookmark> {
+    let text = std::fs::read_to_string(path).unwrap_or_default();
+    text.lines().filter(|l| !l.trim().is_empty()).map(|line| {
+        let (url, title) = line.split_once('\t').unwrap_or((line, ""));
+        Bookmark { url: url.to_owned(), title: title.to_owned() }
+    }).collect()
+}
+
+/// Saves a bookmark at the end of the bookmarks file.
+pub fn add_bookmark(path: &Path, bookmark: &Bookmark) -> Result<(), anyhow::Error> {
+    append_line(path, &format!("{}\t{}", clean(&bookmark.url), clean(&bookmark.title)))
+}
+
+fn append_line(path: &Path, line: &str) -> Result<(), anyhow::Error> {
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)? }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Keeps a field on its own line of a tab separated file.
+fn clean(field: &str) -> String { field.replace(['\t', '\n', '\r'], " ") }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_visits() {
+        let dir = std::env::temp_dir().join(format!("etymon-history-{}", std::process::id()));
+        let path = dir.join(HISTORY_FILE);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, "100\thttps://example.com/\tExample\nnot a visit\n200\thttps://example.com/\t\n").unwrap();
+
+        let mut history = History::load(path.clone()).unwrap();
+        history.record("https://docs.rs/", "Docs.rs\tHome").unwrap();
+        history.record("about:blank", "").unwrap();
+        let example = &history.entries["https://example.com/"];
+        assert_eq!((example.title.as_str(), example.visits, example.last_visit), ("Example", 2, 200));
+
+        let history = History::load(path).unwrap();
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries["https://docs.rs/"].title, "Docs.rs Home");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let entry = HistoryEntry { url: String::new(), title: String::new(), visits: 3, last_visit: 0 };
+        assert_eq!(entry.frecency(DAY), 300.0);
+        assert_eq!(entry.frecency(100 * DAY), 30.0);
+    }
+}
@@ -18,6 +18,23 @@
 #[macro_use] pub mod config; pub use config::CONFIG;
 pub mod utils; use utils::init_panic_handler;
 pub mod browser;
+pub mod adblock;
+pub mod reader;
+pub mod table;
+pub mod graphics;
+pub mod media;
+pub mod downloads;
+pub mod cookies;
+pub mod proxy;
+pub mod profile;
+pub mod fetch;
+pub mod tools;
+pub mod search;
+pub mod find;
+pub mod history;
+pub mod address;
+pub mod backend;
+pub mod events;
 pub mod tui;
 pub mod ui;
 
@@ -25,8 +42,11 @@ pub mod etymon; pub use etymon::*;
 
 // TODO color-eyre, tracing, human-panic
 
+#[allow(clippy::unit_arg)]
 fn main() -> Result<(), anyhow::Error> {
     init_panic_handler();
+    // Errors in the config file are shown before the terminal is taken over.
+    lazy_static::initialize(&CONFIG);
     Etymon::run()?;
     Ok(println!("Shutdown successful!"))
 }
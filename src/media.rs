@@ -0,0 +1,155 @@
+
+use super::tui::{TuiNode, TuiNodeData};
+
+use std::collections::HashMap;
+
+/// Attribute the page snapshot records a media element's duration in, in seconds, once chrome knows it.
+pub const DURATION_ATTRIBUTE: &str = "data-etymon-duration";
+/// Attribute the page snapshot records the source chrome chose to play in, as an absolute URL.
+pub const CURRENT_SOURCE_ATTRIBUTE: &str = "data-etymon-src";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MediaKind { Video, Audio }
+impl MediaKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MediaKind::Video => "video",
+            MediaKind::Audio => "audio",
+        }
+    }
+}
+
+/// One of the sources a media element offers, with its MIME type if the page gave one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaSource {
+    pub src: String,
+    pub mime: Option<String>,
+}
+
+/// A <video> or <audio> element, shown as a placeholder that can be handed off to an external player.
+#[derive(Clone, Debug)]
+pub struct TuiMedia {
+    /// Chars of the placeholder within its block's text.
+    pub start: usize,
+    pub end: usize,
+    pub kind: MediaKind,
+    pub title: Option<String>,
+    /// Length in seconds, if chrome had loaded the media's metadata.
+    pub duration: Option<f64>,
+    /// Sources in the page's order of preference, which may be relative URLs.
+    pub sources: Vec<MediaSource>,
+} impl TuiMedia {
+
+    /// Reads a <video> or <audio> node and the <source> elements within it, or None for other nodes.
+    pub fn from_node(node: &TuiNode) -> Option<TuiMedia> {
+        let TuiNodeData::Element(elem) = &node.data else { return None };
+        let kind = match elem.name() {
+            "video" => MediaKind::Video,
+            "audio" => MediaKind::Audio,
+            _ => return None,
+        };
+
+        let mut sources = Vec::new();
+        // Chrome's choice of source comes first, as it is resolved and known to be playable.
+        let chosen = elem.attr(CURRENT_SOURCE_ATTRIBUTE).or(elem.attr("src")).filter(|s| !s.is_empty());
+        if let Some(src) = chosen { sources.push(MediaSource { src: src.to_owned(), mime: None }) }
+        for child in node.children.iter() {
+            let TuiNodeData::Element(source) = &child.data else { continue };
+            if source.name() != "source" { continue }
+            let Some(src) = source.attr("src").filter(|s| !s.is_empty()) else { continue };
+            let mime = source.attr("type").map(|t| t.split(';').next().unwrap_or_default().trim().to_owned());
+            sources.push(MediaSource { src: src.to_owned(), mime });
+        }
+        if let Some(first) = sources.first_mut() {
+            first.mime = first.mime.take().or_else(|| elem.attr("type").map(str::to_owned));
+        }
+
+        let title = elem.attr("title").or(elem.attr("aria-label"))
+            .map(str::trim).filter(|t| !t.is_empty()).map(str::to_owned);
+        let duration = elem.attr(DURATION_ATTRIBUTE)
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| d.is_finite() && *d > 0.0);
+        Some(TuiMedia { start: 0, end: 0, kind, title, duration, sources })
+    }
+
+    /// Text shown in place of the element, with its title, or the file name of its source, and its length.
+    pub fn label(&self) -> String {
+        let file = self.sources.first().map(|s| file_name(&s.src)).filter(|f| !f.is_empty());
+        let mut label = format!("[{}: {}", self.kind.name(), self.title.as_deref().or(file).unwrap_or("untitled"));
+        if let Some(duration) = self.duration { label += &format!(" ({})", format_duration(duration)) }
+        label + "]"
+    }
+
+    /// MIME type of a source, from the page or else guessed from its file extension.
+    pub fn mime_of(&self, source: &MediaSource) -> String {
+        source.mime.clone()
+            .or_else(|| crate::utils::guess_mime(&source.src).map(str::to_owned))
+            .unwrap_or_else(|| format!("{}/*", self.kind.name()))
+    }
+
+    /// Picks the first source there is a player for, with the player's command.
+    pub fn playable<'a>(&self, players: &'a HashMap<String, String>) -> Option<(&MediaSource, &'a str)> {
+        self.sources.iter().find_map(|s| crate::utils::handler_for(players, &self.mime_of(s)).map(|p| (s, p)))
+    }
+}
+
+/// Formats seconds as m:ss, or h:mm:ss for an hour or more.
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match h {
+        0 => format!("{}:{:02}", m, s),
+        _ => format!("{}:{:02}:{:02}", h, m, s),
+    }
+}
+
+/// Last path segment of a URL, without its query or fragment.
+fn file_name(src: &str) -> &str {
+    src.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+
+    const MEDIA: &str = include_str!("../tests/fixtures/media.html");
+
+    fn media() -> Vec<TuiMedia> {
+        let mut dom = filter_dom_html(MEDIA, &SelectorRules::default()).to_tui();
+        dom.collapse_phantoms();
+        dom.text_blocks().into_iter().flat_map(|b| b.media).collect()
+    }
+
+    #[test]
+    fn reads_media_elements() {
+        let media = media();
+        assert_eq!(media.len(), 3);
+        assert_eq!(media[0].kind, MediaKind::Video);
+        assert_eq!(media[0].label(), "[video: Launch trailer (2:05)]");
+        assert_eq!(media[0].sources, vec![
+            MediaSource { src: "https://example.com/media/trailer.webm".into(), mime: None },
+            MediaSource { src: "/media/trailer.webm".into(), mime: Some("video/webm".into()) },
+            MediaSource { src: "/media/trailer.mp4".into(), mime: Some("video/mp4".into()) },
+        ]);
+        assert_eq!(media[1].kind, MediaKind::Audio);
+        assert_eq!(media[1].label(), "[audio: episode-12.mp3 (1:02:03)]");
+        assert_eq!(media[2].label(), "[video: untitled]");
+        assert!(media[2].sources.is_empty());
+    }
+
+    #[test]
+    fn picks_players_by_mime_type() {
+        let players = HashMap::from([
+            ("video/*".to_owned(), "mpv".to_owned()),
+            ("audio/mpeg".to_owned(), "mpg123 -q".to_owned()),
+        ]);
+        let media = media();
+        assert_eq!(media[0].playable(&players).map(|(s, p)| (s.src.as_str(), p)), Some(("https://example.com/media/trailer.webm", "mpv")));
+        assert_eq!(media[1].playable(&players).map(|(_, p)| p), Some("mpg123 -q"));
+        assert_eq!(crate::utils::handler_for(&players, "audio/ogg"), None);
+        assert_eq!(crate::utils::handler_for(&players, "VIDEO/MP4"), Some("mpv"));
+    }
+}
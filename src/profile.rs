@@ -0,0 +1,113 @@
+
+use std::path::{Path, PathBuf};
+
+/// Lock chrome keeps in a profile while a browser has it open, as a symlink to "hostname-pid".
+const LOCK_FILE: &str = "SingletonLock";
+
+/// The chrome user data directory for the session, holding cookies, logins and site settings.
+pub struct Profile {
+    pub path: PathBuf,
+    /// If true, the profile is deleted when the session ends.
+    pub ephemeral: bool,
+} impl Profile {
+
+    /// Picks the profile to launch chrome with:
+    /// with sync on, an existing Chrome or Chromium profile that no running browser has open;
+    /// with amnesia on, an empty temporary profile; otherwise Etymon's own profile, kept between sessions.
+    pub fn open(sync: bool, path: Option<&Path>, amnesia: bool) -> Result<Profile, anyhow::Error> {
+        if sync {
+            let path = path.map(Path::to_path_buf).or_else(user_profile)
+                .ok_or_else(|| anyhow::anyhow!("No Chrome or Chromium profile was found; set profile_path to one"))?;
+            if !path.is_dir() { return Err(anyhow::anyhow!("There is no profile at {}", path.display())) }
+            // Two browsers writing to one profile corrupt it.
+            if let Some(holder) = lock_holder(&path) {
+                return Err(anyhow::anyhow!("The profile at {} is open in {}; close that browser first", path.display(), holder))
+            }
+            return Ok(Profile { path, ephemeral: false })
+        }
+
+        let (path, ephemeral) = match amnesia {
+            true => (std::env::temp_dir().join(format!("etymon-profile-{}", std::process::id())), true),
+            false => (crate::utils::data_dir().join("chrome-profile"), false),
+        };
+        std::fs::create_dir_all(&path)?;
+        Ok(Profile { path, ephemeral })
+    }
+}
+impl Drop for Profile {
+    fn drop(&mut self) {
+        if self.ephemeral { let _leftover = std::fs::remove_dir_all(&self.path); }
+    }
+}
+
+/// Default profile of a desktop Chrome, or else Chromium, install.
+pub fn user_profile() -> Option<PathBuf> {
+    let config = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(config) => PathBuf::from(config),
+        None => crate::utils::home_dir().join(".config"),
+    };
+    ["google-chrome", "chromium"].into_iter().map(|name| config.join(name)).find(|p| p.is_dir())
+}
+
+/// Describes the browser that has a profile open, if any.
+/// Locks left behind by browsers on this machine that have since exited are ignored, as chrome ignores them.
+pub fn lock_holder(profile: &Path) -> Option<String> {
+    let lock = profile.join(LOCK_FILE);
+    lock.symlink_metadata().ok()?;
+    let target = std::fs::read_link(&lock).map(|t| t.to_string_lossy().into_owned()).unwrap_or_default();
+    match target.rsplit_once('-') {
+        Some((host, pid)) if host == hostname() && pid.parse::<u32>().is_ok() && !Path::new("/proc").join(pid).exists() => None,
+        Some((host, pid)) => Some(format!("process {} on {}", pid, host)),
+        None => Some("another browser".to_owned()),
+    }
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .map(|h| h.trim().to_owned())
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_profiles_in_use() {
+        let dir = std::env::temp_dir().join(format!("etymon-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lock = |target: String| {
+            let _ = std::fs::remove_file(dir.join(LOCK_FILE));
+            std::os::unix::fs::symlink(target, dir.join(LOCK_FILE)).unwrap();
+        };
+        assert_eq!(lock_holder(&dir), None);
+
+        lock(format!("{}-{}", hostname(), std::process::id()));
+        assert_eq!(lock_holder(&dir), Some(format!("process {} on {}", std::process::id(), hostname())));
+        assert!(Profile::open(true, Some(&dir), true).is_err());
+        // Above the kernel's limit on process ids, so nothing can be running as it.
+        lock(format!("{}-99999999", hostname()));
+        assert_eq!(lock_holder(&dir), None);
+        lock("laptop.example-1234".to_owned());
+        assert_eq!(lock_holder(&dir).as_deref(), Some("process 1234 on laptop.example"));
+
+        std::fs::remove_file(dir.join(LOCK_FILE)).unwrap();
+        let profile = Profile::open(true, Some(&dir), true).unwrap();
+        assert!(!profile.ephemeral);
+        drop(profile);
+        assert!(dir.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deletes_ephemeral_profiles() {
+        let profile = Profile::open(false, None, true).unwrap();
+        let path = profile.path.clone();
+        assert!(path.is_dir());
+        std::fs::write(path.join("Cookies"), "").unwrap();
+        drop(profile);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,258 @@
+
+use super::config::ConfigOptions;
+use base64::Engine;
+
+use std::collections::HashMap;
+
+/*
+ Proxy routing shared by chrome and reqwest.
+    Chrome takes a single proxy on its command line, so per-domain rules are handed to it as a PAC script in a data URL.
+    Reqwest picks a proxy for each request with the same rules.
+    .onion addresses can only be reached through Tor, so they always go through the Tor proxy.
+*/
+
+/// Proxy to use for domains that should bypass any other proxy.
+const DIRECT: &str = "direct";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProxyScheme {
+    Http,
+    /// HTTP proxy reached over TLS.
+    Https,
+    /// SOCKS5 with host names resolved locally.
+    Socks5,
+    /// SOCKS5 with host names resolved by the proxy, so lookups don't leak.
+    Socks5h,
+}
+
+/// A proxy server, read from a URL like socks5h://127.0.0.1:9050.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Proxy {
+    pub scheme: ProxyScheme,
+    pub host: String,
+    pub port: u16,
+} impl Proxy {
+
+    pub fn parse(url: &str) -> Result<Proxy, anyhow::Error> {
+        let parsed = url::Url::parse(url).map_err(|e| anyhow::anyhow!("Bad proxy {}: {}", url, e))?;
+        let (scheme, default_port) = match parsed.scheme() {
+            "http" => (ProxyScheme::Http, 80),
+            "https" => (ProxyScheme::Https, 443),
+            "socks5" => (ProxyScheme::Socks5, 1080),
+            "socks5h" => (ProxyScheme::Socks5h, 1080),
+            other => return Err(anyhow::anyhow!("Bad proxy {}: {} proxies aren't supported", url, other)),
+        };
+        let host = parsed.host_str().ok_or_else(|| anyhow::anyhow!("Bad proxy {}: no host", url))?.to_owned();
+        Ok(Proxy { scheme, host, port: parsed.port().unwrap_or(default_port) })
+    }
+
+    pub fn url(&self) -> String {
+        let scheme = match self.scheme {
+            ProxyScheme::Http => "http",
+            ProxyScheme::Https => "https",
+            ProxyScheme::Socks5 => "socks5",
+            ProxyScheme::Socks5h => "socks5h",
+        };
+        format!("{}://{}:{}", scheme, self.host, self.port)
+    }
+
+    /// The proxy as a PAC script result. Chrome always resolves names through SOCKS5 proxies.
+    pub fn pac(&self) -> String {
+        let kind = match self.scheme {
+            ProxyScheme::Http => "PROXY",
+            ProxyScheme::Https => "HTTPS",
+            ProxyScheme::Socks5 | ProxyScheme::Socks5h => "SOCKS5",
+        };
+        format!("{} {}:{}", kind, self.host, self.port)
+    }
+}
+
+/// Decides which proxy, if any, each request goes through.
+#[derive(Clone, Debug)]
+pub struct ProxyRules {
+    /// Proxy for requests no domain rule covers, or None to connect directly.
+    pub default: Option<Proxy>,
+    /// Proxies by domain, most specific first, with None for domains that connect directly.
+    pub domains: Vec<(String, Option<Proxy>)>,
+    pub tor: Proxy,
+    /// If true, all traffic is meant to go through Tor.
+    pub tor_only: bool,
+} impl ProxyRules {
+
+    /// Reads proxy URLs, where domain rules may be "direct" to bypass the proxy.
+    /// With Tor on, requests no domain rule covers go through Tor rather than the default proxy.
+    pub fn new(proxy: Option<&str>, domain_proxies: &HashMap<String, String>, tor: bool, tor_proxy: &str) -> Result<ProxyRules, anyhow::Error> {
+        let tor_proxy = Proxy::parse(tor_proxy)?;
+        let default = match tor {
+            true => Some(tor_proxy.clone()),
+            false => proxy.map(Proxy::parse).transpose()?,
+        };
+        let mut domains = domain_proxies.iter()
+            .map(|(domain, proxy)| match proxy.eq_ignore_ascii_case(DIRECT) {
+                true => Ok((domain.trim_start_matches('.').to_owned(), None)),
+                false => Ok((domain.trim_start_matches('.').to_owned(), Some(Proxy::parse(proxy)?))),
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        domains.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+        Ok(ProxyRules { default, domains, tor: tor_proxy, tor_only: tor })
+    }
+
+    pub fn from_config(config: &ConfigOptions) -> Result<ProxyRules, anyhow::Error> {
+        Self::new(config.proxy.as_deref(), &config.domain_proxies, config.tor, &config.tor_proxy)
+    }
+
+    /// Picks the proxy for a URL: Tor for .onion addresses, then the most specific domain rule, then the default.
+    pub fn proxy_for(&self, url: &url::Url) -> Option<&Proxy> {
+        let Some(host) = url.host_str() else { return self.default.as_ref() };
+        if is_onion(host) { return Some(&self.tor) }
+        match self.domains.iter().find(|(domain, _)| crate::utils::host_matches(host, domain)) {
+            Some((_, proxy)) => proxy.as_ref(),
+            None => self.default.as_ref(),
+        }
+    }
+
+    /// A proxy auto-config script following the same rules as proxy_for.
+    pub fn pac_script(&self) -> String {
+        let route = |proxy: Option<&Proxy>| proxy.map_or("DIRECT".to_owned(), Proxy::pac);
+        let mut script = "function FindProxyForURL(url, host) {\n".to_owned();
+        script += &format!("  if (dnsDomainIs(host, \".onion\")) return \"{}\";\n", self.tor.pac());
+        for (domain, proxy) in self.domains.iter() {
+            script += &format!("  if (host == \"{0}\" || dnsDomainIs(host, \".{0}\")) return \"{1}\";\n", domain, route(proxy.as_ref()));
+        }
+        script + &format!("  return \"{}\";\n}}\n", route(self.default.as_ref()))
+    }
+
+    /// Command line switches that have chrome follow the rules.
+    pub fn chrome_args(&self) -> Vec<String> {
+        let pac = base64::engine::general_purpose::STANDARD.encode(self.pac_script());
+        let mut args = vec![
+            format!("--proxy-pac-url=data:application/x-ns-proxy-autoconfig;base64,{}", pac),
+            // WebRTC would otherwise reach peers around the proxy.
+            "--force-webrtc-ip-handling-policy=disable_non_proxied_udp".to_owned(),
+        ];
+        // Keeps chrome from looking up names itself, which would reveal the sites visited.
+        // Domains set to connect directly can't be reached by name while this is on.
+        if self.tor_only { args.push(format!("--host-resolver-rules=MAP * ~NOTFOUND , EXCLUDE {}", self.tor.host)) }
+        args
+    }
+
+    /// A reqwest proxy that follows the rules.
+    pub fn reqwest_proxy(&self) -> reqwest::Proxy {
+        let rules = self.clone();
+        reqwest::Proxy::custom(move |url| rules.proxy_for(url).map(Proxy::url))
+    }
+}
+
+pub fn is_onion(host: &str) -> bool {
+    crate::utils::host_matches(host, "onion")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn rules(proxy: Option<&str>, tor: bool) -> ProxyRules {
+        let domains = HashMap::from([
+            ("example.com".to_owned(), "http://127.0.0.1:3128".to_owned()),
+            ("intranet.example.com".to_owned(), "direct".to_owned()),
+        ]);
+        ProxyRules::new(proxy, &domains, tor, "socks5h://127.0.0.1:9050").unwrap()
+    }
+
+    fn proxy_for(rules: &ProxyRules, url: &str) -> Option<String> {
+        rules.proxy_for(&url::Url::parse(url).unwrap()).map(Proxy::url)
+    }
+
+    /// Answers each connection with a page saying how it was asked for, after an optional SOCKS5 handshake.
+    fn stand_in_proxy(socks: bool) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || for mut stream in listener.incoming().flatten() {
+            let mut target = String::new();
+            if socks { target = socks_handshake(&mut stream).unwrap_or_default() }
+            let mut request_line = String::new();
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut request_line).is_err() { continue }
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok_and(|n| n > 2) { header.clear() }
+            let body = format!("{}{}", target, request_line.trim());
+            let reply = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+            let _ = stream.write_all(reply.as_bytes());
+        });
+        port
+    }
+
+    /// Accepts a SOCKS5 connect request without authentication, returning the host and port asked for.
+    fn socks_handshake(stream: &mut TcpStream) -> Result<String, std::io::Error> {
+        let mut greeting = [0; 2];
+        stream.read_exact(&mut greeting)?;
+        stream.read_exact(&mut vec![0; greeting[1] as usize])?;
+        stream.write_all(&[5, 0])?;
+        let mut request = [0; 4];
+        stream.read_exact(&mut request)?;
+        let host = match request[3] {
+            // Names come through as is when the proxy is to resolve them.
+            3 => {
+                let mut len = [0; 1];
+                stream.read_exact(&mut len)?;
+                let mut name = vec![0; len[0] as usize];
+                stream.read_exact(&mut name)?;
+                String::from_utf8_lossy(&name).into_owned()
+            },
+            _ => {
+                let mut address = [0; 4];
+                stream.read_exact(&mut address)?;
+                std::net::Ipv4Addr::from(address).to_string()
+            },
+        };
+        let mut port = [0; 2];
+        stream.read_exact(&mut port)?;
+        stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])?;
+        Ok(format!("{}:{} ", host, u16::from_be_bytes(port)))
+    }
+
+    #[test]
+    fn routes_by_domain() {
+        let rules = rules(Some("socks5://10.0.0.1"), false);
+        assert_eq!(proxy_for(&rules, "https://www.example.com/"), Some("http://127.0.0.1:3128".to_owned()));
+        assert_eq!(proxy_for(&rules, "https://intranet.example.com/wiki"), None);
+        assert_eq!(proxy_for(&rules, "https://example.org/"), Some("socks5://10.0.0.1:1080".to_owned()));
+        assert_eq!(proxy_for(&rules, "http://abcdefgh.onion/"), Some("socks5h://127.0.0.1:9050".to_owned()));
+
+        let tor = self::rules(Some("socks5://10.0.0.1"), true);
+        assert_eq!(proxy_for(&tor, "https://example.org/"), Some("socks5h://127.0.0.1:9050".to_owned()));
+        assert!(Proxy::parse("ftp://127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn writes_pac_scripts() {
+        let script = rules(None, false).pac_script();
+        assert_eq!(script, concat!(
+            "function FindProxyForURL(url, host) {\n",
+            "  if (dnsDomainIs(host, \".onion\")) return \"SOCKS5 127.0.0.1:9050\";\n",
+            "  if (host == \"intranet.example.com\" || dnsDomainIs(host, \".intranet.example.com\")) return \"DIRECT\";\n",
+            "  if (host == \"example.com\" || dnsDomainIs(host, \".example.com\")) return \"PROXY 127.0.0.1:3128\";\n",
+            "  return \"DIRECT\";\n}\n",
+        ));
+        assert!(!rules(None, false).chrome_args().iter().any(|a| a.starts_with("--host-resolver-rules")));
+        assert!(rules(None, true).chrome_args().iter().any(|a| a.starts_with("--host-resolver-rules")));
+    }
+
+    #[test]
+    fn sends_requests_through_proxies() {
+        let http = stand_in_proxy(false);
+        let socks = stand_in_proxy(true);
+        let domains = HashMap::from([("example.com".to_owned(), format!("http://127.0.0.1:{}", http))]);
+        let rules = ProxyRules::new(None, &domains, false, &format!("socks5h://127.0.0.1:{}", socks)).unwrap();
+        let client = crate::fetch::client_for(&rules).unwrap();
+        let get = |url: &str| client.get(url).send().and_then(|r| r.text()).unwrap();
+
+        // HTTP proxies are asked for the whole URL.
+        assert_eq!(get("http://www.example.com/page"), "GET http://www.example.com/page HTTP/1.1");
+        // Tor is handed the .onion name to resolve itself.
+        assert_eq!(get("http://hidden.onion/about"), "hidden.onion:80 GET /about HTTP/1.1");
+    }
+}
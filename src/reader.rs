@@ -0,0 +1,266 @@
+
+use super::tui::{TuiNode, TuiNodeData, TuiElement, TuiBlock};
+use ratatui::style::{Style, Stylize};
+
+use std::collections::HashMap;
+
+/*
+ Readability-style extraction:
+    Paragraph-like elements score points for their length and commas.
+    Each paragraph's score goes to its parent, and half of it to its grandparent.
+    Candidates are weighted by tag and class names, then scaled down by their link density.
+    The best candidate and any strong siblings make up the article body.
+*/
+
+/// Pages with less article text than this are not worth a reader view.
+const MIN_ARTICLE_CHARS: usize = 250;
+/// Paragraphs shorter than this don't count towards a candidate's score.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Class and id words that suggest the main content of a page.
+const POSITIVE_NAMES: [&str; 9] = ["article", "body", "content", "entry", "main", "page", "post", "story", "text"];
+/// Class and id words that suggest page furniture around the content.
+const NEGATIVE_NAMES: [&str; 17] = [
+    "ad-", "banner", "combx", "comment", "contact", "footer", "footnote", "masthead", "menu",
+    "nav", "promo", "related", "share", "shoutbox", "sidebar", "social", "sponsor",
+];
+/// Elements that never belong in an article body.
+const FURNITURE_ELEMENTS: [&str; 9] = ["nav", "aside", "footer", "form", "button", "input", "select", "textarea", "dialog"];
+
+/// The main content of a page, with the title and byline picked out.
+pub struct Article {
+    pub title: String,
+    pub byline: Option<String>,
+    pub body: TuiNode,
+} impl Article {
+
+    /// Finds the article on a page, or None if nothing looks like one.
+    pub fn extract(dom: &TuiNode, page_title: &str) -> Option<Article> {
+        let mut scores = HashMap::new();
+        score_paragraphs(dom, &mut Vec::new(), &mut scores);
+
+        let (best, best_score) = scores.iter()
+            .filter_map(|(path, score)| {
+                let node = node_at(dom, path)?;
+                let TuiNodeData::Element(elem) = &node.data else { return None };
+                let weighted = (score + tag_weight(elem) + class_weight(elem)) * (1.0 - link_density(node));
+                Some((path.clone(), weighted))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        if text_len(node_at(dom, &best)?) < MIN_ARTICLE_CHARS { return None }
+
+        // Siblings of the best candidate that score well, or are substantial paragraphs, belong with it.
+        let mut body = TuiNode::default();
+        match best.split_last() {
+            Some((&index, parent_path)) => {
+                let parent = node_at(dom, parent_path)?;
+                let threshold = (best_score * 0.2).max(10.0);
+                for (c, sibling) in parent.children.iter().enumerate() {
+                    let mut path = parent_path.to_vec();
+                    path.push(c);
+                    let score = scores.get(&path).copied().unwrap_or_default() * (1.0 - link_density(sibling));
+                    let paragraph = is_element(sibling, "p") && text_len(sibling) > 80 && link_density(sibling) < 0.25;
+                    if c == index || score >= threshold || paragraph { body.children.push(sibling.clone()) }
+                }
+            },
+            None => body.children.push(dom.clone()),
+        }
+
+        let heading = find(dom, &|n| is_element(n, "h1") && text_len(n) > 0).map(text_of);
+        let title = heading.unwrap_or_else(|| page_title.trim().to_owned());
+        let byline = find(dom, &is_byline).map(text_of);
+
+        remove_furniture(&mut body);
+        // Drop the title and byline from the body, as they are shown above it.
+        body.children.retain(|c| !(is_element(c, "h1") && text_of(c) == title));
+        remove_where(&mut body, &|n| is_byline(n) && byline.as_deref() == Some(text_of(n).as_str()));
+
+        Some(Article { title, byline, body })
+    }
+
+    /// Lays out the title and byline above the body text.
+    pub fn text_blocks(&self) -> Vec<TuiBlock> {
+        let mut blocks = Vec::new();
+        let styled = |text: &str, style: Style| {
+            let mut block = TuiBlock::new(text.to_owned());
+            block.styles.push((0, text.chars().count(), style));
+            block
+        };
+        blocks.push(styled(&self.title, Style::default().bold()));
+        if let Some(byline) = &self.byline { blocks.push(styled(byline, Style::default().italic().dim())) }
+        blocks.extend(self.body.text_blocks());
+        blocks
+    }
+}
+
+/// Adds the score of each paragraph to its parent, and half of it to its grandparent.
+fn score_paragraphs(node: &TuiNode, path: &mut Vec<usize>, scores: &mut HashMap<Vec<usize>, f32>) {
+    if is_hidden(node) { return }
+    if let TuiNodeData::Element(elem) = &node.data {
+        if FURNITURE_ELEMENTS.contains(&elem.name()) { return }
+        if is_paragraph(node, elem) {
+            let text = text_of(node);
+            let len = text.chars().count();
+            if len >= MIN_PARAGRAPH_CHARS {
+                let score = 1.0 + text.matches(',').count() as f32 + (len as f32 / 100.0).min(3.0);
+                if let Some((_, parent)) = path.split_last() {
+                    *scores.entry(parent.to_vec()).or_default() += score;
+                    if let Some((_, grandparent)) = parent.split_last() {
+                        *scores.entry(grandparent.to_vec()).or_default() += score / 2.0;
+                    }
+                }
+            }
+            return
+        }
+    }
+    for (c, child) in node.children.iter().enumerate() {
+        path.push(c);
+        score_paragraphs(child, path, scores);
+        path.pop();
+    }
+}
+
+/// Paragraph-like elements, including divs that only hold inline content.
+fn is_paragraph(node: &TuiNode, elem: &TuiElement) -> bool {
+    match elem.name() {
+        "p" | "pre" | "blockquote" | "td" => true,
+        "div" => !node.children.iter().any(|c| matches!(&c.data, TuiNodeData::Element(e) if e.is_block())),
+        _ => false,
+    }
+}
+
+fn tag_weight(elem: &TuiElement) -> f32 {
+    match elem.name() {
+        "article" | "main" => 10.0,
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "form" | "ol" | "ul" | "li" | "dl" | "dd" | "dt" | "address" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    }
+}
+
+/// Weighs an element by the words in its class and id.
+fn class_weight(elem: &TuiElement) -> f32 {
+    let names = format!("{} {}", elem.attr("class").unwrap_or_default(), elem.attr("id").unwrap_or_default()).to_lowercase();
+    let mut weight = 0.0;
+    if NEGATIVE_NAMES.iter().any(|n| names.contains(n)) { weight -= 25.0 }
+    if POSITIVE_NAMES.iter().any(|n| names.contains(n)) { weight += 25.0 }
+    weight
+}
+
+fn is_element(node: &TuiNode, name: &str) -> bool {
+    matches!(&node.data, TuiNodeData::Element(elem) if elem.name() == name)
+}
+
+fn is_hidden(node: &TuiNode) -> bool { node.style.as_ref().is_some_and(|s| s.hidden) }
+
+/// Short elements marked as naming the author.
+fn is_byline(node: &TuiNode) -> bool {
+    let TuiNodeData::Element(elem) = &node.data else { return false };
+    let names = format!("{} {}", elem.attr("class").unwrap_or_default(), elem.attr("id").unwrap_or_default()).to_lowercase();
+    let marked = elem.attr("rel") == Some("author") || elem.attr("itemprop").is_some_and(|p| p.contains("author"))
+        || names.contains("byline") || names.contains("author");
+    marked && (1..100).contains(&text_len(node))
+}
+
+/// Removes navigation, forms and link-heavy lists from an article body.
+fn remove_furniture(body: &mut TuiNode) {
+    remove_where(body, &|n| {
+        let TuiNodeData::Element(elem) = &n.data else { return false };
+        FURNITURE_ELEMENTS.contains(&elem.name()) || is_hidden(n)
+            || (class_weight(elem) < 0.0 && link_density(n) > 0.2)
+            || (matches!(elem.name(), "ul" | "ol" | "div") && link_density(n) > 0.5)
+    });
+}
+
+/// Removes every descendant matching a predicate.
+fn remove_where(node: &mut TuiNode, predicate: &dyn Fn(&TuiNode) -> bool) {
+    node.children.retain(|c| !predicate(c));
+    node.children.iter_mut().for_each(|c| remove_where(c, predicate));
+}
+
+/// Finds the first node in document order matching a predicate.
+fn find<'a>(node: &'a TuiNode, predicate: &dyn Fn(&TuiNode) -> bool) -> Option<&'a TuiNode> {
+    if predicate(node) { return Some(node) }
+    node.children.iter().find_map(|c| find(c, predicate))
+}
+
+/// Follows a path of child indices down from the root.
+fn node_at<'a>(root: &'a TuiNode, path: &[usize]) -> Option<&'a TuiNode> {
+    path.iter().try_fold(root, |node, &c| node.children.get(c))
+}
+
+/// Visible text within a node, with whitespace collapsed.
+fn text_of(node: &TuiNode) -> String {
+    let mut text = String::new();
+    collect_text(node, &mut text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn collect_text(node: &TuiNode, text: &mut String) {
+    if is_hidden(node) { return }
+    match &node.data {
+        TuiNodeData::Text(t) => { text.push_str(t); text.push(' ') },
+        TuiNodeData::Comment(_) => (),
+        _ => node.children.iter().for_each(|c| collect_text(c, text)),
+    }
+}
+
+fn text_len(node: &TuiNode) -> usize { text_of(node).chars().count() }
+
+/// Fraction of a node's text that is inside links.
+fn link_density(node: &TuiNode) -> f32 {
+    let total = text_len(node);
+    if total == 0 { return 0.0 }
+    let mut links = Vec::new();
+    collect_links(node, &mut links);
+    let linked: usize = links.iter().map(|l| text_len(l)).sum();
+    linked as f32 / total as f32
+}
+
+fn collect_links<'a>(node: &'a TuiNode, links: &mut Vec<&'a TuiNode>) {
+    match is_element(node, "a") {
+        true => links.push(node),
+        false => node.children.iter().for_each(|c| collect_links(c, links)),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+
+    const ARTICLE: &str = include_str!("../tests/fixtures/article.html");
+
+    fn page(html: &str) -> TuiNode {
+        let mut dom = filter_dom_html(html, &SelectorRules::default()).to_tui();
+        dom.collapse_phantoms();
+        dom
+    }
+
+    #[test]
+    fn extracts_article_without_page_furniture() {
+        let article = Article::extract(&page(ARTICLE), "Page title | Example News").expect("Article should be found");
+        assert_eq!(article.title, "Reading the web from a terminal");
+        assert_eq!(article.byline.as_deref(), Some("By Ada Example"));
+
+        let text: Vec<String> = article.text_blocks().into_iter().map(|b| b.text).collect();
+        assert_eq!(text[..2], ["Reading the web from a terminal", "By Ada Example"]);
+        assert!(text.iter().any(|t| t.starts_with("Terminal browsers")));
+        assert!(text.iter().any(|t| t.starts_with("The last paragraph")));
+        for furniture in ["Home", "Most popular", "Subscribe", "Copyright", "Share this"] {
+            assert!(!text.iter().any(|t| t.contains(furniture)), "{:?} should not be in the article", furniture);
+        }
+    }
+
+    #[test]
+    fn finds_no_article_on_link_pages() {
+        let links = "<html><body><ul>".to_owned()
+            + &(0..40).map(|i| format!("<li><a href='/{i}'>Link number {i}, with some words</a></li>")).collect::<String>()
+            + "</ul></body></html>";
+        assert!(Article::extract(&page(&links), "Links").is_none());
+    }
+}
@@ -0,0 +1,173 @@
+
+use super::browser::parse_content;
+use super::fetch::escape_html;
+use serde::{Serialize, Deserialize};
+
+/// Host serving DuckDuckGo's script-free results, which can be read into a native results list.
+const DDG_HTML_HOST: &str = "html.duckduckgo.com";
+
+/// A web search engine, which can be picked by typing its keyword before a search.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SearchEngine {
+    pub name: String,
+    /// Address of the results page, with {} in place of the search terms.
+    pub url: String,
+    /// Short name typed before a search to use this engine, like "w" in ":s w rust".
+    pub keyword: Option<String>,
+} impl SearchEngine {
+
+    pub fn new(name: &str, url: &str, keyword: Option<&str>) -> SearchEngine {
+        SearchEngine { name: name.to_owned(), url: url.to_owned(), keyword: keyword.map(str::to_owned) }
+    }
+
+    /// Address of the results page for the search terms.
+    pub fn url_for(&self, query: &str) -> String {
+        let terms: String = url::form_urlencoded::byte_serialize(query.trim().as_bytes()).collect();
+        self.url.replace("{}", &terms)
+    }
+}
+
+/// A result read from a search engine's results page.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchResult {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+/// Picks the engine for a search, by the keyword it starts with or else the default engine,
+/// returning the engine with the search terms that are left.
+pub fn resolve<'a>(engines: &'a [SearchEngine], default: &str, input: &'a str) -> Option<(&'a SearchEngine, &'a str)> {
+    let input = input.trim();
+    if let Some((first, rest)) = input.split_once(' ') {
+        let engine = engines.iter().find(|e| e.keyword.as_deref().is_some_and(|k| k.eq_ignore_ascii_case(first)));
+        if let Some(engine) = engine { return Some((engine, rest.trim())) }
+    }
+    let engine = engines.iter().find(|e| e.name.eq_ignore_ascii_case(default)).or(engines.first())?;
+    Some((engine, input))
+}
+
+/// Tells whether text typed in the command line is a search rather than an address.
+/// Addresses have a scheme, or are a single word that is localhost or has a dot in its host.
+pub fn is_search(input: &str) -> bool {
+    let input = input.trim();
+    if input.contains("://") || input.starts_with("about:") || input.starts_with("file:") { return false }
+    if input.is_empty() || input.contains(char::is_whitespace) { return !input.is_empty() }
+    let host = input.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(host, |(host, _)| host);
+    let dotted = host.contains('.') && !host.starts_with('.') && !host.ends_with('.');
+    !(dotted || host.eq_ignore_ascii_case("localhost"))
+}
+
+/// Whether a results page can be read into a native results list.
+pub fn has_native_results(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|u| u.host_str() == Some(DDG_HTML_HOST))
+}
+
+/// Reads the results from a page of DuckDuckGo's HTML results, leaving out ads.
+pub fn ddg_results(html: &str) -> Vec<SearchResult> {
+    let dom = parse_content(html);
+    let Ok(results) = dom.select(".result") else { return Vec::new() };
+    results.filter_map(|result| {
+        let node = result.as_node();
+        if result.attributes.borrow().get("class").is_some_and(|c| c.split_whitespace().any(|c| c == "result--ad")) { return None }
+        let link = node.select_first(".result__a").ok()?;
+        let href = link.attributes.borrow().get("href")?.to_owned();
+        let snippet = node.select_first(".result__snippet").map(|s| s.text_contents()).unwrap_or_default();
+        Some(SearchResult {
+            title: link.text_contents().trim().to_owned(),
+            url: unwrap_redirect(&href),
+            snippet: snippet.split_whitespace().collect::<Vec<_>>().join(" "),
+        })
+    }).collect()
+}
+
+/// Gets the destination of a link through DuckDuckGo's redirect, or the link as it is.
+fn unwrap_redirect(href: &str) -> String {
+    let absolute = match href.starts_with("//") { true => format!("https:{}", href), false => href.to_owned() };
+    url::Url::parse(&absolute).ok()
+        .and_then(|u| u.query_pairs().find(|(k, _)| k == "uddg").map(|(_, v)| v.into_owned()))
+        .unwrap_or(absolute)
+}
+
+/// Lays out search results as a compact page, with a title naming the search.
+pub fn results_page(query: &str, results: &[SearchResult]) -> (String, String) {
+    let title = format!("Search: {}", query);
+    let mut html = format!("<!DOCTYPE html><html><head><title>{}</title></head><body><ol>", escape_html(&title));
+    for result in results {
+        html += &format!("<li><p><a href=\"{0}\">{1}</a></p><p>{0}</p><p>{2}</p></li>",
+            escape_html(&result.url), escape_html(&result.title), escape_html(&result.snippet));
+    }
+    if results.is_empty() { html += "<p>No results</p>" }
+    (html + "</ol></body></html>", title)
+}
+
+/// Reads a fetched DuckDuckGo results page into a native results page, or None for any other page.
+pub fn native_page(url: &str, html: &str) -> Option<(String, String)> {
+    if !has_native_results(url) { return None }
+    let query = url::Url::parse(url).ok()?.query_pairs().find(|(k, _)| k == "q").map(|(_, q)| q.into_owned()).unwrap_or_default();
+    Some(results_page(&query, &ddg_results(html)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+
+    const DDG: &str = include_str!("../tests/fixtures/ddg.html");
+
+    fn engines() -> Vec<SearchEngine> {
+        vec![
+            SearchEngine::new("DuckDuckGo", "https://html.duckduckgo.com/html/?q={}", Some("ddg")),
+            SearchEngine::new("Wikipedia", "https://en.wikipedia.org/w/index.php?search={}", Some("w")),
+        ]
+    }
+
+    #[test]
+    fn picks_engines_by_keyword() {
+        let engines = engines();
+        let (engine, query) = resolve(&engines, "duckduckgo", "w Rust (programming language)").unwrap();
+        assert_eq!(engine.name, "Wikipedia");
+        assert_eq!(engine.url_for(query), "https://en.wikipedia.org/w/index.php?search=Rust+%28programming+language%29");
+        let (engine, query) = resolve(&engines, "DuckDuckGo", "what is a w").unwrap();
+        assert_eq!((engine.name.as_str(), query), ("DuckDuckGo", "what is a w"));
+        assert!(resolve(&[], "DuckDuckGo", "anything").is_none());
+    }
+
+    #[test]
+    fn tells_searches_from_addresses() {
+        for search in ["rust borrow checker", "rust", "what.is this", "C++"] {
+            assert!(is_search(search), "{}", search);
+        }
+        for address in ["example.com", "https://example.com/a b", "localhost:8080/api", "192.168.1.1", "about:blank", "file:///tmp/a.html", "docs.rs/tokio"] {
+            assert!(!is_search(address), "{}", address);
+        }
+    }
+
+    #[test]
+    fn reads_ddg_results() {
+        let results = ddg_results(DDG);
+        assert_eq!(results, vec![
+            SearchResult {
+                title: "References and Borrowing - The Rust Programming Language".into(),
+                url: "https://doc.rust-lang.org/book/ch04-02-references-and-borrowing.html".into(),
+                snippet: "The borrow checker makes sure references are always valid.".into(),
+            },
+            SearchResult {
+                title: "Understanding the borrow checker & lifetimes".into(),
+                url: "https://blog.example.com/borrowck".into(),
+                snippet: "A visual guide.".into(),
+            },
+        ]);
+
+        let (html, title) = native_page("https://html.duckduckgo.com/html/?q=rust+borrow+checker", DDG).unwrap();
+        assert_eq!(title, "Search: rust borrow checker");
+        let mut dom = filter_dom_html(&html, &SelectorRules::default()).to_tui();
+        dom.collapse_phantoms();
+        let text: Vec<String> = dom.text_blocks().into_iter().map(|b| b.text).collect();
+        assert_eq!(text[3], "Understanding the borrow checker & lifetimes");
+        assert!(native_page("https://duckduckgo.com/?q=rust", DDG).is_none());
+    }
+}
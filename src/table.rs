@@ -0,0 +1,299 @@
+
+use super::tui::{TuiNode, TuiNodeData};
+use ratatui::{prelude::*, widgets::*};
+
+/// Columns are separated by this, in both inline and full-screen tables.
+const COLUMN_SEPARATOR: &str = " │ ";
+const SEPARATOR_WIDTH: usize = 3;
+/// Columns don't shrink below their longest word, up to this width.
+const MAX_MIN_WIDTH: usize = 20;
+/// Columns don't grow past this width while there are other columns to fit.
+const MAX_NATURAL_WIDTH: usize = 60;
+const MAX_SPAN: usize = 100;
+
+/// An HTML table laid out as a grid of text cells.
+#[derive(Clone, Default)]
+pub struct TuiTable {
+    pub caption: Option<String>,
+    pub rows: Vec<TuiTableRow>,
+    /// Number of grid columns, after spans are counted.
+    pub columns: usize,
+    /// Columns of text scrolled past when the table is wider than the page.
+    pub hscroll: usize,
+} impl TuiTable {
+
+    /// Builds a table from a <table> node, or None for empty tables and tables only used for layout.
+    pub fn from_node(node: &TuiNode) -> Option<TuiTable> {
+        if let TuiNodeData::Element(elem) = &node.data {
+            if matches!(elem.attr("role"), Some("presentation") | Some("none")) { return None }
+        }
+        let mut table = TuiTable::default();
+        let mut rowspans = Vec::new();
+        for child in node.children.iter() {
+            if !table.collect_rows(child, false, &mut rowspans) { return None }
+        }
+        table.rows.iter().any(|r| !r.cells.is_empty()).then_some(table)
+    }
+
+    /// Adds the rows within a node, returning false if a nested table shows this is a layout table.
+    fn collect_rows(&mut self, node: &TuiNode, header: bool, rowspans: &mut Vec<usize>) -> bool {
+        let TuiNodeData::Element(elem) = &node.data else {
+            return node.children.iter().all(|c| self.collect_rows(c, header, rowspans))
+        };
+        if node.style.as_ref().is_some_and(|s| s.hidden) { return true }
+        match elem.name() {
+            "table" => false,
+            "caption" => { self.caption = Some(cell_text(node)); true },
+            "thead" => node.children.iter().all(|c| self.collect_rows(c, true, rowspans)),
+            "tr" => self.add_row(node, header, rowspans),
+            _ => node.children.iter().all(|c| self.collect_rows(c, header, rowspans)),
+        }
+    }
+
+    /// Places the cells of a row in the grid, skipping columns held by cells spanning from rows above.
+    /// Rowspans are approximated by leaving those columns blank.
+    fn add_row(&mut self, tr: &TuiNode, header: bool, rowspans: &mut Vec<usize>) -> bool {
+        let mut row = TuiTableRow { cells: Vec::new(), header };
+        let mut all_th = true;
+        let mut column = 0;
+        for cell in tr.children.iter() {
+            let TuiNodeData::Element(elem) = &cell.data else { continue };
+            if !matches!(elem.name(), "td" | "th") { continue }
+            if contains_table(cell) { return false }
+            all_th &= elem.name() == "th";
+
+            while rowspans.get(column).is_some_and(|&r| r > 0) { column += 1 }
+            let span = |name| elem.attr(name).and_then(|s| s.trim().parse::<usize>().ok()).unwrap_or(1).clamp(1, MAX_SPAN);
+            let (colspan, rowspan) = (span("colspan"), span("rowspan"));
+            if rowspans.len() < column + colspan { rowspans.resize(column + colspan, 0) }
+            rowspans[column..column + colspan].iter_mut().for_each(|r| *r = rowspan);
+
+            row.cells.push(TuiCell { text: cell_text(cell), column, colspan });
+            column += colspan;
+        }
+        rowspans.iter_mut().for_each(|r| *r = r.saturating_sub(1));
+
+        row.header |= all_th && !row.cells.is_empty();
+        self.columns = self.columns.max(column).max(rowspans.len());
+        self.rows.push(row);
+        true
+    }
+
+    /// Widths for each column that fit the table within `available` columns if possible.
+    /// Columns never shrink below their longest word, so very wide tables overflow and scroll.
+    pub fn column_widths(&self, available: usize) -> Vec<usize> {
+        let mut natural = vec![1; self.columns];
+        let mut min = vec![1; self.columns];
+        let cells = || self.rows.iter().flat_map(|r| r.cells.iter());
+        for cell in cells().filter(|c| c.colspan == 1) {
+            natural[cell.column] = natural[cell.column].max(cell.text.chars().count());
+            min[cell.column] = min[cell.column].max(cell.longest_word().min(MAX_MIN_WIDTH));
+        }
+        // Spanning cells widen their columns evenly when they don't fit across them.
+        for cell in cells().filter(|c| c.colspan > 1) {
+            let spanned = cell.column..(cell.column + cell.colspan).min(self.columns);
+            let across: usize = natural[spanned.clone()].iter().sum::<usize>() + SEPARATOR_WIDTH * (cell.colspan - 1);
+            let extra = cell.text.chars().count().saturating_sub(across).div_ceil(cell.colspan);
+            natural[spanned].iter_mut().for_each(|w| *w += extra);
+        }
+        if self.columns > 1 { natural.iter_mut().for_each(|w| *w = (*w).min(MAX_NATURAL_WIDTH)) }
+
+        let room = available.saturating_sub(SEPARATOR_WIDTH * self.columns.saturating_sub(1));
+        let (natural_total, min_total) = (natural.iter().sum::<usize>(), min.iter().sum::<usize>());
+        if natural_total <= room { return natural }
+        if min_total >= room { return min }
+
+        // Share the room beyond the minimum in proportion to how much more each column wants.
+        let spare = room - min_total;
+        let wanted = natural_total - min_total;
+        let mut widths: Vec<usize> = min.iter().zip(natural.iter())
+            .map(|(min, natural)| min + (natural - min) * spare / wanted)
+            .collect();
+        let mut leftover = room - widths.iter().sum::<usize>();
+        for (w, natural) in widths.iter_mut().zip(natural.iter()) {
+            if leftover == 0 { break }
+            if *w < *natural { *w += 1; leftover -= 1 }
+        }
+        widths
+    }
+
+    /// Width of the table once laid out within `available` columns.
+    pub fn layout_width(&self, available: usize) -> usize {
+        let widths = self.column_widths(available);
+        widths.iter().sum::<usize>() + SEPARATOR_WIDTH * widths.len().saturating_sub(1)
+    }
+
+    /// Lays out the table as lines of text, with cells wrapped within their columns.
+    /// Each line is paired with whether it belongs to a header.
+    pub fn lines(&self, available: usize) -> Vec<(String, bool)> {
+        let widths = self.column_widths(available);
+        let mut lines = Vec::new();
+        if let Some(caption) = &self.caption {
+            lines.extend(crate::utils::wrap_text(caption, available).into_iter().map(|(_, l)| (l, true)));
+        }
+
+        for (r, row) in self.rows.iter().enumerate() {
+            let segments = self.row_segments(row, &widths);
+            let height = segments.iter().map(|(_, lines)| lines.len()).max().unwrap_or(1).max(1);
+            for l in 0..height {
+                let line: Vec<String> = segments.iter()
+                    .map(|(width, text)| format!("{:<width$}", text.get(l).map_or("", String::as_str), width = width))
+                    .collect();
+                // Blank cells at the end of a row leave no trailing separators.
+                let line = line.join(COLUMN_SEPARATOR).trim_end_matches([' ', '│']).to_owned();
+                lines.push((line, row.header));
+            }
+            // Rule off the header from the body.
+            if row.header && self.rows.get(r + 1).is_some_and(|next| !next.header) {
+                let rule: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+                lines.push((rule.join("─┼─"), false));
+            }
+        }
+        lines
+    }
+
+    /// Wraps each cell of a row to the width of the columns it spans, with blanks for empty columns.
+    fn row_segments(&self, row: &TuiTableRow, widths: &[usize]) -> Vec<(usize, Vec<String>)> {
+        let mut segments = Vec::new();
+        let mut column = 0;
+        while column < widths.len() {
+            match row.cells.iter().find(|c| c.column == column) {
+                Some(cell) => {
+                    let end = (column + cell.colspan).min(widths.len());
+                    let width = widths[column..end].iter().sum::<usize>() + SEPARATOR_WIDTH * (end - column - 1);
+                    let text = crate::utils::wrap_text(&cell.text, width).into_iter().map(|(_, l)| l).collect();
+                    segments.push((width, text));
+                    column = end;
+                },
+                None => {
+                    segments.push((widths[column], Vec::new()));
+                    column += 1;
+                },
+            }
+        }
+        segments
+    }
+
+    /// Builds a ratatui Table for the full-screen view, starting from a column.
+    /// Spanning cells are placed in their first column, with the rest of the span left blank.
+    pub fn widget(&self, available: usize, first_column: usize) -> Table<'static> {
+        let widths = self.column_widths(available);
+        let first_column = first_column.min(self.columns.saturating_sub(1));
+        let to_row = |row: &TuiTableRow| {
+            let mut height = 1;
+            let cells: Vec<Cell> = (first_column..self.columns).map(|column| {
+                let Some(cell) = row.cells.iter().find(|c| c.column == column) else { return Cell::from("") };
+                let lines: Vec<Line> = crate::utils::wrap_text(&cell.text, widths[column]).into_iter()
+                    .map(|(_, l)| Line::from(l))
+                    .collect();
+                height = height.max(lines.len());
+                Cell::from(Text::from(lines))
+            }).collect();
+            let style = match row.header { true => Style::default().bold(), false => Style::default() };
+            Row::new(cells).height(height as u16).style(style)
+        };
+
+        let header_rows = self.rows.iter().take_while(|r| r.header).count().min(1);
+        let constraints: Vec<Constraint> = widths[first_column..].iter().map(|w| Constraint::Length(*w as u16)).collect();
+        let mut table = Table::new(self.rows[header_rows..].iter().map(to_row), constraints)
+            .column_spacing(SEPARATOR_WIDTH as u16)
+            .block(Block::default().borders(Borders::ALL).title(self.caption.clone().unwrap_or_else(|| "Table".to_owned())));
+        if let Some(header) = self.rows.first().filter(|_| header_rows == 1) {
+            table = table.header(to_row(header).bottom_margin(1));
+        }
+        table
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct TuiTableRow {
+    pub cells: Vec<TuiCell>,
+    /// Rows in <thead>, or made only of <th> cells.
+    pub header: bool,
+}
+
+#[derive(Clone)]
+pub struct TuiCell {
+    pub text: String,
+    /// Grid column the cell starts in.
+    pub column: usize,
+    pub colspan: usize,
+} impl TuiCell {
+    fn longest_word(&self) -> usize {
+        self.text.split_whitespace().map(|w| w.chars().count()).max().unwrap_or(0)
+    }
+}
+
+/// Text of a cell on a single line.
+fn cell_text(node: &TuiNode) -> String {
+    node.text_blocks().iter().map(|b| b.text.trim()).collect::<Vec<_>>().join(" ")
+}
+
+fn contains_table(node: &TuiNode) -> bool {
+    node.children.iter().any(|c| matches!(&c.data, TuiNodeData::Element(e) if e.name() == "table") || contains_table(c))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+
+    const TABLES: &str = include_str!("../tests/fixtures/tables.html");
+
+    fn tables() -> Vec<Option<TuiTable>> {
+        let mut dom = filter_dom_html(TABLES, &SelectorRules::default()).to_tui();
+        dom.collapse_phantoms();
+        let mut tables = Vec::new();
+        find_tables(&dom, &mut tables);
+        tables
+    }
+
+    fn find_tables(node: &TuiNode, tables: &mut Vec<Option<TuiTable>>) {
+        match &node.data {
+            TuiNodeData::Element(e) if e.name() == "table" => tables.push(TuiTable::from_node(node)),
+            _ => node.children.iter().for_each(|c| find_tables(c, tables)),
+        }
+    }
+
+    #[test]
+    fn aligns_columns_under_header() {
+        let table = tables().remove(0).expect("Data table");
+        assert_eq!(table.caption.as_deref(), Some("Planets"));
+        assert_eq!(table.lines(80).into_iter().map(|(l, _)| l).collect::<Vec<_>>(), vec![
+            "Planets",
+            "Name    │ Moons │ Notes",
+            &format!("{}┼{}┼{}", "─".repeat(8), "─".repeat(7), "─".repeat(19)),
+            "Mercury │ 0     │ Closest to the sun",
+            "Jupiter │ 95    │ Largest planet",
+            "Total   │ 95",
+        ]);
+    }
+
+    #[test]
+    fn wraps_cells_to_fit() {
+        let table = tables().remove(0).expect("Data table");
+        let lines: Vec<String> = table.lines(30).into_iter().map(|(l, _)| l).collect();
+        assert!(lines.iter().all(|l| l.chars().count() <= 30), "{:?}", lines);
+        assert!(lines.contains(&"Mercury │ 0     │ Closest to".to_owned()));
+    }
+
+    #[test]
+    fn approximates_spans() {
+        let table = tables().remove(1).expect("Spanning table");
+        assert_eq!(table.columns, 3);
+        assert_eq!(table.rows[1].cells.iter().map(|c| c.column).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(table.lines(80).into_iter().map(|(l, _)| l).collect::<Vec<_>>(), vec![
+            "Group │ A  │ B",
+            "      │ A2 │ B2",
+            "Across all",
+        ]);
+    }
+
+    #[test]
+    fn skips_layout_tables() {
+        let tables = tables();
+        assert!(tables[2].is_none() && tables[3].is_none());
+    }
+}
@@ -0,0 +1,153 @@
+
+use super::cookies::Cookie;
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/*
+ Hands pages off to curl and wget.
+    The tools are given the tab's cookies, user agent and referer, so sites treat them like the tab.
+    Cookies go through a cookies.txt file only the user can read, rather than the command line where ps shows them,
+    and keep their domain, path and secure flag so redirects don't carry them to other sites.
+    Flags typed before the URL are passed through as they are.
+*/
+
+/// What a tab would send with a request, for tools to send the same.
+#[derive(Clone, Default, Debug)]
+pub struct Session {
+    pub user_agent: Option<String>,
+    /// Address of the page the request is made from.
+    pub referer: Option<String>,
+    pub cookies: Vec<Cookie>,
+}
+
+/// A tool to run once the session it sends is gathered, with the flags it was given.
+#[derive(Clone, Debug)]
+pub enum Tool { Curl(Vec<String>), Wget(Vec<String>) }
+
+/// Splits arguments to :c or :w into flags for the tool and the URL, which comes last, after -- if there is one.
+/// Without --, the last word is only the URL if it is an address, and a bare host right after a flag
+/// is taken to be the flag's value, as in `-O out.html`, unless it has a scheme.
+/// With no URL, only flags are returned.
+pub fn parse_args(args: &str) -> (Vec<String>, Option<String>) {
+    let mut words = crate::utils::split_args(args);
+    if let Some(end) = words.iter().position(|w| w == "--") {
+        let url = words.drain(end..).nth(1);
+        return (words, url.map(|u| crate::address::normalize(&u).unwrap_or(u)))
+    }
+
+    let Some(last) = words.last().filter(|w| !w.starts_with('-')) else { return (words, None) };
+    let Some(url) = crate::address::normalize(last) else { return (words, None) };
+    let after_flag = words.len() > 1 && words[words.len() - 2].starts_with('-');
+    if after_flag && !last.contains(':') { return (words, None) }
+    words.pop();
+    (words, Some(url))
+}
+
+/// A session's cookies written to a temporary cookies.txt file for a tool to load, deleted when dropped.
+/// It should be kept until the tool exits.
+pub struct CookieJar {
+    pub path: PathBuf,
+} impl CookieJar {
+
+    /// Writes the session's cookies to a file only the user can read, or None if it has no cookies.
+    /// wget skips curl's #HttpOnly_ lines as comments, so they are only marked for curl.
+    pub fn write(session: &Session, http_only_marks: bool) -> Result<Option<CookieJar>, anyhow::Error> {
+        if session.cookies.is_empty() { return Ok(None) }
+        static JARS: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("etymon-cookies-{}-{}", std::process::id(), JARS.fetch_add(1, Ordering::Relaxed));
+        let jar = CookieJar { path: std::env::temp_dir().join(name) };
+        let cookies: Vec<Cookie> = session.cookies.iter()
+            .map(|c| Cookie { http_only: c.http_only && http_only_marks, ..c.clone() })
+            .collect();
+        let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&jar.path)?;
+        file.write_all(crate::cookies::to_netscape(&cookies).as_bytes())?;
+        Ok(Some(jar))
+    }
+}
+impl Drop for CookieJar {
+    fn drop(&mut self) { let _gone = std::fs::remove_file(&self.path); }
+}
+
+/// Builds a curl command that prints the response to a URL, following redirects.
+pub fn curl(url: &str, flags: &[String], session: &Session, jar: Option<&CookieJar>) -> Command {
+    let mut command = Command::new("curl");
+    command.args(["--silent", "--show-error", "--location"]);
+    if let Some(agent) = &session.user_agent { command.arg("--user-agent").arg(agent); }
+    if let Some(referer) = &session.referer { command.arg("--referer").arg(referer); }
+    if let Some(jar) = jar { command.arg("--cookie").arg(&jar.path); }
+    command.args(flags).arg(url);
+    command
+}
+
+/// Builds a wget command that saves a URL to a file.
+pub fn wget(url: &str, flags: &[String], session: &Session, jar: Option<&CookieJar>, path: &std::path::Path) -> Command {
+    let mut command = Command::new("wget");
+    command.arg("--quiet");
+    if let Some(agent) = &session.user_agent { command.arg(format!("--user-agent={}", agent)); }
+    if let Some(referer) = &session.referer { command.arg(format!("--referer={}", referer)); }
+    if let Some(jar) = jar { command.arg(format!("--load-cookies={}", jar.path.display())); }
+    command.arg(format!("--output-document={}", path.display())).args(flags).arg(url);
+    command
+}
+
+/// A new id for a download made by wget, in place of the guid chrome gives its downloads.
+pub fn download_id() -> String {
+    static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+    format!("wget-{}", DOWNLOADS.fetch_add(1, Ordering::Relaxed))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command.get_args().map(|a| a.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn passes_the_session_to_tools() {
+        let (flags, url) = parse_args("-i -H 'Accept: application/json' https://example.com/api");
+        assert_eq!(flags, ["-i", "-H", "Accept: application/json"]);
+        assert_eq!(url.as_deref(), Some("https://example.com/api"));
+        assert_eq!(parse_args("--head"), (vec!["--head".to_owned()], None));
+        assert_eq!(parse_args("example.com"), (Vec::new(), Some("https://example.com/".to_owned())));
+
+        // Values of flags aren't taken for the URL.
+        assert_eq!(parse_args("-H 'Accept: text/html'"), (vec!["-H".to_owned(), "Accept: text/html".to_owned()], None));
+        assert_eq!(parse_args("-O out.html"), (vec!["-O".to_owned(), "out.html".to_owned()], None));
+        assert_eq!(parse_args("-O out.html -- example.com"),
+            (vec!["-O".to_owned(), "out.html".to_owned()], Some("https://example.com/".to_owned())));
+        assert_eq!(parse_args("-O out.html https://example.com/a").1.as_deref(), Some("https://example.com/a"));
+        assert_eq!(parse_args("-i --").1, None);
+
+        let session = Session {
+            user_agent: Some("Mozilla/5.0".into()),
+            referer: Some("https://example.com/".into()),
+            cookies: crate::cookies::from_netscape("example.com\tFALSE\t/\tFALSE\t0\ta\t1\nexample.com\tFALSE\t/\tFALSE\t0\tb\t2\n"),
+        };
+        let jar = CookieJar::write(&session, true).unwrap().unwrap();
+        let command = curl("https://example.com/api", &flags, &session, Some(&jar));
+        assert_eq!(command.get_program(), "curl");
+        assert_eq!(args(&command), [
+            "--silent", "--show-error", "--location", "--user-agent", "Mozilla/5.0", "--referer", "https://example.com/",
+            "--cookie", &jar.path.to_string_lossy(), "-i", "-H", "Accept: application/json", "https://example.com/api",
+        ]);
+        // The cookies stay off the command line, in a file only the user can read.
+        assert!(!args(&command).iter().any(|a| a.contains("a=1")));
+        assert_eq!(crate::cookies::from_netscape(&std::fs::read_to_string(&jar.path).unwrap()), session.cookies);
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&jar.path).unwrap().permissions().mode() & 0o777, 0o600);
+        let path = jar.path.clone();
+        drop(jar);
+        assert!(!path.exists());
+
+        let command = wget("https://example.com/a.zip", &[], &Session::default(), None, std::path::Path::new("/tmp/wget-0"));
+        assert_eq!(args(&command), ["--quiet", "--output-document=/tmp/wget-0", "https://example.com/a.zip"]);
+        assert!(CookieJar::write(&Session::default(), false).unwrap().is_none());
+    }
+}
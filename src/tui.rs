@@ -1,12 +1,20 @@
 
-use headless_chrome as chrome;
+use crate::ui::UIMode;
+use crate::reader::Article;
+use crate::table::TuiTable;
+use crate::media::TuiMedia;
+use crate::downloads::{Downloads, DownloadState};
+use crate::cookies::Cookies;
+use crate::backend::BackendKind;
+use crate::find::Find;
+use crate::address::{Completion, CompletionSource};
+use crate::graphics::{TuiPicture, Placement, GraphicsProtocol};
 use ratatui::{prelude::*, widgets::*, Terminal, backend::CrosstermBackend as Backend};
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
 
-use super::browser::*;
-
-// TODO Expandable media types: Image, video?
 
 
 /// Holistic frontend state & events.
@@ -14,6 +22,8 @@ pub struct Tui {
     pub terminal: Terminal<Backend<std::io::Stdout>>,
     pub display: TuiDisplay,
     pub cursor_cache: (u16, u16),
+    /// Pictures drawn over the screen by a graphics protocol at the last draw.
+    pub placed: Vec<Placement>,
 } impl Tui {
 
     /// Initializes the TUI on startup. Panics if a new terminal can't be generated.
@@ -22,39 +32,75 @@ pub struct Tui {
         let screen = std::io::stdout();
         if let Ok(terminal) = Terminal::new(Backend::new(screen)) {
             let display = TuiDisplay::new();
-            Self { terminal, display, cursor_cache: (0, 0)}
+            Self { terminal, display, cursor_cache: (0, 0), placed: Vec::new() }
         } else { panic!("Fatal Error: `Tui::init()` failed to create frontend!") }
     }
 
     /// Moves the cursor within bounds.
+    /// The position is tracked here rather than queried from the terminal,
+    /// which would contend with the input thread for terminal events.
     pub fn move_cursor(&mut self, direction: CursorDirection) -> Result<(), anyhow::Error> {
-        let cursor = self.terminal.get_cursor()?;
+        let size = self.terminal.size()?;
+        let (x, y) = &mut self.cursor_cache;
         use CursorDirection::*;
         match direction {
-            Up    => if cursor.1 > 0 { self.terminal.set_cursor(cursor.0, cursor.1 - 1)? },
-            Down  => self.terminal.set_cursor(cursor.0, cursor.1 + 1)?,
-            Left  => if cursor.0 > 0 { self.terminal.set_cursor(cursor.0 - 1, cursor.1)? },
-            Right => self.terminal.set_cursor(cursor.0 + 1, cursor.1)?,
+            Up    => *y = y.saturating_sub(1),
+            Down  => *y = (*y + 1).min(size.height.saturating_sub(1)),
+            Left  => *x = x.saturating_sub(1),
+            Right => *x = (*x + 1).min(size.width.saturating_sub(1)),
         }
         Ok(())
     }
 
-    /// Stores the cursor position to allow redrawing of display.
-    pub fn cache_cursor(&mut self) -> Result<(), anyhow::Error>{
-        self.cursor_cache = self.terminal.get_cursor()?;
+    /// Resizes the terminal buffers after the terminal window has changed size.
+    /// Page text is rewrapped to the new dimensions on the next draw.
+    pub fn resize(&mut self, width: u16, height: u16) -> Result<(), anyhow::Error> {
+        self.terminal.resize(Rect::new(0, 0, width, height))?;
+        let (x, y) = self.cursor_cache;
+        self.cursor_cache = (x.min(width.saturating_sub(1)), y.min(height.saturating_sub(1)));
         Ok(())
     }
 
+    /// Width in columns available to page content, accounting for the page border.
+    pub fn page_columns(&self) -> Result<u16, anyhow::Error> {
+        Ok(self.terminal.size()?.width.saturating_sub(2))
+    }
+
     /// Redraw the TUI screen according to its Display layout.
     pub fn draw(&mut self) -> Result<(), anyhow::Error> {
+        self.draw_frame()?;
+        self.place_pictures()
+    }
+
+    fn draw_frame(&mut self) -> Result<(), anyhow::Error> {
         self.terminal.draw(|frame| {
-            self.display.render(frame).expect("Display renders to frame."); // TODO Handle draw error with logging?
+            self.display.render(frame, self.cursor_cache).expect("Display renders to frame."); // TODO Handle draw error with logging?
             let (x, y) = self.cursor_cache;
-            frame.set_cursor(x, y); // TODO DEBUG, causes reset to 0, 0 on redraw.
+            frame.set_cursor(x, y);
         })?;
         Ok(())
     }
 
+    /// Draws pictures with the terminal's graphics protocol once ratatui has drawn the frame around them.
+    /// Pictures are only redrawn when they move, as ratatui leaves the cells under them alone.
+    fn place_pictures(&mut self) -> Result<(), anyhow::Error> {
+        let Some(protocol) = crate::graphics::current_protocol().filter(|p| *p != GraphicsProtocol::HalfBlock) else { return Ok(()) };
+        let placements = match (&self.display.overlay, self.display.tabs.get(self.display.current_tab)) {
+            (None, Some(tab)) => tab.placements.clone(),
+            _ => Vec::new(),
+        };
+        if placements == self.placed { return Ok(()) }
+
+        // Sixels stay on screen until overwritten, so the frame is redrawn in full to clear them.
+        if protocol == GraphicsProtocol::Sixel && !self.placed.is_empty() {
+            self.terminal.clear()?;
+            self.draw_frame()?;
+        }
+        crate::graphics::place(self.terminal.backend_mut(), protocol, &placements)?;
+        self.placed = placements;
+        Ok(())
+    }
+
     /// TBD Runs destructors and resets terminal changes.
     pub fn quit(&mut self) -> Result<(), anyhow::Error> {
         crossterm::terminal::disable_raw_mode()?;
@@ -73,37 +119,72 @@ pub struct TuiDisplay {
     pub navbar: TuiNavbar,
     pub tabs: VecDeque<TuiTab>,
     pub current_tab: usize,
+    /// Full-screen view drawn in place of the page, if one is open.
+    pub overlay: Option<TuiOverlay>,
+    pub downloads: Downloads,
 } impl TuiDisplay {
 
     /// Struct to organize the state and properties of widgets to be rendered.
     pub fn new() -> TuiDisplay {
-        TuiDisplay { navbar: TuiNavbar::new(), tabs: VecDeque::new(), current_tab: 0 }
+        TuiDisplay { navbar: TuiNavbar::new(), tabs: VecDeque::new(), current_tab: 0, overlay: None, downloads: Downloads::default() }
     }
 
-    /// Gets titles for all active tabs.
-    pub fn tab_titles(&self) -> Vec<String> { self.tabs.iter().map(|t| t.title.clone()).collect() }
+    /// Gets the tab currently in view.
+    pub fn current_tab_mut(&mut self) -> Option<&mut TuiTab> { self.tabs.get_mut(self.current_tab) }
+
+    /// Gets titles for all active tabs, marking those that are still loading.
+    pub fn tab_titles(&self) -> Vec<String> {
+        self.tabs.iter().map(|t| match &t.load {
+            LoadState::Loading(percent) => format!("{} ({}%)", t.title, percent),
+            LoadState::Failed(_) => format!("{} (!)", t.title),
+            LoadState::Loaded => t.title.clone(),
+        }).collect()
+    }
 
-    /// Adds a new TuiTab to the register. TuiNavbar has to be redrawn separately for change to become visible.
-    pub fn new_tab(&mut self, id: &str, title: &str) -> Result<(), anyhow::Error> {
-        self.tabs.push_back(TuiTab::new(id, title)); // TODO Push tab to spec position
+    /// Adds a new TuiTab to the register and brings it into view.
+    /// TuiNavbar has to be redrawn separately for change to become visible.
+    pub fn new_tab(&mut self, id: &str, title: &str, backend: BackendKind) -> Result<(), anyhow::Error> {
+        self.tabs.push_back(TuiTab::new(id, title, backend)); // TODO Push tab to spec position
+        self.current_tab = self.tabs.len() - 1;
         Ok(())
     }
 
-    /// Extracts HTML from chrome tab and rebuilds TuiTab to reflect changes.
-    pub fn update_tab(&mut self, chrome_tab: &chrome::Tab) -> Result<(), anyhow::Error> {
-        let tab_id = chrome_tab.get_target_id();
-        let html = chrome_tab.get_content()?.to_string();
-        filter_dom_html(&html);
+    /// Removes a tab, keeping the tab in view if it wasn't the one removed.
+    pub fn close_tab(&mut self, id: &str) {
+        let Some(index) = self.tabs.iter().position(|t| t.id == id) else { return };
+        self.tabs.remove(index);
+        if index < self.current_tab || self.current_tab >= self.tabs.len() {
+            self.current_tab = self.current_tab.saturating_sub(1);
+        }
+    }
 
-        let tab = self.tabs.iter_mut().find(|t| &t.id == tab_id).expect("Finds tab in record");
-        tab.update(parse_content(&html).to_tui(), &chrome_tab.get_title()?)?;
+    /// Adds a download started from a tab to the download list.
+    pub fn start_download(&mut self, tab_id: &str, guid: &str, url: &str, filename: &str) {
+        let download = self.downloads.start(tab_id, guid, url, filename);
+        self.navbar.status.post(&format!("Downloading {} (D lists downloads)", download.filename));
+    }
 
-        Ok(())
+    /// Updates a download in the list, reporting how it ended once it has.
+    /// Returns true if the download has ended.
+    pub fn update_download(&mut self, guid: &str, received: u64, total: Option<u64>, state: DownloadState) -> bool {
+        let Some(download) = self.downloads.update(guid, received, total, state) else { return false };
+        let message = match &download.state {
+            DownloadState::Completed => format!("Downloaded {}", download.path.display()),
+            DownloadState::Canceled => format!("Canceled download of {}", download.filename),
+            DownloadState::Failed(error) => format!("Download of {} failed: {}", download.filename, error),
+            DownloadState::InProgress => return false,
+        };
+        self.navbar.status.post(&message);
+        true
     }
 
+    /// Finds a tab by the id of its page backend.
+    pub fn tab_mut(&mut self, id: &str) -> Option<&mut TuiTab> { self.tabs.iter_mut().find(|t| t.id == id) }
+
     /// Renders the Display to the current screen.
-    pub fn render(&mut self, frame: &mut Frame) -> Result<(), anyhow::Error> {
-        let bar_h = match self.navbar.show_tabs { true => 2, false => 1 };
+    /// The cursor position is used to find the link under focus.
+    pub fn render(&mut self, frame: &mut Frame, cursor: (u16, u16)) -> Result<(), anyhow::Error> {
+        let bar_h = match self.navbar.show_tabs { true => 3, false => 2 };
         let layout = {
             Layout::default()
                 .direction(Direction::Vertical)
@@ -114,34 +195,35 @@ pub struct TuiDisplay {
                 .split(frame.size())
         };
 
-        let navbar_constraints = match self.navbar.show_tabs {
-            true => [ Constraint::Percentage(50), Constraint::Percentage(50) ],
-            false => [ Constraint::Percentage(100), Constraint::Percentage(0) ]
-        };
-
         let navbar_layout = {
             Layout::default()
                 .direction(Direction::Vertical)
-                .constraints(navbar_constraints)
+                .constraints(vec![Constraint::Length(1); bar_h as usize])
                 .split(layout[1])
         };
 
         let tab_content = Block::default().borders(Borders::ALL).title("Page Content");
+        let page_area = tab_content.inner(layout[0]);
         frame.render_widget(tab_content, layout[0]);
 
-        // TODO Debug: Recursion fails to resolve and program hangs
-        //let current_page = &self.tabs[self.current_tab];
-        //current_page.render_page(frame);
+        if let Some(overlay) = &self.overlay {
+            overlay.render(frame, page_area, &self.downloads);
+        } else if let Some(current_page) = self.tabs.get_mut(self.current_tab) {
+            current_page.render(frame, page_area);
+        }
+
+        let status_bar = self.navbar.status.render(self.tabs.get(self.current_tab), cursor);
+        frame.render_widget(status_bar, navbar_layout[0]);
 
         let tab_titles = self.tab_titles();
-        let tab_list = self.navbar.render_tab_list(tab_titles);
+        let tab_list = self.navbar.render_tab_list(tab_titles, self.current_tab);
         let command_line = self.navbar.render_command_line();
 
         if self.navbar.show_tabs {
-            frame.render_widget(tab_list, navbar_layout[0]);
-            frame.render_widget(command_line, navbar_layout[1]);
+            frame.render_widget(tab_list, navbar_layout[1]);
+            frame.render_widget(command_line, navbar_layout[2]);
         } else {
-            frame.render_widget(command_line, navbar_layout[0]);
+            frame.render_widget(command_line, navbar_layout[1]);
         }
 
         Ok(())
@@ -151,45 +233,261 @@ pub struct TuiDisplay {
 
 
 
+/// Full-screen views that take the place of the page until closed.
+pub enum TuiOverlay {
+    /// A table from the page, scrolled by row and column.
+    Table { table: TuiTable, row: usize, column: usize },
+    /// The session's downloads, with the selected one highlighted.
+    Downloads,
+    /// Cookies of the page in view, with the selected one highlighted.
+    Cookies(Cookies),
+} impl TuiOverlay {
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, downloads: &Downloads) {
+        match self {
+            TuiOverlay::Table { table, row, column } => {
+                let mut state = TableState::default().with_offset(*row);
+                frame.render_stateful_widget(table.widget(area.width.saturating_sub(2) as usize, *column), area, &mut state);
+            },
+            TuiOverlay::Downloads => {
+                let mut state = TableState::default().with_selected(Some(downloads.selected));
+                frame.render_stateful_widget(downloads.widget(), area, &mut state);
+            },
+            TuiOverlay::Cookies(cookies) => {
+                let mut state = TableState::default().with_selected(Some(cookies.selected));
+                frame.render_stateful_widget(cookies.widget(), area, &mut state);
+            },
+        }
+    }
+
+    /// Scrolls the overlay by rows and columns, within its extents.
+    pub fn scroll(&mut self, rows: isize, columns: isize) {
+        match self {
+            TuiOverlay::Table { table, row, column } => {
+                let shift = |at: usize, by: isize, len: usize| ((at as isize + by).max(0) as usize).min(len.saturating_sub(1));
+                *row = shift(*row, rows, table.rows.len());
+                *column = shift(*column, columns, table.columns);
+            },
+            TuiOverlay::Downloads | TuiOverlay::Cookies(_) => (),
+        }
+    }
+}
+
+impl std::default::Default for TuiDisplay {
+    fn default() -> Self { TuiDisplay::new() }
+}
+
 
 
 pub struct TuiNavbar {
     pub show_tabs: bool,
     //pub show_bookmarks: bool,
+    pub status: TuiStatusbar,
+    /// Text typed into the command line while in Command mode.
+    pub command: String,
+    /// Pages offered for the address being typed, best first.
+    pub completions: Vec<Completion>,
+    /// Completion filled into the command line with Tab, if any.
+    pub completion: Option<usize>,
 } impl TuiNavbar {
 
-    pub fn new() -> Self { TuiNavbar { show_tabs: true }}
+    pub fn new() -> Self {
+        TuiNavbar { show_tabs: true, status: TuiStatusbar::new(), command: String::new(), completions: Vec::new(), completion: None }
+    }
 
-    pub fn render_tab_list(&self, tab_titles: Vec<String>) -> Tabs {
+    /// Fills the next (or previous) completion into the command line in place of the address typed from start.
+    pub fn cycle_completion(&mut self, start: usize, step: isize) {
+        if self.completions.is_empty() { return }
+        let len = self.completions.len() as isize;
+        let next = match self.completion {
+            Some(current) => (current as isize + step).rem_euclid(len),
+            None if step < 0 => len - 1,
+            None => 0,
+        } as usize;
+        self.command.truncate(start);
+        self.command += &self.completions[next].url;
+        self.completion = Some(next);
+    }
+
+    pub fn render_tab_list(&self, tab_titles: Vec<String>, current_tab: usize) -> Tabs<'_> {
        Tabs::new(tab_titles)
             .block(Block::default().borders(Borders::NONE))
             .style(Style::default().white().bg(Color::Blue))
             .highlight_style(Style::default().yellow())
-            .select(0)
+            .select(current_tab)
             .divider(symbols::DOT)
             .padding("┌", "┐") // TBD No padding for active tab, has top 3 sides border instead
     }
 
-    pub fn render_command_line(&self) -> Paragraph {
-        Paragraph::new("Command line interface").style(Style::default().white().bg(Color::Black))
+    pub fn render_command_line(&self) -> Paragraph<'_> {
+        let mut spans = match self.status.mode {
+            UIMode::Command => vec![Span::from(format!(":{}", self.command))],
+            _ => vec![Span::from("Command line interface")],
+        };
+        // Completions follow what is typed, with the one filled in highlighted.
+        if self.status.mode == UIMode::Command && !self.completions.is_empty() {
+            spans.push(Span::from("   Tab:").dark_gray());
+            for (i, completion) in self.completions.iter().enumerate() {
+                let marker = match completion.source {
+                    CompletionSource::Tab => "▣",
+                    CompletionSource::Bookmark => "★",
+                    CompletionSource::History => "·",
+                };
+                let label = match completion.title.is_empty() {
+                    true => format!(" {} {} ", marker, completion.url),
+                    false => format!(" {} {} ({}) ", marker, completion.title, completion.url),
+                };
+                spans.push(match self.completion == Some(i) {
+                    true => Span::from(label).black().on_gray(),
+                    false => Span::from(label).gray(),
+                });
+            }
+        }
+        Paragraph::new(Line::from(spans)).style(Style::default().white().bg(Color::Black))
     }
 }
 
+impl std::default::Default for TuiNavbar {
+    fn default() -> Self { TuiNavbar::new() }
+}
+
+/// Summarizes the state of the browser and the current page.
+pub struct TuiStatusbar {
+    /// Mirrors the input mode held by Etymon.
+    pub mode: UIMode,
+    /// Transient message and the time it was posted.
+    pub message: Option<(String, std::time::Instant)>,
+} impl TuiStatusbar {
+
+    /// How long a transient message stays in the status bar.
+    const MESSAGE_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+
+    pub fn new() -> Self { TuiStatusbar { mode: UIMode::View, message: None }}
+
+    /// Shows a message in the status bar until it expires.
+    pub fn post(&mut self, message: &str) {
+        self.message = Some((message.to_owned(), std::time::Instant::now()))
+    }
+
+    /// Clears the message once it has been shown long enough.
+    pub fn expire_message(&mut self) {
+        if self.message.as_ref().is_some_and(|(_, posted)| posted.elapsed() > Self::MESSAGE_DURATION) {
+            self.message = None
+        }
+    }
+
+    /// Builds the status line for the given tab.
+    pub fn render(&self, tab: Option<&TuiTab>, cursor: (u16, u16)) -> Paragraph<'_> {
+        let mode = match self.mode {
+            UIMode::View    => Span::from(" VIEW ").black().on_green(),
+            UIMode::Insert  => Span::from(" INSERT ").black().on_yellow(),
+            UIMode::Command => Span::from(" COMMAND ").black().on_cyan(),
+        };
+        let mut spans = vec![mode, Span::from(" ")];
+
+        if let Some(tab) = tab {
+            spans.push(match tab.security() {
+                Security::Secure   => Span::from("[https] ").green(),
+                Security::Insecure => Span::from("[http] ").red(),
+                Security::Local    => Span::from("[local] ").gray(),
+            });
+            spans.push(Span::from(tab.url.clone()));
+            spans.push(match &tab.load {
+                LoadState::Loading(percent) => Span::from(format!(" loading {}%", percent)).yellow(),
+                LoadState::Failed(_) => Span::from(" failed").red(),
+                LoadState::Loaded => Span::from(""),
+            });
+            if tab.reader { spans.push(Span::from(" [reader]").green()) }
+            if tab.backend != BackendKind::Chrome { spans.push(Span::from(format!(" [{}]", tab.backend.name())).cyan()) }
+            if tab.blocked > 0 {
+                spans.push(Span::from(format!(" [{} blocked]", tab.blocked)).magenta());
+            }
+            if let Some(find) = &tab.find { spans.push(Span::from(format!(" [{}]", find.status())).yellow()) }
+            spans.push(Span::from(format!(" {}%", tab.scroll_percent())));
+            if let Some(link) = tab.link_at(cursor.0, cursor.1) {
+                spans.push(Span::from(format!(" -> {}", link.href)).cyan());
+            }
+        }
+
+        if let Some((message, _)) = &self.message {
+            spans.push(Span::from(format!(" | {}", message)).bold());
+        }
+        Paragraph::new(Line::from(spans)).style(Style::default().white().bg(Color::DarkGray))
+    }
+}
+
+impl std::default::Default for TuiStatusbar {
+    fn default() -> Self { TuiStatusbar::new() }
+}
+
+/// Connection security of a page, as shown in the status bar.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Security { Secure, Insecure, Local }
+
 /// Represents the associated chrome tab and webpage as a TUI object.
 pub struct TuiTab {
     pub id: String,
     pub title: String,
+    /// Address of the page as reported by the browser.
+    pub url: String,
     pub dom: TuiNode,
-    pub layout: Layout
+    /// Page text flattened into sections that wrap independently.
+    pub blocks: Vec<TuiBlock>,
+    /// Times of the first and latest DOM changes reported since the page was last read.
+    pub dom_changed: Option<(Instant, Instant)>,
+    /// When the page was last read from the browser.
+    pub last_read: Instant,
+    /// Index of the first wrapped line shown in the page view.
+    pub scroll: usize,
+    /// Area of the screen the page was last laid out for.
+    pub viewport: Rect,
+    /// Progress of the most recent page load.
+    pub load: LoadState,
+    /// Requests blocked by the filter lists since the page started loading.
+    pub blocked: usize,
+    /// Reader view was asked for, so the page's main article is shown whenever one can be found.
+    pub reader_wanted: bool,
+    /// Whether the last layout showed an article, for the status bar. Pages without one fall back to the full view.
+    pub reader: bool,
+    /// Landmark regions taken out of the page flow, such as navigation and sidebars.
+    pub panes: Vec<TuiPane>,
+    /// Index of the landmark pane with focus, or None for the main content.
+    pub focus: Option<usize>,
+    /// Images fetched for the page, by their src.
+    pub images: HashMap<String, ImageState>,
+    /// Sources of the images to show expanded rather than as placeholders.
+    pub expanded_images: HashSet<String>,
+    /// Pictures drawn at the last render, for graphics protocols to place.
+    pub placements: Vec<Placement>,
+    /// How the tab loads its pages.
+    pub backend: BackendKind,
+    /// Search for text on the page, highlighted until cleared.
+    pub find: Option<Find>,
 } impl TuiTab {
 
     /// Creates a new tab
-    pub fn new(id: &str, title: &str) -> TuiTab {
+    pub fn new(id: &str, title: &str, backend: BackendKind) -> TuiTab {
         Self {
             id: id.to_string(),
             title: title.to_string(),
+            url: String::new(),
             dom: TuiNode::default(),
-            layout: Layout::default()
+            dom_changed: None,
+            last_read: Instant::now(),
+            blocks: Vec::new(),
+            scroll: 0,
+            viewport: Rect::default(),
+            load: LoadState::Loading(0),
+            blocked: 0,
+            reader_wanted: false,
+            reader: false,
+            panes: Vec::new(),
+            focus: None,
+            images: HashMap::new(),
+            expanded_images: HashSet::new(),
+            placements: Vec::new(),
+            backend,
+            find: None,
         }
     }
 
@@ -201,33 +499,891 @@ pub struct TuiTab {
         Ok(()) // TBD
     }
 
+    /// Brings the tab in line with a fresh snapshot of the same page.
+    /// Only the parts of the tree that changed are replaced, but any change lays out the whole page again,
+    /// since reader view and isolated regions depend on all of it. A snapshot with no changes costs no layout.
+    pub fn patch(&mut self, mut dom: TuiNode, title: &str) {
+        dom.collapse_phantoms();
+        self.title = title.to_owned();
+        if self.dom.patch(dom) { self.update_layout() }
+    }
+
+    /// Rebuilds the page blocks from the DOM and wraps them to the current viewport,
+    /// keeping the reading position on the same content where possible.
     pub fn update_layout(&mut self) {
-        let nav = self.dom.isolate_nav_elements();
-        // TBD Isolate nav elements: Find top nav bar & sidebar
-        // then, in descending order, flatten the tree into a stack of paragraphs
-        let dom = self.dom.flatten();
-        let mut constraints = vec![];
+        let anchor = self.anchor();
+        let mut main = self.dom.clone();
+        let regions = match crate::CONFIG.isolate_regions { true => main.isolate_nav_elements(), false => Vec::new() };
+        let article = match self.reader_wanted { true => Article::extract(&main, &self.title), false => None };
+        // Pages without an article fall back to the full page view, until a refresh finds one again.
+        let reader = article.is_some();
+        self.update_panes(if reader { Vec::new() } else { regions });
+        self.reader = reader;
+        let pictures = self.take_pictures();
+        self.blocks = article.map_or_else(|| main.text_blocks(), |a| a.text_blocks());
+        self.attach_images(pictures);
+        self.rewrap();
+        self.restore_anchor(anchor);
+    }
+
+    /// Adjusts the page to a new viewport area, rewrapping text if the width changed.
+    /// Returns true if the page had to be rewrapped.
+    pub fn resize(&mut self, area: Rect) -> bool {
+        let rewrap = area.width != self.viewport.width;
+        if rewrap {
+            let anchor = self.anchor();
+            self.viewport = area;
+            self.rewrap();
+            self.restore_anchor(anchor);
+        } else {
+            self.viewport = area;
+            self.scroll = self.scroll.min(self.max_scroll());
+        }
+        rewrap
+    }
+
+    /// Gives each block the images of its own that are expanded and ready,
+    /// along with any pictures already built from them, for wrapping to reuse.
+    fn attach_images(&mut self, pictures: Vec<Arc<TuiPicture>>) {
+        for block in self.blocks.iter_mut() {
+            block.expanded = block.images.iter()
+                .filter(|i| self.expanded_images.contains(&i.src))
+                .filter_map(|i| match self.images.get(&i.src) { Some(ImageState::Ready(image)) => Some(image.clone()), _ => None })
+                .collect();
+            block.pictures = pictures.iter().filter(|p| block.expanded.iter().any(|i| Arc::ptr_eq(i, &p.image))).cloned().collect();
+        }
+    }
+
+    /// Takes the pictures built for every block, to hand on to blocks laid out afresh.
+    fn take_pictures(&mut self) -> Vec<Arc<TuiPicture>> {
+        self.blocks.iter_mut().flat_map(|b| std::mem::take(&mut b.pictures)).collect()
+    }
+
+    /// Lays out the page again after images have been expanded, collapsed or fetched.
+    pub fn refresh_images(&mut self) {
+        let anchor = self.anchor();
+        let pictures = self.take_pictures();
+        self.attach_images(pictures);
+        self.rewrap();
+        self.restore_anchor(anchor);
+    }
+
+    /// Sources of every image on the page, in order.
+    pub fn image_sources(&self) -> Vec<String> {
+        let mut sources: Vec<String> = Vec::new();
+        for image in self.blocks.iter().flat_map(|b| b.images.iter()) {
+            if !sources.contains(&image.src) { sources.push(image.src.clone()) }
+        }
+        sources
+    }
+
+    /// Finds the image at a screen position, or else the first image on that line or in its block.
+    pub fn image_at(&self, x: u16, y: u16) -> Option<&TuiImageRef> {
+        let vp = self.viewport;
+        if !within(vp, x, y) { return None }
+        let (block, l) = self.line_at(self.scroll + (y - vp.y) as usize)?;
+        let (start, text) = &block.lines[l];
+        let end = start + text.chars().count();
+        let offset = start + (x - vp.x) as usize;
+        block.images.iter().find(|i| i.start <= offset && offset < i.end)
+            .or_else(|| block.images.iter().find(|i| i.start < end && *start < i.end))
+            .or_else(|| block.images.first())
+    }
+
+    /// Finds the video or audio at a screen position, or else the first on that line or in its block.
+    pub fn media_at(&self, x: u16, y: u16) -> Option<&TuiMedia> {
+        let vp = self.viewport;
+        if !within(vp, x, y) { return None }
+        let (block, l) = self.line_at(self.scroll + (y - vp.y) as usize)?;
+        let (start, text) = &block.lines[l];
+        let end = start + text.chars().count();
+        let offset = start + (x - vp.x) as usize;
+        block.media.iter().find(|m| m.start <= offset && offset < m.end)
+            .or_else(|| block.media.iter().find(|m| m.start < end && *start < m.end))
+            .or_else(|| block.media.first())
+    }
+
+    /// Wraps every block to the width of the viewport.
+    fn rewrap(&mut self) {
+        let width = self.viewport.width;
+        self.blocks.iter_mut().for_each(|b| b.wrap(width));
+        // Tables are laid out afresh, so matches in them move.
+        self.refresh_find();
+    }
+
+    /// Starts a search for text on the page, or clears it if None, and scrolls to the first match.
+    pub fn set_find(&mut self, find: Option<Find>) {
+        self.find = find;
+        if self.find.is_none() {
+            let panes = self.panes.iter_mut().flat_map(|p| p.blocks.iter_mut());
+            self.blocks.iter_mut().chain(panes).for_each(|b| b.highlights.clear());
+        }
+        self.refresh_find();
+        self.scroll_to_match();
+    }
+
+    /// Searches the page and its landmark panes again after their text changes.
+    fn refresh_find(&mut self) {
+        let Some(find) = &mut self.find else { return };
+        let panes: Vec<&[TuiBlock]> = self.panes.iter().map(|p| p.blocks.as_slice()).collect();
+        find.search(&self.blocks, &panes);
+        self.highlight_find();
+    }
+
+    fn highlight_find(&mut self) {
+        let Some(find) = &self.find else { return };
+        let mut panes: Vec<&mut [TuiBlock]> = self.panes.iter_mut().map(|p| p.blocks.as_mut_slice()).collect();
+        find.highlight(&mut self.blocks, &mut panes);
+    }
+
+    /// Moves to the next (or previous) match of the search, scrolling it into view.
+    pub fn step_find(&mut self, step: isize) -> bool {
+        let Some(find) = &mut self.find else { return false };
+        find.step(step);
+        self.highlight_find();
+        self.scroll_to_match();
+        true
+    }
+
+    /// Scrolls the focused match of the search to the middle of the view, if it is out of view.
+    /// Matches in a landmark pane expand and focus it, and scroll it instead.
+    fn scroll_to_match(&mut self) {
+        let Some(found) = self.find.as_ref().and_then(|f| f.focused()).copied() else { return };
+        let Some(pane) = found.pane else {
+            self.focus = None;
+            let line = line_of(&self.blocks, found.block, found.start);
+            let height = self.viewport.height as usize;
+            if line < self.scroll || line >= self.scroll + height {
+                self.scroll = line.saturating_sub(height / 2).min(self.max_scroll());
+            }
+            return
+        };
+        let Some(pane_view) = self.panes.get_mut(pane) else { return };
+        self.focus = Some(pane);
+        pane_view.collapsed = false;
+        // Panes that haven't been drawn open yet are scrolled into range when they are.
+        let line = line_of(&pane_view.blocks, found.block, found.start);
+        let height = pane_view.content_area().height as usize;
+        if line < pane_view.scroll || line >= pane_view.scroll + height {
+            pane_view.scroll = line.saturating_sub(height / 2);
+        }
+    }
+
+    /// Finds the block and char offset of the first visible line.
+    fn anchor(&self) -> Option<(usize, usize)> {
+        let mut line = 0;
+        for (b, block) in self.blocks.iter().enumerate() {
+            if self.scroll < line + block.height() {
+                return Some((b, block.lines[self.scroll - line].0))
+            }
+            line += block.height();
+        }
+        None
+    }
+
+    /// Scrolls to the wrapped line containing the anchored content.
+    fn restore_anchor(&mut self, anchor: Option<(usize, usize)>) {
+        self.scroll = match anchor {
+            Some((b, offset)) if b < self.blocks.len() => {
+                let block_start: usize = self.blocks[..b].iter().map(|b| b.height()).sum();
+                let within = self.blocks[b].lines.iter().rposition(|(o, _)| *o <= offset).unwrap_or(0);
+                block_start + within
+            },
+            _ => 0,
+        };
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// Switches between the reader view and the full page.
+    /// Returns false if reader view was asked for but the page has no article.
+    pub fn toggle_reader(&mut self) -> bool {
+        self.reader_wanted = !self.reader_wanted;
+        self.update_layout();
+        self.scroll = 0;
+        if self.reader_wanted && !self.reader {
+            // Nothing changed on screen, so the next toggle asks for reader view again.
+            self.reader_wanted = false;
+            return false
+        }
+        true
+    }
+
+    /// Total number of wrapped lines on the page.
+    pub fn line_count(&self) -> usize { self.blocks.iter().map(|b| b.height()).sum() }
+
+    /// Furthest the page can be scrolled while still filling the viewport.
+    pub fn max_scroll(&self) -> usize { self.line_count().saturating_sub(self.viewport.height as usize) }
+
+    /// Scrolls the page by a number of lines, within the page extents.
+    pub fn scroll_by(&mut self, lines: isize) {
+        let scroll = self.scroll as isize + lines;
+        self.scroll = (scroll.max(0) as usize).min(self.max_scroll());
+    }
+
+    /// Scrolls to the top (false) or bottom (true) of the page.
+    pub fn scroll_to_end(&mut self, bottom: bool) {
+        self.scroll = if bottom { self.max_scroll() } else { 0 }
+    }
+
+    /// How far down the page the view is scrolled, as a percentage.
+    pub fn scroll_percent(&self) -> usize {
+        match self.max_scroll() {
+            0 => 100,
+            max => self.scroll * 100 / max,
+        }
+    }
+
+    /// Security of the connection, judged by the page's URL scheme.
+    pub fn security(&self) -> Security {
+        match self.url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("https") | Some("wss") => Security::Secure,
+            Some("http") | Some("ws") => Security::Insecure,
+            _ => Security::Local,
+        }
+    }
+
+    /// Finds the block and line index for a line of the page.
+    fn line_at(&self, line: usize) -> Option<(&TuiBlock, usize)> { line_in(&self.blocks, line) }
+
+    /// Finds the block displayed at a screen position.
+    pub fn block_at(&self, x: u16, y: u16) -> Option<&TuiBlock> {
+        let vp = self.viewport;
+        if !within(vp, x, y) { return None }
+        self.line_at(self.scroll + (y - vp.y) as usize).map(|(block, _)| block)
+    }
+
+    /// Finds the table displayed at a screen position.
+    pub fn table_at(&self, x: u16, y: u16) -> Option<&TuiTable> {
+        self.block_at(x, y).and_then(|b| b.table.as_deref())
+    }
+
+    /// Scrolls the table at a screen position across by a number of columns.
+    /// Returns false if there is no table there.
+    pub fn scroll_table_at(&mut self, x: u16, y: u16, columns: isize) -> bool {
+        if !within(self.viewport, x, y) { return false }
+        let mut line = self.scroll + (y - self.viewport.y) as usize;
+        let Some(block) = self.blocks.iter_mut().find(|b| {
+            let found = line < b.height();
+            if !found { line -= b.height() }
+            found
+        }) else { return false };
+        let Some(table) = &mut block.table else { return false };
+        table.hscroll = (table.hscroll as isize + columns).max(0) as usize;
+        block.wrap(self.viewport.width);
+        self.refresh_find();
+        true
+    }
+
+    /// Finds the link at a screen position, or else the first link on that line of the page.
+    /// Links in expanded landmark panes are found as well.
+    pub fn link_at(&self, x: u16, y: u16) -> Option<&TuiLink> {
+        match self.panes.iter().find(|p| within(p.content_area(), x, y)) {
+            Some(pane) => link_in(&pane.blocks, pane.scroll, pane.content_area(), x, y),
+            None => link_in(&self.blocks, self.scroll, self.viewport, x, y),
+        }
+    }
+
+    /// Draws the landmark panes around the page, fitting the page to the area that remains.
+    pub fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let (main, pane_areas) = self.pane_layout(area);
+        for (p, pane_area) in pane_areas {
+            let focused = self.focus == Some(p);
+            self.panes[p].render(frame, pane_area, focused);
+        }
+        self.resize(main);
+        self.render_page(frame, main);
+    }
+
+    /// Splits the page area between landmark panes and the main content.
+    /// Banners, navigation and collapsed panes sit above the content, footers below it,
+    /// and expanded sidebars to its right when there is room.
+    fn pane_layout(&mut self, area: Rect) -> (Rect, Vec<(usize, Rect)>) {
+        let sidebar_width = (area.width / 3).min(40);
+        let side_by_side = area.width >= 60;
+        let is_sidebar = |p: &TuiPane| side_by_side && !p.collapsed && p.landmark == Landmark::Complementary;
+        let (mut top, mut bottom, mut side) = (Vec::new(), Vec::new(), Vec::new());
+        for (p, pane) in self.panes.iter().enumerate() {
+            if is_sidebar(pane) { side.push(p) }
+            else if pane.landmark == Landmark::ContentInfo { bottom.push(p) }
+            else { top.push(p) }
+        }
+
+        let max_height = (area.height / 4).max(1) as usize;
+        let mut constraints = Vec::new();
+        for &p in top.iter().chain(bottom.iter()) {
+            let pane = &mut self.panes[p];
+            pane.fit(area.width);
+            let height = match pane.collapsed { true => 1, false => 1 + pane.line_count().min(max_height) };
+            constraints.push(Constraint::Length(height as u16));
+        }
+        constraints.insert(top.len(), Constraint::Min(1));
+        let rows = Layout::default().direction(Direction::Vertical).constraints(constraints).split(area);
+
+        let mut main = rows[top.len()];
+        let mut pane_areas: Vec<(usize, Rect)> = top.iter().copied().zip(rows.iter().copied()).collect();
+        pane_areas.extend(bottom.iter().copied().zip(rows[top.len() + 1..].iter().copied()));
+        if !side.is_empty() {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(sidebar_width)])
+                .split(main);
+            main = columns[0];
+            let stacked = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Ratio(1, side.len() as u32); side.len()])
+                .split(columns[1]);
+            for (&p, &pane_area) in side.iter().zip(stacked.iter()) {
+                self.panes[p].fit(pane_area.width);
+                pane_areas.push((p, pane_area));
+            }
+        }
+        (main, pane_areas)
+    }
+
+    /// Draws the visible page text. Pictures that fit on screen are left blank for a graphics protocol
+    /// to draw over, while those partly scrolled out of view are drawn in half blocks.
+    pub fn render_page(&mut self, frame: &mut Frame, area: Rect) {
+        let mut lines = visible_lines(&self.blocks, self.scroll, area.height);
+        self.placements.clear();
+        if crate::graphics::current_protocol().is_some_and(|p| p != GraphicsProtocol::HalfBlock) {
+            let mut block_start = 0;
+            for block in self.blocks.iter() {
+                let mut line = block_start + block.picture_start;
+                for picture in block.pictures.iter() {
+                    let rows = picture.rows as usize;
+                    if line >= self.scroll && line + rows <= self.scroll + lines.len() {
+                        let y = line - self.scroll;
+                        lines[y..y + rows].iter_mut().for_each(|l| *l = Line::default());
+                        let area = Rect::new(area.x, area.y + y as u16, picture.cols, picture.rows);
+                        self.placements.push(Placement { area, picture: picture.clone() });
+                    }
+                    line += rows;
+                }
+                block_start += block.height();
+            }
+        }
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    /// Moves focus to the next (or previous) landmark pane, cycling back through the main content.
+    /// Returns the screen position of the newly focused region.
+    pub fn focus_landmark(&mut self, step: isize) -> (u16, u16) {
+        let slots = self.panes.len() as isize + 1;
+        let current = self.focus.map_or(0, |p| p as isize + 1);
+        self.focus = match (current + step).rem_euclid(slots) {
+            0 => None,
+            slot => Some(slot as usize - 1),
+        };
+        match self.focused_pane() {
+            Some(pane) => (pane.area.x, pane.area.y),
+            None => (self.viewport.x, self.viewport.y),
+        }
+    }
 
-        for _ in dom.iter() {
-            constraints.push(Constraint::Min(1))
+    pub fn focused_pane(&self) -> Option<&TuiPane> { self.panes.get(self.focus?) }
+
+    pub fn focused_pane_mut(&mut self) -> Option<&mut TuiPane> { self.panes.get_mut(self.focus?) }
+
+    /// Replaces the landmark panes, keeping the state of those that are still on the page.
+    fn update_panes(&mut self, regions: Vec<TuiNode>) {
+        let old = std::mem::take(&mut self.panes);
+        self.panes = regions.iter().filter_map(TuiPane::new).map(|mut pane| {
+            if let Some(o) = old.iter().find(|o| o.landmark == pane.landmark && o.label == pane.label) {
+                pane.collapsed = o.collapsed;
+                pane.scroll = o.scroll;
+            }
+            pane
+        }).collect();
+        if self.focus.is_some_and(|p| p >= self.panes.len()) { self.focus = None }
+    }
+
+}
+
+/// Tracks whether a tab's page has finished arriving from the browser.
+#[derive(Clone, PartialEq)]
+pub enum LoadState {
+    /// Navigation is underway, with approximate progress as a percentage.
+    Loading(u8),
+    Loaded,
+    /// The load failed with the given error message.
+    Failed(String),
+}
+
+/// A landmark region of the page, shown in its own collapsible pane.
+pub struct TuiPane {
+    pub landmark: Landmark,
+    /// Name of the region, from its aria-label or else its landmark role.
+    pub label: String,
+    /// Link text, or else the opening text, shown while the pane is collapsed.
+    pub summary: String,
+    pub blocks: Vec<TuiBlock>,
+    pub collapsed: bool,
+    pub scroll: usize,
+    /// Area the pane was last drawn in, including its title line.
+    pub area: Rect,
+    /// Width the blocks are wrapped to.
+    width: u16,
+} impl TuiPane {
+
+    /// Makes a collapsed pane for a landmark region, or None if it has no text.
+    pub fn new(node: &TuiNode) -> Option<TuiPane> {
+        let TuiNodeData::Element(elem) = &node.data else { return None };
+        let landmark = Landmark::of(elem, false)?;
+        let blocks = node.text_blocks();
+        if blocks.is_empty() { return None }
+
+        let links: Vec<String> = blocks.iter()
+            .flat_map(|b| b.links.iter().map(|k| b.text.chars().skip(k.start).take(k.end - k.start).collect::<String>()))
+            .collect();
+        let summary = match links.is_empty() {
+            true => blocks[0].text.clone(),
+            false => links.join(" · "),
+        };
+        let label = elem.attr("aria-label").map_or_else(|| landmark.name().to_owned(), str::to_owned);
+        Some(TuiPane { landmark, label, summary, blocks, collapsed: true, scroll: 0, area: Rect::default(), width: 0 })
+    }
+
+    /// Rewraps the text if the pane has changed width.
+    pub fn fit(&mut self, width: u16) {
+        if width == self.width { return }
+        self.width = width;
+        self.blocks.iter_mut().for_each(|b| b.wrap(width));
+    }
+
+    pub fn line_count(&self) -> usize { self.blocks.iter().map(|b| b.height()).sum() }
+
+    /// Area below the title line where the text is drawn, which is empty while collapsed.
+    pub fn content_area(&self) -> Rect {
+        match self.collapsed {
+            true => Rect::default(),
+            false => Rect { y: self.area.y + 1, height: self.area.height.saturating_sub(1), ..self.area },
+        }
+    }
+
+    /// Scrolls the pane text by a number of lines, within its extents.
+    pub fn scroll_by(&mut self, lines: isize) {
+        let max = self.line_count().saturating_sub(self.content_area().height as usize);
+        self.scroll = ((self.scroll as isize + lines).max(0) as usize).min(max);
+    }
+
+    /// Draws the title line, and the text below it unless the pane is collapsed.
+    fn render(&mut self, frame: &mut Frame, area: Rect, focused: bool) {
+        self.area = area;
+        let title = match self.collapsed {
+            true => format!("▸ {}: {}", self.label, self.summary),
+            false => format!("▾ {}", self.label),
+        };
+        let style = match focused {
+            true => Style::default().black().on_cyan(),
+            false => Style::default().white().on_dark_gray(),
+        };
+        frame.render_widget(Paragraph::new(title).style(style), Rect { height: 1, ..area });
+
+        let content = self.content_area();
+        if content.height == 0 { return }
+        self.scroll = self.scroll.min(self.line_count().saturating_sub(content.height as usize));
+        frame.render_widget(Paragraph::new(visible_lines(&self.blocks, self.scroll, content.height)), content);
+    }
+}
+
+/// Landmark roles of page regions that are shown apart from the main content.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Landmark { Banner, Navigation, Search, Complementary, ContentInfo }
+impl Landmark {
+
+    /// Finds the landmark role of an element, from its role attribute or its tag.
+    /// Headers and footers only count as landmarks outside of sectioning content.
+    pub fn of(elem: &TuiElement, sectioned: bool) -> Option<Landmark> {
+        use Landmark::*;
+        match elem.attr("role").unwrap_or_default() {
+            "banner" => return Some(Banner),
+            "navigation" => return Some(Navigation),
+            "search" => return Some(Search),
+            "complementary" => return Some(Complementary),
+            "contentinfo" => return Some(ContentInfo),
+            _ => (),
+        }
+        match elem.name() {
+            "nav" => Some(Navigation),
+            "aside" => Some(Complementary),
+            "search" => Some(Search),
+            "header" if !sectioned => Some(Banner),
+            "footer" if !sectioned => Some(ContentInfo),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Landmark::Banner => "Header",
+            Landmark::Navigation => "Navigation",
+            Landmark::Search => "Search",
+            Landmark::Complementary => "Sidebar",
+            Landmark::ContentInfo => "Footer",
+        }
+    }
+}
+
+/// Finds the block and line index for a line of wrapped blocks.
+fn line_in(blocks: &[TuiBlock], line: usize) -> Option<(&TuiBlock, usize)> {
+    let mut start = 0;
+    for block in blocks.iter() {
+        if line < start + block.height() { return Some((block, line - start)) }
+        start += block.height();
+    }
+    None
+}
+
+/// Styles the wrapped lines of blocks that fit in a view scrolled to a line.
+fn visible_lines(blocks: &[TuiBlock], scroll: usize, height: u16) -> Vec<Line<'static>> {
+    blocks.iter()
+        .flat_map(|b| (0..b.height()).map(|l| b.render_line(l)))
+        .skip(scroll)
+        .take(height as usize)
+        .collect()
+}
+
+/// Line a char offset of a block is wrapped onto, counting down from the first of the blocks.
+fn line_of(blocks: &[TuiBlock], block: usize, offset: usize) -> usize {
+    let block_start: usize = blocks.iter().take(block).map(|b| b.height()).sum();
+    let within = blocks.get(block).and_then(|b| b.lines.iter().rposition(|(o, _)| *o <= offset)).unwrap_or(0);
+    block_start + within
+}
+
+fn within(area: Rect, x: u16, y: u16) -> bool {
+    x >= area.x && y >= area.y && x < area.right() && y < area.bottom()
+}
+
+/// Finds the link at a screen position of blocks drawn in an area,
+/// or else the first link on that line.
+fn link_in(blocks: &[TuiBlock], scroll: usize, area: Rect, x: u16, y: u16) -> Option<&TuiLink> {
+    if !within(area, x, y) { return None }
+
+    let (block, l) = line_in(blocks, scroll + (y - area.y) as usize)?;
+    let (start, text) = &block.lines[l];
+    let end = start + text.chars().count();
+    let offset = start + (x - area.x) as usize;
+    block.links.iter().find(|k| k.start <= offset && offset < k.end)
+        .or_else(|| block.links.iter().find(|k| k.start < end && *start < k.end))
+}
+
+/// A section of page text that is wrapped as a single unit.
+#[derive(Clone, Default)]
+pub struct TuiBlock {
+    pub text: String,
+    /// Wrapped lines, each with the char offset in `text` where it begins.
+    pub lines: Vec<(usize, String)>,
+    /// Hyperlinks within the text.
+    pub links: Vec<TuiLink>,
+    /// Ranges of chars in `text` and the style they are drawn with.
+    pub styles: Vec<(usize, usize, Style)>,
+    /// Keeps the line breaks and spacing of the text instead of rewrapping it.
+    pub preformatted: bool,
+    /// CSS selectors for the block elements containing the text, innermost first.
+    pub selectors: Vec<String>,
+    /// Table laid out in place of wrapped text.
+    pub table: Option<Box<TuiTable>>,
+    /// Images within the text, shown as placeholders.
+    pub images: Vec<TuiImageRef>,
+    /// Video and audio within the text, shown as placeholders.
+    pub media: Vec<TuiMedia>,
+    /// Images to draw below the text.
+    pub expanded: Vec<Arc<image::DynamicImage>>,
+    /// Expanded images sized to the wrap width.
+    pub pictures: Vec<Arc<TuiPicture>>,
+    /// Index of the first line taken up by pictures.
+    pub picture_start: usize,
+    /// Ranges of chars drawn over with another style, like matches of a search.
+    pub highlights: Vec<(usize, usize, Style)>,
+} impl TuiBlock {
+
+    pub fn new(text: String) -> Self { TuiBlock { text, ..Default::default() }}
+
+    /// Rewraps the text to fit within the given width.
+    pub fn wrap(&mut self, width: u16) {
+        if self.table.is_some() { return self.wrap_table(width) }
+        self.lines = match self.preformatted {
+            true => crate::utils::wrap_preformatted(&self.text, width as usize),
+            false => crate::utils::wrap_text(&self.text, width as usize),
+        };
+        let end = self.text.chars().count();
+
+        // Pictures take up blank lines below the text. Scaling is slow, so they are only rebuilt for a new width.
+        self.picture_start = self.lines.len();
+        let built = std::mem::take(&mut self.pictures);
+        self.pictures = self.expanded.iter()
+            .map(|image| match built.iter().find(|p| Arc::ptr_eq(&p.image, image) && p.max_cols == width) {
+                Some(picture) => picture.clone(),
+                None => Arc::new(TuiPicture::new(image.clone(), width, crate::CONFIG.image_rows)),
+            })
+            .collect();
+        let picture_rows: usize = self.pictures.iter().map(|p| p.rows as usize).sum();
+        self.lines.extend(std::iter::repeat_n((end, String::new()), picture_rows));
+
+        // Blank line separates blocks.
+        self.lines.push((end, String::new()));
+    }
+
+    /// Lays out a table to the width, with its text rebuilt from the laid out lines
+    /// and each line scrolled across to the table's horizontal scroll.
+    fn wrap_table(&mut self, width: u16) {
+        let Some(table) = &mut self.table else { return };
+        let width = width as usize;
+        table.hscroll = table.hscroll.min(table.layout_width(width).saturating_sub(width));
+
+        let (mut text, mut lines, mut styles) = (String::new(), Vec::new(), Vec::new());
+        let mut offset = 0;
+        for (line, header) in table.lines(width) {
+            let len = line.chars().count();
+            if header { styles.push((offset, offset + len, Style::default().bold())) }
+            lines.push((offset + table.hscroll.min(len), line.chars().skip(table.hscroll).take(width).collect()));
+            text.push_str(&line);
+            text.push('\n');
+            offset += len + 1;
+        }
+        lines.push((offset, String::new()));
+        (self.text, self.lines, self.styles) = (text, lines, styles);
+    }
+
+    /// Number of lines the block takes up when rendered.
+    pub fn height(&self) -> usize { self.lines.len() }
+
+    /// Gets the style of the char at an offset in the text, with links marked out.
+    fn style_at(&self, offset: usize) -> Style {
+        let mut style = self.styles.iter()
+            .find(|(start, end, _)| *start <= offset && offset < *end)
+            .map_or(Style::default(), |(_, _, style)| *style);
+        if self.links.iter().any(|k| k.start <= offset && offset < k.end) {
+            style = style.cyan().underlined()
+        }
+        if let Some((_, _, highlight)) = self.highlights.iter().find(|(start, end, _)| *start <= offset && offset < *end) {
+            style = style.patch(*highlight)
+        }
+        style
+    }
+
+    /// Finds the picture, and its row, drawn on a line of the block.
+    fn picture_row(&self, index: usize) -> Option<(usize, usize)> {
+        let mut row = index.checked_sub(self.picture_start)?;
+        for (p, picture) in self.pictures.iter().enumerate() {
+            if row < picture.rows as usize { return Some((p, row)) }
+            row -= picture.rows as usize;
+        }
+        None
+    }
+
+    /// Styles a wrapped line for display.
+    pub fn render_line(&self, index: usize) -> Line<'static> {
+        if let Some((picture, row)) = self.picture_row(index) { return self.pictures[picture].lines[row].clone() }
+        let (start, text) = &self.lines[index];
+        let mut spans: Vec<Span> = Vec::new();
+        let mut run = String::new();
+        let mut run_style = None;
+        for (c, ch) in text.chars().enumerate() {
+            let style = self.style_at(start + c);
+            if run_style.is_some_and(|s| s != style) {
+                spans.push(Span::styled(std::mem::take(&mut run), run_style.unwrap_or_default()))
+            }
+            run_style = Some(style);
+            run.push(ch);
+        }
+        if !run.is_empty() { spans.push(Span::styled(run, run_style.unwrap_or_default())) }
+        Line::from(spans)
+    }
+}
+
+/// A hyperlink covering a range of chars in a block's text.
+#[derive(Clone)]
+pub struct TuiLink {
+    pub start: usize,
+    pub end: usize,
+    pub href: String,
+}
+
+/// An image within a block's text, covering the chars of its placeholder.
+#[derive(Clone)]
+pub struct TuiImageRef {
+    pub start: usize,
+    pub end: usize,
+    /// Source as written in the page, which may be relative.
+    pub src: String,
+}
+
+/// Progress of fetching an image from the browser.
+#[derive(Clone)]
+pub enum ImageState {
+    Loading,
+    Ready(Arc<image::DynamicImage>),
+    Failed,
+}
+
+/// Accumulates inline content into a block, collapsing whitespace like HTML does.
+#[derive(Default)]
+struct BlockBuilder {
+    text: String,
+    /// Length of the text in chars.
+    len: usize,
+    /// Whitespace is pending between the text and whatever comes next.
+    space: bool,
+    links: Vec<TuiLink>,
+    images: Vec<TuiImageRef>,
+    media: Vec<TuiMedia>,
+    styles: Vec<(usize, usize, Style)>,
+    preformatted: bool,
+    /// Selectors of the block elements being collected, outermost first.
+    path: Vec<String>,
+} impl BlockBuilder {
+
+    fn push_text(&mut self, text: &str, style: Style) {
+        if self.preformatted { return self.push_verbatim(text, style) }
+        if text.starts_with(char::is_whitespace) { self.space = true }
+        for word in text.split_whitespace() {
+            if self.space && self.len > 0 { self.text.push(' '); self.len += 1 }
+            self.push_styled(word, style);
+            self.space = true;
+        }
+        self.space = text.ends_with(char::is_whitespace) || (self.space && text.trim().is_empty());
+    }
+
+    /// Appends text exactly as given, for preformatted blocks.
+    fn push_verbatim(&mut self, text: &str, style: Style) {
+        self.push_styled(&text.replace('\t', "    "), style)
+    }
+
+    /// Appends text, extending the last style run if the style hasn't changed.
+    fn push_styled(&mut self, text: &str, style: Style) {
+        let start = self.len;
+        self.text.push_str(text);
+        self.len += text.chars().count();
+        if style == Style::default() { return }
+        match self.styles.last_mut() {
+            Some((_, end, last)) if *last == style && start <= *end + 1 => *end = self.len,
+            _ => self.styles.push((start, self.len, style)),
         }
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(constraints);
+    }
+
+    /// Appends a placeholder for an image, labelled with its alt text.
+    fn push_image(&mut self, label: &str, src: &str, style: Style) {
+        let start = self.next_offset();
+        self.push_text(&format!("[image: {}]", label), style.magenta());
+        self.images.push(TuiImageRef { start, end: self.len, src: src.to_owned() });
+    }
+
+    /// Appends a placeholder for a video or audio element.
+    fn push_media(&mut self, mut media: TuiMedia, style: Style) {
+        media.start = self.next_offset();
+        self.push_text(&media.label(), style.magenta());
+        media.end = self.len;
+        self.media.push(media);
+    }
+
+    /// Char offset at which the next text will start.
+    fn next_offset(&self) -> usize {
+        if self.space && self.len > 0 && !self.preformatted { self.len + 1 } else { self.len }
+    }
 
-        self.layout = layout;
+    fn finish(&mut self) -> Option<TuiBlock> {
+        let (preformatted, path) = (self.preformatted, self.path.clone());
+        let builder = std::mem::replace(self, BlockBuilder { preformatted, path, ..Default::default() });
+        let text = match preformatted {
+            true => builder.text.trim_end().to_owned(),
+            false => builder.text,
+        };
+        if text.trim().is_empty() { return None }
+        let selectors = builder.path.into_iter().rev().collect();
+        Some(TuiBlock { links: builder.links, images: builder.images, media: builder.media, styles: builder.styles, preformatted, selectors, ..TuiBlock::new(text) })
+    }
+}
+
+/// Presentation of a node, simplified from the CSS that chrome computed for it.
+#[derive(Clone, Default, PartialEq)]
+pub struct TuiStyle {
+    /// Whether the node starts its own block, if known.
+    pub flow: Option<TextFlow>,
+    pub hidden: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// Keeps line breaks and spacing, like <pre>.
+    pub preformatted: bool,
+    pub fg: Option<(u8, u8, u8)>,
+    pub bg: Option<(u8, u8, u8)>,
+} impl TuiStyle {
+
+    /// Reads the style recorded by chrome in the order of `browser::COMPUTED_STYLE_PROPERTIES`.
+    pub fn from_computed(computed: &str) -> TuiStyle {
+        let values: Vec<&str> = computed.split('|').collect();
+        let value = |i: usize| values.get(i).copied().unwrap_or_default();
+        let weight = value(2).parse::<u16>().unwrap_or(if value(2) == "bold" { 700 } else { 400 });
+        TuiStyle {
+            flow: match value(0) {
+                "" => None,
+                display if display.starts_with("inline") || display == "contents" => Some(TextFlow::Inline),
+                _ => Some(TextFlow::Block),
+            },
+            hidden: value(0) == "none" || value(1) == "hidden" || value(1) == "collapse",
+            bold: weight >= 600,
+            italic: value(3) == "italic" || value(3) == "oblique",
+            underline: value(6).contains("underline"),
+            strikethrough: value(6).contains("line-through"),
+            preformatted: value(7).starts_with("pre") || value(7) == "break-spaces",
+            fg: crate::utils::parse_css_color(value(4)),
+            bg: crate::utils::parse_css_color(value(5)),
+        }
     }
 
-    pub fn render_page(&self, frame: &mut Frame) {
-        // TBD dont use whole page, save room for navbars & menus
-        let layout = self.layout.split(frame.size());
-        for (n, node) in self.dom.flatten().iter().enumerate() {
-            frame.render_widget(node.render_node(), layout[n]);
+    /// Approximates the style of an element from its tag, when chrome hasn't computed one.
+    pub fn inherit_tag(&self, tag: &str) -> TuiStyle {
+        let mut style = TuiStyle { flow: None, ..self.clone() };
+        match tag {
+            "b" | "strong" | "th" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => style.bold = true,
+            "i" | "em" | "cite" | "var" => style.italic = true,
+            "u" | "ins" => style.underline = true,
+            "s" | "del" | "strike" => style.strikethrough = true,
+            "pre" | "textarea" => style.preformatted = true,
+            _ => (),
         }
+        style
+    }
 
+    /// Converts to a terminal style, according to the configured colour support.
+    /// Gray text on the page's default background is left in the terminal's own colours,
+    /// since dark page text is unreadable on dark terminals and vice versa.
+    pub fn to_style(&self) -> Style {
+        let mut style = Style::default();
+        if self.bold { style = style.bold() }
+        if self.italic { style = style.italic() }
+        if self.underline { style = style.underlined() }
+        if self.strikethrough { style = style.crossed_out() }
+
+        let grayscale = |(r, g, b): (u8, u8, u8)| r.max(g).max(b) - r.min(g).min(b) < 40;
+        let bg = self.bg.filter(|&c| !grayscale(c));
+        let fg = self.fg.filter(|&c| bg.is_some() || !grayscale(c));
+        if let Some(color) = fg.and_then(terminal_color) { style = style.fg(color) }
+        if let Some(color) = bg.and_then(terminal_color) { style = style.bg(color) }
+        style
     }
+}
 
+/// Whether content breaks onto its own block or flows inline with its siblings.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextFlow { Block, Inline }
+
+/// Picks the closest colour the terminal can show.
+pub(crate) fn terminal_color(rgb: (u8, u8, u8)) -> Option<Color> {
+    use crate::config::ColorMode::*;
+    match crate::CONFIG.color_mode {
+        Auto if truecolor_supported() => Some(Color::Rgb(rgb.0, rgb.1, rgb.2)),
+        Auto | Indexed => Some(Color::Indexed(crate::utils::nearest_indexed_color(rgb))),
+        TrueColor => Some(Color::Rgb(rgb.0, rgb.1, rgb.2)),
+        Monochrome => None,
+    }
+}
 
+/// Checks whether the terminal advertises 24-bit colour.
+fn truecolor_supported() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|c| c == "truecolor" || c == "24bit")
 }
 
 
@@ -243,9 +1399,47 @@ pub struct TuiTab {
 #[derive(Clone)]
 pub struct TuiNode {
     pub data: TuiNodeData,
-    pub children: Vec<TuiNode>
+    pub children: Vec<TuiNode>,
+    /// Style computed by chrome, if it was requested.
+    pub style: Option<TuiStyle>,
 } impl TuiNode {
 
+    /// Replaces the parts of this tree that differ from an updated copy of it.
+    /// Children that match at the start and end of a list are patched in place,
+    /// so appended or removed content doesn't replace its siblings.
+    /// Returns true if anything changed.
+    pub fn patch(&mut self, new: TuiNode) -> bool {
+        if !self.data.same_as(&new.data) || self.style != new.style {
+            *self = new;
+            return true
+        }
+
+        let mut new_children = new.children;
+        let shallow_match = |a: &TuiNode, b: &TuiNode| a.data.same_as(&b.data) && a.style == b.style;
+        let prefix = self.children.iter().zip(new_children.iter())
+            .take_while(|(a, b)| shallow_match(a, b))
+            .count();
+        let suffix = self.children[prefix..].iter().rev().zip(new_children[prefix..].iter().rev())
+            .take_while(|(a, b)| shallow_match(a, b))
+            .count();
+
+        let mut changed = false;
+        if self.children.len() != new_children.len() || prefix + suffix < self.children.len() {
+            let middle = new_children.drain(prefix..new_children.len() - suffix).collect::<Vec<_>>();
+            let end = self.children.len() - suffix;
+            self.children.splice(prefix..end, middle);
+            changed = true;
+        }
+
+        let kept = (0..prefix).chain(self.children.len() - suffix..self.children.len());
+        let updates = (0..prefix).chain(new_children.len() - suffix..new_children.len());
+        let mut new_children: Vec<Option<TuiNode>> = new_children.into_iter().map(Some).collect();
+        for (old, new) in kept.zip(updates) {
+            if let Some(new) = new_children[new].take() { changed |= self.children[old].patch(new) }
+        }
+        changed
+    }
+
     pub fn collapse_phantoms(&mut self) {
         let phantoms: Vec<_> = self.children.iter_mut().enumerate()
             .filter_map(|(c, child)| {
@@ -270,14 +1464,30 @@ pub struct TuiNode {
         }
     }
 
+    /// Removes landmark regions like navigation, banners, sidebars and footers from the tree,
+    /// returning them in document order.
     pub fn isolate_nav_elements(&mut self) -> Vec<TuiNode> {
-        let nav_elements = vec![];
-        // TODO
-        // for each in children, if it is a nav element
-        // remove that child branch and push to nav_elems
+        let mut nav_elements = vec![];
+        self.isolate_landmarks(false, &mut nav_elements);
         nav_elements
     }
 
+    fn isolate_landmarks(&mut self, sectioned: bool, found: &mut Vec<TuiNode>) {
+        let mut c = 0;
+        while c < self.children.len() {
+            let child = &mut self.children[c];
+            let TuiNodeData::Element(elem) = &child.data else { c += 1; continue };
+            if Landmark::of(elem, sectioned).is_some() {
+                found.push(self.children.remove(c));
+                continue
+            }
+            // Headers and footers within sectioning content belong to that section, not the page.
+            let sectioned = sectioned || matches!(elem.name(), "article" | "aside" | "main" | "nav" | "section");
+            child.isolate_landmarks(sectioned, found);
+            c += 1;
+        }
+    }
+
     pub fn flatten(&self) -> Vec<&TuiNode> {
         let mut flattened = vec![];
         flattened.push(self);
@@ -287,22 +1497,85 @@ pub struct TuiNode {
         flattened
     }
 
-    pub fn render_node(&self) -> impl Widget {
+    /// Flattens the tree into blocks of text, breaking at block-level elements.
+    pub fn text_blocks(&self) -> Vec<TuiBlock> {
+        let mut blocks = Vec::new();
+        let mut current = BlockBuilder::default();
+        self.collect_text(&mut blocks, &mut current, &TuiStyle::default());
+        blocks.extend(current.finish());
+        blocks
+    }
+
+    fn collect_text(&self, blocks: &mut Vec<TuiBlock>, current: &mut BlockBuilder, inherited: &TuiStyle) {
         use TuiNodeData::*;
-        match &self.data {
-            Element(elem) => {/* TBD */ todo!{"Render elements!"}},
-            Text(text) => {
-                Paragraph::new(text.clone()).block(Block::default().borders(Borders::NONE))
+        let elem = match &self.data {
+            Text(text) => return current.push_text(text, inherited.to_style()),
+            Comment(_) => return,
+            Element(elem) => elem,
+            Phantom => return self.children.iter().for_each(|c| c.collect_text(blocks, current, inherited)),
+        };
+
+        let style = self.style.clone().unwrap_or_else(|| inherited.inherit_tag(elem.name()));
+        if style.hidden { return }
+        let children = |blocks: &mut Vec<TuiBlock>, current: &mut BlockBuilder| {
+            self.children.iter().for_each(|c| c.collect_text(blocks, current, &style))
+        };
+
+        let is_block = style.flow.map_or(elem.is_block(), |flow| flow == TextFlow::Block);
+        let table = match elem.name() == "table" && !current.preformatted {
+            true => TuiTable::from_node(self),
+            false => None,
+        };
+        match elem.name() {
+            // Data tables are laid out as a grid, while layout tables flow like other blocks.
+            _ if table.is_some() => {
+                blocks.extend(current.finish());
+                let selectors = std::iter::once(elem.selector()).chain(current.path.iter().rev().cloned()).collect();
+                blocks.push(TuiBlock { table: table.map(Box::new), selectors, ..Default::default() });
             },
-            Comment(text) => { todo!{"Render comments!"}},
-            _ => {
-                Paragraph::new("Hmm... I am not a useful node, why am I here?").block(Block::default().borders(Borders::NONE))
-            }
+            "br" if current.preformatted => current.push_verbatim("\n", Style::default()),
+            "br" => blocks.extend(current.finish()),
+            "img" => {
+                let alt = elem.attr("alt").map(str::trim);
+                let src = elem.attr("src").or(elem.attr("data-src")).unwrap_or_default();
+                // Images with empty alt text are decorative.
+                if alt == Some("") || (alt.is_none() && src.is_empty()) { return }
+                let file = src.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+                current.push_image(alt.unwrap_or(file), src, inherited.to_style());
+            },
+            // Fallback content and <source> elements are left out, as the media is handed off to a player.
+            "video" | "audio" => current.push_media(TuiMedia::from_node(self).expect("Media element is read"), inherited.to_style()),
+            "a" if elem.attr("href").is_some() => {
+                let (block_count, start) = (blocks.len(), current.next_offset());
+                children(blocks, current);
+                // Links broken up by blocks are left unmarked.
+                if blocks.len() == block_count && start < current.len {
+                    let href = elem.attr("href").unwrap_or_default().to_owned();
+                    current.links.push(TuiLink { start, end: current.len, href });
+                }
+            },
+            _ if style.preformatted && !current.preformatted => {
+                blocks.extend(current.finish());
+                current.preformatted = true;
+                current.path.push(elem.selector());
+                children(blocks, current);
+                blocks.extend(current.finish());
+                current.path.pop();
+                current.preformatted = false;
+            },
+            _ if is_block && !current.preformatted => {
+                blocks.extend(current.finish());
+                current.path.push(elem.selector());
+                children(blocks, current);
+                blocks.extend(current.finish());
+                current.path.pop();
+            },
+            _ => children(blocks, current),
         }
     }
 }
 impl std::default::Default for TuiNode {
-    fn default() -> Self { TuiNode { data: TuiNodeData::Phantom, children: Vec::new() }}
+    fn default() -> Self { TuiNode { data: TuiNodeData::Phantom, children: Vec::new(), style: None }}
 }
 
 
@@ -314,7 +1587,18 @@ pub enum TuiNodeData {
     Phantom
 } impl TuiNodeData {
     /// TODO Documentation
-    pub fn is_phantom(&self) -> bool { match self { Self::Phantom => true, _ => false }}
+    pub fn is_phantom(&self) -> bool { matches!(self, Self::Phantom) }
+
+    /// Compares node data without regard to children, including element attributes.
+    pub fn same_as(&self, other: &TuiNodeData) -> bool {
+        use TuiNodeData::*;
+        match (self, other) {
+            (Element(a), Element(b)) => a == b && a.attributes == b.attributes,
+            (Text(a), Text(b)) | (Comment(a), Comment(b)) => a == b,
+            (Phantom, Phantom) => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -325,6 +1609,39 @@ pub struct TuiElement {
     //pub template: Vec<TuiNode>
 } impl TuiElement {
 
+    /// Gets the value of an attribute by its local name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|a| a.name == name).map(|a| a.value.as_str())
+    }
+
+    /// Gets the CSS selector that names only this element, as marked when the page was read.
+    /// Elements that weren't marked fall back to their id, or else their tag and classes.
+    pub fn selector(&self) -> String {
+        if let Some(selector) = self.attr(crate::browser::SELECTOR_ATTRIBUTE) { return selector.to_owned() }
+        let is_ident = crate::utils::is_css_ident;
+        if let Some(id) = self.attr("id").filter(|id| is_ident(id)) { return format!("#{}", id) }
+
+        let mut selector = self.name().to_owned();
+        for class in self.attr("class").unwrap_or_default().split_whitespace().filter(|c| is_ident(c)) {
+            selector.push('.');
+            selector.push_str(class);
+        }
+        selector
+    }
+
+    /// Local tag name of the element, e.g. "div".
+    pub fn name(&self) -> &str { &self.qual_name.local }
+
+    /// True for elements that start a new block of text when rendered.
+    pub fn is_block(&self) -> bool {
+        matches!(self.name(),
+            "address" | "article" | "aside" | "blockquote" | "body" | "dd" | "details" | "dialog" |
+            "div" | "dl" | "dt" | "fieldset" | "figcaption" | "figure" | "footer" | "form" |
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "header" | "hr" | "li" | "main" | "nav" |
+            "ol" | "p" | "pre" | "section" | "summary" | "table" | "td" | "th" | "tr" | "ul"
+        )
+    }
+
 } impl PartialEq for TuiElement {
     fn eq(&self, other: &TuiElement) -> bool {
         self.qual_name == other.qual_name
@@ -332,14 +1649,18 @@ pub struct TuiElement {
 }
 
 
-#[derive(Clone)]
-pub struct TuiAttribute(pub String);
+#[derive(Clone, PartialEq)]
+pub struct TuiAttribute {
+    pub name: String,
+    pub value: String,
+}
 
 /*
  Display layout-
     Tab layout-
-        Sidebar-
-        Page view-
+        Header, navigation & collapsed panes-
+        Page view-      Sidebar-
+        Footer panes-
     Bottom bar-
         Status bar-
         Tab list-
@@ -373,3 +1694,175 @@ pub struct TuiAttribute(pub String);
 
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::{filter_dom_html, ToTui};
+    use crate::config::SelectorRules;
+
+    const ARTICLE: &str = include_str!("../tests/fixtures/article.html");
+
+    fn dom(html: &str) -> TuiNode { filter_dom_html(html, &SelectorRules::default()).to_tui() }
+
+    /// A tab showing a page, laid out in a viewport of the given size.
+    fn tab(html: &str, width: u16, height: u16) -> TuiTab {
+        let mut tab = TuiTab::new("tab", "", BackendKind::Reqwest);
+        tab.viewport = Rect::new(0, 0, width, height);
+        tab.update(dom(html), "Page title | Example News").unwrap();
+        tab
+    }
+
+    #[test]
+    fn ends_downloads_from_closed_tabs() {
+        let mut display = TuiDisplay::new();
+        display.new_tab("tab", "", BackendKind::Chrome).unwrap();
+        display.start_download("tab", "guid", "https://example.com/file.zip", "file.zip");
+        display.close_tab("tab");
+
+        assert!(!display.update_download("guid", 10, Some(20), DownloadState::InProgress));
+        assert!(display.update_download("guid", 20, Some(20), DownloadState::Canceled));
+        assert!(display.downloads.list[0].state == DownloadState::Canceled);
+        assert_eq!(display.navbar.status.message.as_ref().map(|(m, _)| m.as_str()), Some("Canceled download of file.zip"));
+        // Events for downloads that have ended are ignored.
+        assert!(!display.update_download("guid", 20, Some(20), DownloadState::Completed));
+    }
+
+    #[test]
+    fn finds_text_in_landmark_panes() {
+        let html = "<html><body><nav><a href='/'>Home</a> <a href='/menu'>Café menu</a></nav>\
+            <main><p>Our café opens at nine.</p></main><aside><p>Visit the café garden.</p></aside></body></html>";
+        let mut tab = tab(html, 80, 24);
+        assert_eq!(tab.panes.len(), 2);
+        tab.set_find(Some(Find::parse("café").unwrap()));
+        let find = tab.find.as_ref().unwrap();
+        assert_eq!(find.matches.iter().map(|m| m.pane).collect::<Vec<_>>(), [None, Some(0), Some(1)]);
+        assert!(tab.focus.is_none() && tab.panes.iter().all(|p| p.collapsed));
+
+        // Stepping into a pane opens it and highlights the match there.
+        assert!(tab.step_find(1));
+        assert_eq!(tab.focus, Some(0));
+        assert!(!tab.panes[0].collapsed);
+        let nav = &tab.panes[0].blocks;
+        assert!(nav.iter().any(|b| b.highlights.iter().any(|h| h.2 == Find::CURRENT_STYLE)));
+        assert!(tab.blocks.iter().all(|b| b.highlights.iter().all(|h| h.2 == Find::MATCH_STYLE)));
+
+        tab.step_find(2);
+        assert_eq!(tab.focus, None);
+        tab.set_find(None);
+        assert!(tab.panes.iter().flat_map(|p| p.blocks.iter()).all(|b| b.highlights.is_empty()));
+    }
+
+    #[test]
+    fn keeps_reader_view_wanted_across_refreshes() {
+        let mut tab = tab(ARTICLE, 80, 24);
+        assert!(tab.toggle_reader());
+        assert!(tab.reader);
+
+        // A refresh that finds no article falls back to the full page, but reader view comes back with the article.
+        tab.patch(dom("<html><body><p>Loading…</p></body></html>"), "Page title | Example News");
+        assert!(tab.reader_wanted && !tab.reader);
+        assert_eq!(tab.blocks[0].text, "Loading…");
+        tab.patch(dom(ARTICLE), "Page title | Example News");
+        assert!(tab.reader);
+        assert_eq!(tab.blocks[0].text, "Reading the web from a terminal");
+
+        assert!(tab.toggle_reader());
+        assert!(!tab.reader_wanted && !tab.reader);
+        let mut tab = self::tab("<html><body><p>Short</p></body></html>", 80, 24);
+        assert!(!tab.toggle_reader());
+        assert!(!tab.reader_wanted);
+    }
+
+    #[test]
+    fn keeps_scroll_anchored_across_widths() {
+        let paragraph = |n: usize| format!("<p>Paragraph {n} {}</p>", "has quite a few words to wrap. ".repeat(8));
+        let mut tab = tab(&format!("<html><body>{}</body></html>", (0..6).map(paragraph).collect::<String>()), 80, 10);
+        assert!(tab.blocks.iter().all(|b| b.lines.iter().all(|(_, l)| l.chars().count() <= 80)));
+        let wide = tab.blocks[3].height();
+
+        // Scroll to the second line of the fourth paragraph.
+        tab.scroll = tab.blocks[..3].iter().map(|b| b.height()).sum::<usize>() + 1;
+        let (block, offset) = tab.anchor().unwrap();
+        assert_eq!((block, offset), (3, tab.blocks[3].lines[1].0));
+
+        assert!(tab.resize(Rect::new(0, 0, 30, 10)));
+        assert!(tab.blocks.iter().all(|b| b.lines.iter().all(|(_, l)| l.chars().count() <= 30)));
+        assert!(tab.blocks[3].height() > wide);
+        let (narrow_block, narrow_offset) = tab.anchor().unwrap();
+        assert_eq!(narrow_block, 3);
+        let line = &tab.blocks[3].lines.iter().find(|(o, _)| *o == narrow_offset).unwrap().1;
+        assert!(narrow_offset <= offset && offset < narrow_offset + line.chars().count().max(1) + 1);
+
+        // Back at the first width, the view starts on the wide line holding the narrow one.
+        assert!(tab.resize(Rect::new(0, 0, 80, 10)));
+        let anchor = tab.anchor().unwrap();
+        assert_eq!(anchor.0, 3);
+        assert_eq!(anchor.1, tab.blocks[3].lines.iter().rev().find(|(o, _)| *o <= narrow_offset).unwrap().0);
+        assert!(!tab.resize(Rect::new(0, 0, 80, 5)));
+        assert_eq!(tab.anchor(), Some(anchor));
+
+        // Anchors past the end of the page fall back to the top.
+        tab.restore_anchor(Some((99, 0)));
+        assert_eq!(tab.scroll, 0);
+    }
+
+    #[test]
+    fn reads_computed_styles() {
+        let style = TuiStyle::from_computed("block|visible|700|italic|rgb(255, 0, 0)|rgba(0, 0, 0, 0)|underline line-through|pre-wrap");
+        assert!(style.flow == Some(TextFlow::Block) && !style.hidden);
+        assert!(style.bold && style.italic && style.underline && style.strikethrough && style.preformatted);
+        assert!(style.fg == Some((255, 0, 0)) && style.bg.is_none());
+
+        let style = TuiStyle::from_computed("inline-block|hidden|bold|oblique|rgb(0, 0, 0)|rgb(1, 2, 3)|none|normal");
+        assert!(style.flow == Some(TextFlow::Inline) && style.hidden && style.bold && style.italic);
+        assert!(!style.underline && !style.preformatted && style.bg == Some((1, 2, 3)));
+        let style = TuiStyle::from_computed("contents|visible|500|normal|||none|break-spaces");
+        assert!(style.flow == Some(TextFlow::Inline) && !style.bold && style.preformatted && style.fg.is_none());
+        assert!(TuiStyle::from_computed("none").hidden);
+        assert!(TuiStyle::from_computed("") == TuiStyle::default());
+    }
+
+    #[test]
+    fn expands_tabs_in_preformatted_text() {
+        let blocks = dom("<html><body><pre>a\tb\n\tc</pre></body></html>").text_blocks();
+        assert_eq!(blocks[0].text, "a    b\n    c");
+        assert!(blocks[0].preformatted);
+    }
+
+    #[test]
+    fn reuses_pictures_until_the_width_changes() {
+        let html = "<html><body><p>A picture <img src='a.png' alt='A'> of something</p></body></html>";
+        let mut tab = tab(html, 40, 24);
+        let image = Arc::new(image::DynamicImage::new_rgb8(64, 32));
+        tab.images.insert("a.png".to_owned(), ImageState::Ready(image));
+        tab.expanded_images.insert("a.png".to_owned());
+        tab.refresh_images();
+        let picture = tab.blocks[0].pictures[0].clone();
+
+        tab.refresh_images();
+        assert!(Arc::ptr_eq(&picture, &tab.blocks[0].pictures[0]));
+        tab.patch(dom(&html.replace("something", "something else")), "Page title | Example News");
+        assert!(Arc::ptr_eq(&picture, &tab.blocks[0].pictures[0]));
+        tab.resize(Rect::new(0, 0, 20, 24));
+        assert!(!Arc::ptr_eq(&picture, &tab.blocks[0].pictures[0]));
+        assert_eq!(tab.blocks[0].pictures[0].max_cols, 20);
+    }
+
+    #[test]
+    fn patches_only_changed_nodes() {
+        let page = |body: &str| dom(&format!("<html><body>{}</body></html>", body));
+        let text = |node: &TuiNode| node.text_blocks().into_iter().map(|b| b.text).collect::<Vec<_>>();
+        let mut node = page("<p>One</p><p>Two</p><p>Three</p>");
+        assert!(!node.patch(page("<p>One</p><p>Two</p><p>Three</p>")));
+
+        assert!(node.patch(page("<p>One</p><p>2</p><p>Three</p><p>Four</p>")));
+        assert_eq!(text(&node), ["One", "2", "Three", "Four"]);
+        assert!(node.patch(page("<p>One</p><p class='new'>Three</p>")));
+        assert_eq!(text(&node), ["One", "Three"]);
+        assert!(node.patch(page("<div>One</div>")));
+        assert_eq!(text(&node), ["One"]);
+        assert!(!node.patch(page("<div>One</div>")));
+    }
+}
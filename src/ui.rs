@@ -1,43 +1,65 @@
 
 use crate::Etymon;
 use crate::config::CursorControls;
+use crate::tui::TuiOverlay;
+use crate::backend::BackendKind;
 
-use crossterm::event::{read, Event, KeyEvent, KeyCode::{self, *}, KeyEventKind, KeyModifiers};
+use crossterm::event::{Event, KeyEvent, KeyCode::{self, *}, KeyEventKind, KeyModifiers};
+
+/// Columns that Left and Right scroll a table across by.
+const TABLE_SCROLL: isize = 8;
 
 /// UIMode describes the various contexts for input events.
 /// Fn keys work in all modes, but keymaps only work in View,
 /// because in Insert mode characters keys are used to place text.
+#[derive(Clone, Copy, PartialEq)]
 pub enum UIMode {
     /// Default mode for navigating pages and viewing text.
     View,
     /// Mode for the insertion of character into forms or other test-based widgets.
-    Insert
+    Insert,
+    /// Mode for typing into the navbar command line.
+    Command,
+}
+
+/// Commands run from the command line, each named in `COMMANDS`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command { Quit, Hide, Reader, Play, Request, Curl, Find, Search, Wget, Close, Bookmark, Tab, Downloads, Cookies, OpenWith }
+
+/// Names of the commands run by execute_command. Anything else typed is opened as an address or searched for.
+pub const COMMANDS: &[(&str, Command)] = &[
+    ("q", Command::Quit), ("quit", Command::Quit), ("hide", Command::Hide), ("reader", Command::Reader),
+    ("play", Command::Play), ("r", Command::Request), ("c", Command::Curl), ("f", Command::Find),
+    ("s", Command::Search), ("w", Command::Wget), ("close", Command::Close), ("bookmark", Command::Bookmark),
+    ("tab", Command::Tab), ("downloads", Command::Downloads), ("cookies", Command::Cookies), ("open-with", Command::OpenWith),
+];
+
+impl Command {
+    /// Finds the command with a name, if it is one.
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().find(|(n, _)| *n == name).map(|(_, command)| *command)
+    }
 }
-impl Etymon {
 
-    /// Loops for duration to process user input, then breaks to allow backend to run.
-    pub fn handle_ui(&mut self) -> Result<(), anyhow::Error> { // TODO Replace with async for simultaneous keys
-        let start = std::time::Instant::now();
-        let delay = (1000.0 / crate::CONFIG.frame_rate) as u64;
-        let duration = std::time::Duration::from_millis(delay);
+impl Etymon {
 
+    /// Processes a single terminal input event.
+    pub fn handle_ui(&mut self, event: Event) -> Result<(), anyhow::Error> {
         use UIMode::*;
-        while start.elapsed() < duration {
-            let event = read()?;
-            match event {
-                Event::FocusGained => {/* TBD */},
-                Event::FocusLost => {/* TBD */},
-                Event::Key(event) => match self.mode {
-                    View => self.view_key_event(event)?,
-                    Insert => self.insert_key_event(event)?
-                },
-                Event::Mouse(_event) => {/* TBD */},
-                Event::Paste(ref _data) => {/* TBD */},
-                Event::Resize(_width, _height) => {/* TBD */},
-            }
+        match event {
+            Event::FocusGained => {/* TBD */},
+            Event::FocusLost => {/* TBD */},
+            Event::Key(event) => match self.mode {
+                View if self.tui.display.overlay.is_some() => self.overlay_key_event(event),
+                View => self.view_key_event(event)?,
+                Insert => self.insert_key_event(event)?,
+                Command => self.command_key_event(event)?,
+            },
+            Event::Mouse(_event) => {/* TBD */},
+            Event::Paste(ref _data) => {/* TBD */},
+            Event::Resize(width, height) => self.resize(width, height)?,
         }
-
-        Ok(self.tui().cache_cursor()?)
+        Ok(())
     }
 
     /// Process keyboard events in View mode.
@@ -49,10 +71,10 @@ impl Etymon {
         let shift = KeyModifiers::from_name("SHIFT").unwrap();
         let alt   = KeyModifiers::from_name("ALT").unwrap();
         let ctrl  = KeyModifiers::from_name("CONTROL").unwrap();
-        let ctrl_shift = { let mut m = shift.clone(); m.insert(ctrl.clone()); m };
-        let ctrl_alt   = { let mut m = alt.clone(); m.insert(ctrl.clone()); m };
-        let alt_shift  = { let mut m = shift.clone(); m.insert(alt.clone()); m };
-        let maximum_overmod = { let mut mo = ctrl_shift.clone(); mo.insert(alt.clone()); mo };
+        let ctrl_shift = { let mut m = shift; m.insert(ctrl); m };
+        let ctrl_alt   = { let mut m = alt; m.insert(ctrl); m };
+        let alt_shift  = { let mut m = shift; m.insert(alt); m };
+        let maximum_overmod = { let mut mo = ctrl_shift; mo.insert(alt); mo };
 
         match event.code {
 
@@ -67,29 +89,29 @@ impl Etymon {
                 else if mods(alt)           { todo!{"Alternate keymap"}}
             },
 
-            Enter                 => {
-                if mods(none) { todo!{"Select a button or form"}}
-                else {/* TBD */}
-            },
+            Enter if mods(none)   => todo!{"Select a button or form"},
+            Enter                 => {/* TBD */},
+
+            Left                  => self.scroll_table(-TABLE_SCROLL),
+            Right                 => self.scroll_table(TABLE_SCROLL),
+            Tab                   => self.focus_landmark(1),
+            BackTab               => self.focus_landmark(-1),
+
+            PageUp                => self.scroll_page(-1),
+            PageDown              => self.scroll_page(1),
+            Home                  => self.scroll_to_end(false),
+            End                   => self.scroll_to_end(true),
 
             _ => (),
 
             // TBD Unimplemented:
             /*
             Esc                   => println!("End subroutine and return to base view mode"),
-            Left                  => println!("Move focus to left widget"),
-            Right                 => println!("Widget to right"),
             Up                    => println!("Widget above"),
             Down                  => println!("Widget below"),
-            Home                  => println!("Jump to page top"),
-            End                   => println!("Jump to page bottom"),
-            PageUp                => println!("Scroll up"),
-            PageDown              => println!("Scroll down"),
             F(f_u8)               => println!("Execute mapped script on current page"),
 
             Backspace              => println!("reconstruct dom, removing user changes"),
-            Tab                    => println!("cycle through forms"),
-            BackTab                => println!("cycle through forms in reverse"),
             Delete                 => println!("remove tui element from dom"),
             Insert                 => println!("enter html edit mode"),
             PrintScreen            => println!("take snapshot of tui and/or chrome as pdf"),
@@ -126,9 +148,18 @@ impl Etymon {
     pub fn char_press(&mut self, ch: char) -> Result<(), anyhow::Error> {
         match ch {
             'w'|'a'|'s'|'d'|'h'|'j'|'k'|'l' => self.process_cursor(ch),
-            'q' => Ok(self.quit()), // TBD Final location where?
+            ':' => { self.set_mode(UIMode::Command); Ok(()) },
+            'r' => { self.toggle_reader(); Ok(()) },
+            'e' => { self.toggle_pane(); Ok(()) },
+            't' => { self.open_table(); Ok(()) },
+            'i' => { self.toggle_image(); Ok(()) },
+            'm' => { self.play_media(); Ok(()) },
+            'D' => { self.tui.display.overlay = Some(TuiOverlay::Downloads); Ok(()) },
+            'n' => { self.step_find(1); Ok(()) },
+            'N' => { self.step_find(-1); Ok(()) },
+            'q' => { self.quit(); Ok(()) }, // TBD Final location where?
             'p' => panic!("This panic was used to intentionally crash the program! Toodaloo!"), // TBD Final location where?
-            _ => Ok(self.process_keymap(ch)),
+            _ => { self.process_keymap(ch); Ok(()) },
         }
     }
 
@@ -187,6 +218,139 @@ impl Etymon {
         Ok(())
     }
 
+    /// Scrolls the current page, or its focused landmark pane, by a number of screens.
+    pub fn scroll_page(&mut self, pages: isize) {
+        let Some(tab) = self.tui.display.current_tab_mut() else { return };
+        match tab.focused_pane_mut().filter(|p| !p.collapsed) {
+            Some(pane) => {
+                let height = pane.content_area().height.max(1) as isize;
+                pane.scroll_by(pages * height)
+            },
+            None => {
+                let height = tab.viewport.height.max(1) as isize;
+                tab.scroll_by(pages * height)
+            },
+        }
+    }
+
+    /// Moves focus between the landmark panes and main content of the page, taking the cursor along.
+    pub fn focus_landmark(&mut self, step: isize) {
+        if let Some(tab) = self.tui.display.current_tab_mut() {
+            self.tui.cursor_cache = tab.focus_landmark(step);
+        }
+    }
+
+    /// Scrolls the table under the cursor across by a number of columns.
+    pub fn scroll_table(&mut self, columns: isize) {
+        let (x, y) = self.tui.cursor_cache;
+        if let Some(tab) = self.tui.display.current_tab_mut() { tab.scroll_table_at(x, y, columns); }
+    }
+
+    /// Opens the table under the cursor in a full-screen view.
+    pub fn open_table(&mut self) {
+        let (x, y) = self.tui.cursor_cache;
+        let display = &mut self.tui.display;
+        let table = display.tabs.get(display.current_tab).and_then(|t| t.table_at(x, y)).cloned();
+        match table {
+            Some(table) => display.overlay = Some(TuiOverlay::Table { table, row: 0, column: 0 }),
+            None => display.navbar.status.post("No table under the cursor"),
+        }
+    }
+
+    /// Process keyboard events while a full-screen view is open over the page.
+    pub fn overlay_key_event(&mut self, event: KeyEvent) {
+        if event.kind == KeyEventKind::Release { return }
+        let Some(overlay) = &mut self.tui.display.overlay else { return };
+        if let TuiOverlay::Downloads = overlay { return self.downloads_key_event(event) }
+        if let TuiOverlay::Cookies(_) = overlay { return self.cookies_key_event(event) }
+        match event.code {
+            Esc | Char('q') | Char('t') => self.tui.display.overlay = None,
+            Up | Char('w') | Char('k')    => overlay.scroll(-1, 0),
+            Down | Char('s') | Char('j')  => overlay.scroll(1, 0),
+            Left | Char('a') | Char('h')  => overlay.scroll(0, -1),
+            Right | Char('d') | Char('l') => overlay.scroll(0, 1),
+            PageUp                        => overlay.scroll(-10, 0),
+            PageDown                      => overlay.scroll(10, 0),
+            Home                          => overlay.scroll(isize::MIN / 2, 0),
+            End                           => overlay.scroll(isize::MAX / 2, 0),
+            _ => (),
+        }
+    }
+
+    /// Handles keys while the download list is open.
+    fn downloads_key_event(&mut self, event: KeyEvent) {
+        let downloads = &mut self.tui.display.downloads;
+        match event.code {
+            Esc | Char('q') | Char('D')   => self.tui.display.overlay = None,
+            Up | Char('w') | Char('k')    => downloads.select(-1),
+            Down | Char('s') | Char('j')  => downloads.select(1),
+            Home                          => downloads.select(isize::MIN / 2),
+            End                           => downloads.select(isize::MAX / 2),
+            Enter | Char('o')             => self.open_download(None),
+            Char('c') | Delete            => self.cancel_download(),
+            Char(':')                     => { self.tui.display.overlay = None; self.set_mode(UIMode::Command) },
+            _ => (),
+        }
+    }
+
+    /// Handles keys while the cookie list is open.
+    fn cookies_key_event(&mut self, event: KeyEvent) {
+        let Some(TuiOverlay::Cookies(cookies)) = &mut self.tui.display.overlay else { return };
+        match event.code {
+            Esc | Char('q')               => self.tui.display.overlay = None,
+            Up | Char('w') | Char('k')    => cookies.select(-1),
+            Down | Char('s') | Char('j')  => cookies.select(1),
+            Home                          => cookies.select(isize::MIN / 2),
+            End                           => cookies.select(isize::MAX / 2),
+            Char('d') | Delete            => self.delete_cookies(false),
+            Char('x')                     => self.delete_cookies(true),
+            Char(':')                     => { self.tui.display.overlay = None; self.set_mode(UIMode::Command) },
+            _ => (),
+        }
+    }
+
+    /// Finds text on the current page, or clears the search when there is no text.
+    /// Errors in a regular expression are only reported once the search is entered.
+    pub fn find(&mut self, args: &str, entered: bool) {
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get_mut(display.current_tab) else { return };
+        match crate::find::Find::parse(args) {
+            Ok(find) if find.query.is_empty() => tab.set_find(None),
+            Ok(find) => tab.set_find(Some(find)),
+            Err(error) if entered => display.navbar.status.post(&format!("Bad search: {}", error)),
+            Err(_) => (),
+        }
+    }
+
+    /// Moves to the next (or previous) match of the search on the current page.
+    pub fn step_find(&mut self, step: isize) {
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get_mut(display.current_tab) else { return };
+        if !tab.step_find(step) { display.navbar.status.post("Find text on the page with :f first") }
+    }
+
+    /// Expands or collapses the focused landmark pane.
+    pub fn toggle_pane(&mut self) {
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get_mut(display.current_tab) else { return };
+        match tab.focused_pane_mut() {
+            Some(pane) => pane.collapsed = !pane.collapsed,
+            None => display.navbar.status.post("Focus a landmark pane with Tab to expand it"),
+        }
+    }
+
+    /// Jumps to the top or bottom of the current page.
+    pub fn scroll_to_end(&mut self, bottom: bool) {
+        if let Some(tab) = self.tui.display.current_tab_mut() { tab.scroll_to_end(bottom) }
+    }
+
+    /// Switches the current page between reader view and the full page.
+    pub fn toggle_reader(&mut self) {
+        let display = &mut self.tui.display;
+        let Some(tab) = display.tabs.get_mut(display.current_tab) else { return };
+        if !tab.toggle_reader() { display.navbar.status.post("No article found on this page") }
+    }
+
     pub fn process_keymap(&mut self, ch: char) {
         if let Some(mapped) = crate::CONFIG.char_keymap.binds.get(&ch) {
             // TODO Do something with mapped
@@ -194,7 +358,7 @@ impl Etymon {
         }
     }
 
-    /// Process keyboard events in View mode.
+    /// Process keyboard events in Insert mode.
     pub fn insert_key_event(&mut self, event: KeyEvent) -> Result<(), anyhow::Error> {
 
         let mods = |mods| { event.modifiers.contains(mods) };
@@ -203,10 +367,10 @@ impl Etymon {
         let shift = KeyModifiers::from_name("SHIFT").unwrap();
         let alt   = KeyModifiers::from_name("ALT").unwrap();
         let ctrl  = KeyModifiers::from_name("CONTROL").unwrap();
-        let ctrl_shift = { let mut m = shift.clone(); m.insert(ctrl.clone()); m };
-        let _ctrl_alt   = { let mut m = alt.clone(); m.insert(ctrl.clone()); m };
-        let _alt_shift  = { let mut m = shift.clone(); m.insert(alt.clone()); m };
-        let _maximum_overmod = { let mut mo = ctrl_shift.clone(); mo.insert(alt.clone()); mo };
+        let ctrl_shift = { let mut m = shift; m.insert(ctrl); m };
+        let _ctrl_alt   = { let mut m = alt; m.insert(ctrl); m };
+        let _alt_shift  = { let mut m = shift; m.insert(alt); m };
+        let _maximum_overmod = { let mut mo = ctrl_shift; mo.insert(alt); mo };
 
         match event.code {
 
@@ -214,12 +378,10 @@ impl Etymon {
                 /* TODO self.insert_text() */
             },
 
-            Enter => {
-                if mods(none) { todo!{"Submit a form"}}
-                else {/* TBD: Newline without CR */}
-            },
+            Enter if mods(none) => todo!{"Submit a form"},
+            Enter => {/* TBD: Newline without CR */},
 
-            Esc                   => println!("Exit insert mode"),
+            Esc                   => self.set_mode(UIMode::View),
             Left                  => println!("Move cursor to left within widget"),
             Right                 => println!("Widget to right within widget"),
             Up                    => println!("Move cursor up within widget"),
@@ -238,11 +400,102 @@ impl Etymon {
         Ok(())
     }
 
+    /// Process keyboard events in Command mode, editing the command line.
+    pub fn command_key_event(&mut self, event: KeyEvent) -> Result<(), anyhow::Error> {
+        if event.kind == KeyEventKind::Release { return Ok(()) }
+        let navbar = &mut self.tui.display.navbar;
+        let command = &mut navbar.command;
+        match event.code {
+            Char(ch) => command.push(ch),
+            Backspace if command.is_empty() => self.set_mode(UIMode::View),
+            Backspace => { command.pop(); },
+            // Tab and BackTab cycle through completions of an address.
+            Tab | BackTab => if let Some(start) = address_start(command) {
+                navbar.cycle_completion(start, match event.code { BackTab => -1, _ => 1 });
+            },
+            Esc => {
+                command.clear();
+                self.set_mode(UIMode::View);
+            },
+            Enter => {
+                let command = std::mem::take(command);
+                self.set_mode(UIMode::View);
+                self.execute_command(&command)?;
+            },
+            _ => (),
+        }
+        if matches!(event.code, Char(_) | Backspace) {
+            // Finds update as the search is typed, and completions as the address is.
+            let command = self.tui.display.navbar.command.clone();
+            if let Some(args) = command.strip_prefix("f ") { self.find(args, false) }
+            self.update_completions();
+        }
+        Ok(())
+    }
+
+    /// Runs a command entered on the command line.
+    pub fn execute_command(&mut self, command: &str) -> Result<(), anyhow::Error> {
+        let command = command.trim();
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        if name.is_empty() { return Ok(()) }
+        let Some(command_name) = Command::from_name(name) else { return self.go(command) };
+        match command_name {
+            Command::Quit => self.quit(),
+            // :hide [levels] hides the focused block, or the given number of ancestors up from it.
+            Command::Hide => self.hide_focused(args.trim().parse().unwrap_or(0)),
+            Command::Reader => self.toggle_reader(),
+            Command::Play => self.play_media(),
+            Command::Request => self.request_page(args.trim())?,
+            // :c [flags] [--] [url] and :w [flags] [--] [url] pass the flags on to curl and wget.
+            Command::Curl => self.curl_page(args.trim())?,
+            // :f [-c] [-r] text finds text on the page, case sensitive with -c and as a regular expression with -r.
+            Command::Find => self.find(args, true),
+            // :s [keyword] terms searches with the engine the keyword names, or the default engine.
+            Command::Search => self.search(args)?,
+            Command::Wget => self.wget_download(args.trim())?,
+            Command::Close => self.close_tab(),
+            Command::Bookmark => self.bookmark(),
+            // :tab [chrome|reqwest] [url] opens a new tab, with the given backend or else the default.
+            Command::Tab => {
+                let (first, rest) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+                match BackendKind::from_name(first) {
+                    Some(kind) => self.new_tab(Some(rest.trim()).filter(|u| !u.is_empty()), kind)?,
+                    None => self.new_tab(Some(args.trim()).filter(|u| !u.is_empty()), crate::CONFIG.backend)?,
+                }
+            },
+            Command::Downloads => self.tui.display.overlay = Some(TuiOverlay::Downloads),
+            // :cookies [export <file> | import <file> | clear-storage]
+            Command::Cookies => self.cookies_command(args.trim()),
+            // :open-with <command> opens the download selected in the download list.
+            Command::OpenWith => self.open_download(Some(args.trim()).filter(|a| !a.is_empty())),
+        }
+        Ok(())
+    }
+
 }
 
 
 
 
+/// Where the address starts in a command that opens one, for completing it: after :tab and its backend,
+/// after :r, or the whole command when it isn't a command at all.
+pub fn address_start(command: &str) -> Option<usize> {
+    let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+    match name {
+        "tab" => {
+            let first = args.split(' ').next().unwrap_or_default();
+            match BackendKind::from_name(first).is_some() && args.len() > first.len() {
+                true => Some(name.len() + first.len() + 2),
+                false => Some(name.len() + 1).filter(|start| *start <= command.len()),
+            }
+        },
+        "r" => Some(2).filter(|start| *start <= command.len()),
+        _ if Command::from_name(name).is_some() => None,
+        _ => Some(0),
+    }
+}
+
+
 //TBD
 // Reserved keys
 // depending on cursor mode, wasd or hjkl
@@ -255,12 +508,31 @@ impl Etymon {
 // perl scripts for navigation & manipulation?
 
 // navbar commands
-// http://www... loads link via chrome
-// :r http://... loads link or API request via reqwuest
-// :c http://... same thing but with curl
-// :w http://... again for wget
-// :s keywords   searches web using default search engine
-// :f keywords   searches current text
+// http://www... loads link with the tab's backend (chrome by default)
+// :r http://... loads link or API request via reqwest; JSON is pretty-printed, text shown as is
+// :c [flags] http://...   shows curl's output in a new tab, sending the tab's cookies, user agent and referer
+// :w [flags] http://...   downloads with wget into the download list, with the same session; flags pass through
+// :s [k] words  searches the web with the engine whose keyword is k, or the default engine; other text
+//               that isn't an address is searched for too. DuckDuckGo results are listed natively
+// :f [-c] [-r] text   finds text on the page as it is typed, case sensitive with -c, as a regex with -r;
+//                      n/N move to the next/previous match and :f alone clears the highlights
+// :example.com  opens an address: bare hosts use https (http for localhost), paths and file:// open local files,
+//               and about: pages work too. Tab/BackTab cycle through completions from open tabs (▣),
+//               bookmarks (★) and history (·), ranked by how often and how lately each page was visited
+// :bookmark     bookmarks the page in view, which is then offered as a completion
+// :hide [n]     hides the focused element (or its nth ancestor) on the current domain
+// :reader       toggles reader view of the page's main article (also 'r' in View mode)
+// t             opens the table under the cursor full screen; Left/Right scroll it across in place
+// Tab/BackTab   moves focus between landmark panes (navigation, sidebars, footers); 'e' expands or collapses one
+// i             expands the image under the cursor in place, or collapses it back to its placeholder
+// :play         plays the video or audio under the cursor in an external player (also 'm' in View mode)
+// :downloads    lists downloads with their progress (also 'D'); Enter/o opens one, c cancels it
+// :open-with c  opens the selected download with command c instead of its configured handler
+// :cookies     lists the current site's cookies; d deletes one, x deletes all of its domain's
+// :cookies export f / import f   saves or loads the site's cookies as a Netscape cookies.txt file, for curl and wget
+// :cookies clear-storage         clears the page's localStorage and sessionStorage
+// :tab [b] [u]  opens a new tab with backend b (chrome, reqwest or curl, else the configured default) at URL u
+// :close        closes the tab in view
 
 // :perl my $perl;
 // :perl perlscript.pl
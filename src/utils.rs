@@ -14,3 +14,330 @@ pub fn init_panic_handler() {
 
     // TODO Color-eyre and human-panic hooks
 }
+
+/// Wraps text on word boundaries to fit within `width` columns.
+/// Each line is paired with the char offset where it begins in the original text,
+/// so that a reading position can be carried across different wrap widths.
+pub fn wrap_text(text: &str, width: usize) -> Vec<(usize, String)> {
+    let width = width.max(1);
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut line = String::new();
+    let mut line_start = 0;
+    let mut line_len = 0;
+
+    for (start, word) in words_with_offsets(text) {
+        let mut start = start;
+        let mut word: Vec<char> = word.chars().collect();
+
+        // Words that can't fit on any line are split across lines.
+        while word.len() > width {
+            if line_len > 0 { lines.push((line_start, std::mem::take(&mut line))); line_len = 0 }
+            let rest = word.split_off(width);
+            lines.push((start, word.into_iter().collect()));
+            start += width;
+            word = rest;
+        }
+
+        if line_len > 0 && line_len + 1 + word.len() > width {
+            lines.push((line_start, std::mem::take(&mut line)));
+            line_len = 0;
+        }
+        if line_len == 0 { line_start = start } else { line.push(' '); line_len += 1 }
+        line_len += word.len();
+        line.extend(word);
+    }
+
+    if line_len > 0 { lines.push((line_start, line)) }
+    lines
+}
+
+/// Splits text on whitespace, keeping the char offset of each word.
+fn words_with_offsets(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut word_start: Option<(usize, usize)> = None;
+    for (c, (b, ch)) in text.char_indices().enumerate() {
+        match (ch.is_whitespace(), word_start) {
+            (true, Some((cs, bs))) => { words.push((cs, &text[bs..b])); word_start = None },
+            (false, None) => word_start = Some((c, b)),
+            _ => (),
+        }
+    }
+    if let Some((cs, bs)) = word_start { words.push((cs, &text[bs..])) }
+    words
+}
+
+/// True for names that can be used in a CSS selector without escaping, like ids and classes.
+pub fn is_css_ident(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// True if the host is the domain itself or one of its subdomains.
+pub fn host_matches(host: &str, domain: &str) -> bool {
+    let (host, domain) = (host.trim_end_matches('.'), domain.trim_start_matches('.'));
+    host.eq_ignore_ascii_case(domain) ||
+        host.len() > domain.len() &&
+        host[host.len() - domain.len()..].eq_ignore_ascii_case(domain) &&
+        host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+/// Wraps text that keeps its own line breaks and spacing, splitting lines wider than `width`.
+/// Lines are paired with their char offsets like `wrap_text`.
+pub fn wrap_preformatted(text: &str, width: usize) -> Vec<(usize, String)> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split('\n') {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() { lines.push((offset, String::new())) }
+        for (c, chunk) in chars.chunks(width).enumerate() {
+            lines.push((offset + c * width, chunk.iter().collect()));
+        }
+        offset += chars.len() + 1;
+    }
+    lines
+}
+
+/// Parses a CSS colour as reported by `getComputedStyle`, e.g. "rgb(0, 0, 0)" or "rgba(0, 0, 0, 0.5)",
+/// or as written in a stylesheet, in hex or as one of the basic colour names.
+/// Returns None for fully transparent colours.
+pub fn parse_css_color(color: &str) -> Option<(u8, u8, u8)> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') { return parse_hex_color(hex) }
+    if let Some((_, rgb)) = NAMED_COLORS.iter().find(|(name, _)| name.eq_ignore_ascii_case(color)) { return Some(*rgb) }
+    let args = color.strip_prefix("rgba(").or_else(|| color.strip_prefix("rgb("))?.strip_suffix(')')?;
+    let parts: Vec<f32> = args.split([',', ' ', '/']).filter(|p| !p.is_empty()).map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    match parts[..] {
+        [_, _, _, 0.0] => None,
+        [r, g, b, ..] => Some((r as u8, g as u8, b as u8)),
+        _ => None,
+    }
+}
+
+/// The basic CSS colour names.
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 16] = [
+    ("black", (0, 0, 0)), ("silver", (192, 192, 192)), ("gray", (128, 128, 128)), ("white", (255, 255, 255)),
+    ("maroon", (128, 0, 0)), ("red", (255, 0, 0)), ("purple", (128, 0, 128)), ("fuchsia", (255, 0, 255)),
+    ("green", (0, 128, 0)), ("lime", (0, 255, 0)), ("olive", (128, 128, 0)), ("yellow", (255, 255, 0)),
+    ("navy", (0, 0, 128)), ("blue", (0, 0, 255)), ("teal", (0, 128, 128)), ("aqua", (0, 255, 255)),
+];
+
+/// Parses the digits of a #rgb, #rgba, #rrggbb or #rrggbbaa colour.
+fn parse_hex_color(hex: &str) -> Option<(u8, u8, u8)> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) { return None }
+    let channels: Vec<u8> = match hex.len() {
+        3 | 4 => hex.chars().map(|c| c.to_digit(16).map(|d| d as u8 * 17)).collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok()).collect::<Option<_>>()?,
+        _ => return None,
+    };
+    match channels[..] {
+        [_, _, _, 0] => None,
+        [r, g, b, ..] => Some((r, g, b)),
+        _ => None,
+    }
+}
+
+/// Finds the nearest colour in the xterm 256-colour palette's colour cube and grayscale ramp.
+pub fn nearest_indexed_color((r, g, b): (u8, u8, u8)) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |c: u8| (0..6).min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs()).unwrap_or(0);
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = ((average.saturating_sub(8)) / 10).min(23) as u8;
+    let gray = 8 + gray_index * 10;
+
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        (cr as i32 - r as i32).pow(2) + (cg as i32 - g as i32).pow(2) + (cb as i32 - b as i32).pow(2)
+    };
+    if distance((gray, gray, gray)) < distance(cube) { 232 + gray_index }
+    else { 16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8 }
+}
+
+/// Directory Etymon keeps its data in, following the XDG base directory spec.
+pub fn data_dir() -> std::path::PathBuf {
+    match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(data) => std::path::PathBuf::from(data).join("etymon"),
+        None => home_dir().join(".local/share/etymon"),
+    }
+}
+
+/// Directory Etymon's config file is kept in, following the XDG base directory spec.
+pub fn config_dir() -> std::path::PathBuf {
+    match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(config) => std::path::PathBuf::from(config).join("etymon"),
+        None => home_dir().join(".config/etymon"),
+    }
+}
+
+/// Reads a path typed by the user, expanding a leading ~ to the home directory.
+pub fn expand_home(path: &str) -> std::path::PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest),
+        None if path == "~" => home_dir(),
+        None => std::path::PathBuf::from(path),
+    }
+}
+
+/// The user's home directory, or the working directory if HOME isn't set.
+pub fn home_dir() -> std::path::PathBuf {
+    std::env::var_os("HOME").map(std::path::PathBuf::from).unwrap_or_default()
+}
+
+/// Guesses the MIME type of a file, or a URL to one, from its extension.
+pub fn guess_mime(path: &str) -> Option<&'static str> {
+    let file = path.split(['?', '#']).next().unwrap_or_default().rsplit('/').next().unwrap_or_default();
+    let extension = file.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "m3u8" => "application/vnd.apple.mpegurl",
+        "mpd" => "application/dash+xml",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "epub" => "application/epub+zip",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "xz" => "application/x-xz",
+        "7z" => "application/x-7z-compressed",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" | "md" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        _ => return None,
+    })
+}
+
+/// Finds the handler for a MIME type: an exact match, then a type/* wildcard, then */*.
+pub fn handler_for<'a>(handlers: &'a std::collections::HashMap<String, String>, mime: &str) -> Option<&'a str> {
+    let mime = mime.to_ascii_lowercase();
+    let wildcard = format!("{}/*", mime.split('/').next().unwrap_or_default());
+    [mime.as_str(), wildcard.as_str(), "*/*"].iter()
+        .find_map(|m| handlers.get(*m))
+        .map(String::as_str)
+}
+
+/// Builds a handler command, putting the target in place of {url}, or after the arguments if there is no {url}.
+pub fn handler_command(handler: &str, target: &str) -> Option<std::process::Command> {
+    let mut words = handler.split_whitespace();
+    let mut command = std::process::Command::new(words.next()?);
+    let mut placed = false;
+    for word in words {
+        placed |= word.contains("{url}");
+        command.arg(word.replace("{url}", target));
+    }
+    if !placed { command.arg(target); }
+    Some(command)
+}
+
+/// Splits typed arguments on whitespace, keeping text in single or double quotes together.
+pub fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for ch in text.chars() {
+        match (quote, ch) {
+            (Some(q), ch) if ch == q => quote = None,
+            (Some(_), ch) => current.get_or_insert_with(String::new).push(ch),
+            (None, '\'' | '"') => { quote = Some(ch); current.get_or_insert_with(String::new); },
+            (None, ch) if ch.is_whitespace() => args.extend(current.take()),
+            (None, ch) => current.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    args.extend(current);
+    args
+}
+
+/// Starts a handler on a URL or file in the background, detached from the terminal.
+pub fn spawn_handler(handler: &str, target: &str) -> Result<(), anyhow::Error> {
+    use std::process::Stdio;
+    let mut command = handler_command(handler, target).ok_or_else(|| anyhow::anyhow!("Handler command is empty"))?;
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    // Reaps the handler once it exits.
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_quoted_args() {
+        assert_eq!(split_args(" -H 'X-A: 1'  \"two words\" -o '' url "), ["-H", "X-A: 1", "two words", "-o", "", "url"]);
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn builds_handler_commands() {
+        let args = |c: std::process::Command| c.get_args().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>();
+        let command = handler_command("mpv --no-video", "https://example.com/a.mp3").unwrap();
+        assert_eq!(command.get_program(), "mpv");
+        assert_eq!(args(command), ["--no-video", "https://example.com/a.mp3"]);
+        let command = handler_command("vlc --play-and-exit {url} --quiet", "https://example.com/a.mp4").unwrap();
+        assert_eq!(args(command), ["--play-and-exit", "https://example.com/a.mp4", "--quiet"]);
+        assert!(handler_command("  ", "https://example.com").is_none());
+    }
+
+    #[test]
+    fn wraps_preformatted_lines() {
+        assert_eq!(wrap_preformatted("ab\n\nabcdefg", 3),
+            [(0, "ab".to_owned()), (3, String::new()), (4, "abc".to_owned()), (7, "def".to_owned()), (10, "g".to_owned())]);
+        assert_eq!(wrap_preformatted("  x", 0), [(0, " ".to_owned()), (1, " ".to_owned()), (2, "x".to_owned())]);
+    }
+
+    #[test]
+    fn parses_css_colors() {
+        assert_eq!(parse_css_color("rgb(255, 0, 10)"), Some((255, 0, 10)));
+        assert_eq!(parse_css_color(" rgba(1, 2, 3, 0.5) "), Some((1, 2, 3)));
+        assert_eq!(parse_css_color("rgb(1 2 3 / 0.25)"), Some((1, 2, 3)));
+        assert_eq!(parse_css_color("#36c"), Some((51, 102, 204)));
+        assert_eq!(parse_css_color("#336699"), Some((51, 102, 153)));
+        assert_eq!(parse_css_color("Navy"), Some((0, 0, 128)));
+        // Fully transparent colours draw nothing.
+        for transparent in ["rgba(0, 0, 0, 0)", "rgb(0 0 0 / 0)", "#0000", "#33669900", "transparent"] {
+            assert_eq!(parse_css_color(transparent), None, "{}", transparent);
+        }
+        for bad in ["", "rgb(1, 2)", "#12", "#ggg", "hsl(0, 100%, 50%)", "rebeccapurple"] {
+            assert_eq!(parse_css_color(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn maps_to_indexed_colors() {
+        assert_eq!(nearest_indexed_color((0, 0, 0)), 16);
+        assert_eq!(nearest_indexed_color((255, 255, 255)), 231);
+        assert_eq!(nearest_indexed_color((255, 0, 0)), 196);
+        assert_eq!(nearest_indexed_color((95, 135, 175)), 67);
+        assert_eq!(nearest_indexed_color((100, 130, 170)), 67);
+        // Grays between the cube's levels go to the grayscale ramp.
+        assert_eq!(nearest_indexed_color((128, 128, 128)), 244);
+        assert_eq!(nearest_indexed_color((10, 10, 10)), 232);
+    }
+
+    #[test]
+    fn guesses_mime_types() {
+        assert_eq!(guess_mime("https://example.com/report.PDF?download=1"), Some("application/pdf"));
+        assert_eq!(guess_mime("/media/trailer.webm#t=10"), Some("video/webm"));
+        assert_eq!(guess_mime("archive.tar.gz"), Some("application/gzip"));
+        assert_eq!(guess_mime("https://example.com/download"), None);
+    }
+}